@@ -2,6 +2,9 @@
 
 extern crate rlibc as _;
 
+#[global_allocator]
+static ALLOCATOR: allocator::Allocator = allocator::Allocator;
+
 #[panic_handler]
 fn panic(_: &core::panic::PanicInfo<'_>) -> ! {
     syscalls::test_user_app_failed();
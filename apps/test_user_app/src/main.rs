@@ -3,7 +3,620 @@
 
 extern crate test_user_app as _;
 
+use {
+    allocator::{format, BTreeMap, String, Vec},
+    core::{
+        arch::asm,
+        convert::{TryFrom, TryInto},
+        hint, ptr, slice,
+        time::Duration,
+    },
+    ipc::{
+        message::{Body, Header},
+        Error, Message, ReceiveFrom,
+    },
+    os_units::Bytes,
+    pid::{predefined, Pid},
+    posix::{
+        signal::{SIGILL, SIGSEGV},
+        sys::{
+            mman::{
+                self, MAP_ANONYMOUS, MAP_FAILED, MAP_PRIVATE, MAP_SHARED, PROT_READ, PROT_WRITE,
+            },
+            wait::{self, WNOHANG},
+        },
+        time::{self, Timespec, CLOCK_MONOTONIC},
+        unistd,
+    },
+    syscalls::{
+        ExecError, ExecRequest, ForkError, GrantAccess, GrantError, IrqError, MemoryError,
+        Priority, ProcessName, SetPriorityError, SpawnError,
+    },
+    x86_64::VirtAddr,
+};
+
+// No process uses this PID.
+const UNUSED_PID: Pid = Pid::new(1000);
+
+// Longer than the size of the buffer of the tty, so the tty must copy it in multiple chunks.
+static LONG_STRING: &str =
+    "Antei is an experimental Operating System for the `x86_64` architecture \
+written in stable Rust. This string is long enough to be copied chunk by chunk.\n";
+
+// The arguments passed to the process executed by `exec_passes_arguments`.
+const EXEC_ARGV: [&str; 2] = ["test_user_app", "exec"];
+const EXEC_ENVP: [&str; 1] = ["KEY=VALUE"];
+
+// Larger than the initial stack, so the stack must grow to hold a buffer of this size.
+const LARGE_BUFFER_BYTES: usize = 64 * 1024;
+
+// Not backed by the executable file, so its pages are mapped when they are accessed.
+static mut LARGE_BSS: [u8; LARGE_BUFFER_BYTES] = [0; LARGE_BUFFER_BYTES];
+
+// Not in any region of this process.
+const UNMAPPED_ADDR: usize = 0x5000_0000_0000;
+
+// The vector of the first input of the I/O APIC.
+const IRQ_VECTOR: u8 = 0x30;
+
+// The higher half is the kernel's.
+const KERNEL_ADDR: u64 = 0xffff_8000_0000_0000;
+
+const PAGE_SIZE: usize = 4096;
+
+// Not page-aligned so that the break is not at a page boundary.
+const HEAP_GROWTH_BYTES: usize = PAGE_SIZE * 3 + 100;
+
+// Large enough that the allocator grows the heap several times.
+const NUM_OF_HEAP_ELEMENTS: usize = 100_000;
+
 #[no_mangle]
-fn main() -> ! {
+extern "sysv64" fn main(argc: usize, argv: *const *const u8, envp: *const *const u8) -> ! {
+    // This process is started without any arguments, so the arguments mean that it is executed by
+    // `exec_passes_arguments`.
+    if argc > 0 {
+        // SAFETY: The kernel passes valid arguments.
+        unsafe {
+            check_exec_arguments(argc, argv, envp);
+        }
+    }
+
+    syscalls::write(LONG_STRING);
+
+    ipc_errors();
+
+    receive_buffer_must_be_writable();
+
+    fork_copies_pages_on_write();
+
+    exec_passes_arguments();
+
+    wait_collects_exit_status();
+
+    set_priority_requires_privilege();
+
+    kernel_calls_require_privilege();
+
+    copy_data_from_requires_a_grant();
+
+    stale_pid_does_not_address_new_process();
+
+    sleep_advances_monotonic_clock();
+
+    fault_terminates_only_the_process();
+
+    pages_are_mapped_on_demand();
+
+    access_outside_regions_terminates_the_process();
+
+    heap_grows_and_shrinks();
+
+    anonymous_memory_is_mapped_and_protected();
+
+    // After `heap_grows_and_shrinks` because the allocator moves the break.
+    collections_grow_on_the_heap();
+
     syscalls::test_user_app_succeed();
 }
+
+fn ipc_errors() {
+    let r = ipc::try_send(UNUSED_PID, Message::default());
+    assert_eq!(r, Err(Error::NoSuchProcess(UNUSED_PID.into())));
+
+    let r = ipc::try_send(predefined::TEST_USER_APP, Message::default());
+    assert_eq!(r, Err(Error::Deadlock));
+
+    let r = ipc::try_receive(predefined::TEST_USER_APP.into());
+    assert_eq!(r, Err(Error::Deadlock));
+
+    // TEST_2 never receives a message from this process.
+    let r = ipc::send_nonblock(predefined::TEST_2, Message::default());
+    assert_eq!(r, Err(Error::WouldBlock));
+
+    // TEST_2 never sends a message to this process.
+    let r = ipc::receive_timeout(ReceiveFrom::Pid(predefined::TEST_2), 1);
+    assert_eq!(r, Err(Error::Timeout));
+}
+
+fn receive_buffer_must_be_writable() {
+    let read_only = mman::mmap(
+        ptr::null_mut(),
+        PAGE_SIZE,
+        PROT_READ,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        -1,
+        0,
+    );
+    assert_ne!(read_only, MAP_FAILED);
+
+    let r = receive_nonblock_into(read_only as u64);
+    assert_eq!(r, Err(Error::InvalidAddress));
+
+    let r = receive_nonblock_into(KERNEL_ADDR);
+    assert_eq!(r, Err(Error::InvalidAddress));
+
+    // Not aligned as `Message`, so it may cross a page boundary.
+    let buffer = [Message::default(); 2];
+    let r = receive_nonblock_into(buffer.as_ptr() as u64 + 8);
+    assert_eq!(r, Err(Error::InvalidAddress));
+
+    assert_eq!(mman::munmap(read_only, PAGE_SIZE), 0);
+}
+
+// `ipc::receive_nonblock` always passes a valid buffer, so this function calls the system call
+// directly.
+fn receive_nonblock_into(buffer: u64) -> Result<(), Error> {
+    // Any sender.
+    let from = u64::MAX;
+
+    let r: u64;
+
+    // SAFETY: The kernel validates `buffer` before writing to it.
+    unsafe {
+        asm!("syscall",
+        in("rdi") ipc::syscalls::Ty::ReceiveNonBlock as u64,
+        in("rsi") from,
+        in("rdx") buffer,
+        in("r10") 0,
+        lateout("rax") r,
+        clobber_abi("sysv64"));
+    }
+
+    Error::decode(r)
+}
+
+fn fork_copies_pages_on_write() {
+    let mut value = 1_u64;
+
+    let pid = posix::unistd::fork();
+    assert!(pid >= 0, "Failed to fork.");
+
+    if pid == 0 {
+        // SAFETY: `value` is a valid variable. The volatile access ensures the page is written.
+        unsafe {
+            ptr::write_volatile(&mut value, 2);
+        }
+
+        ipc::send(
+            predefined::TEST_USER_APP,
+            Message {
+                header: Header::default(),
+                body: Body(value, 0, 0, 0, 0),
+            },
+        );
+
+        syscalls::exit(0);
+    }
+
+    let child = Pid::new(pid.try_into().unwrap());
+
+    let message = ipc::receive(ReceiveFrom::Pid(child));
+    assert_eq!(message.body.0, 2, "The child did not see its own write.");
+
+    // SAFETY: `value` is a valid variable.
+    let value = unsafe { ptr::read_volatile(&value) };
+    assert_eq!(value, 1, "The write of the child is visible to the parent.");
+
+    assert_eq!(wait::waitpid(pid, None, 0), pid);
+}
+
+fn exec_passes_arguments() {
+    let pid = posix::unistd::fork();
+    assert!(pid >= 0, "Failed to fork.");
+
+    if pid == 0 {
+        let e = syscalls::exec("test_user_app", &EXEC_ARGV, &EXEC_ENVP);
+
+        panic!("Failed to execute `test_user_app`: {:?}", e);
+    }
+
+    let child = Pid::new(pid.try_into().unwrap());
+
+    let message = ipc::receive(ReceiveFrom::Pid(child));
+    assert_eq!(
+        message.body.0, 1,
+        "The executed image received wrong arguments."
+    );
+
+    assert_eq!(wait::waitpid(pid, None, 0), pid);
+}
+
+fn wait_collects_exit_status() {
+    // All the children are reaped by the previous tests.
+    assert_eq!(wait::waitpid(-1, None, WNOHANG), -1);
+
+    let pid = posix::unistd::fork();
+    assert!(pid >= 0, "Failed to fork.");
+
+    if pid == 0 {
+        // Do not exit until the parent confirms that this process is alive.
+        let _ = ipc::receive(ReceiveFrom::Pid(predefined::TEST_USER_APP));
+
+        syscalls::exit(3);
+    }
+
+    assert_eq!(wait::waitpid(pid, None, WNOHANG), 0);
+
+    ipc::send(Pid::new(pid.try_into().unwrap()), Message::default());
+
+    let mut status = 0;
+
+    assert_eq!(wait::waitpid(pid, Some(&mut status), 0), pid);
+    assert!(wait::wifexited(status));
+    assert_eq!(wait::wexitstatus(status), 3);
+}
+
+fn set_priority_requires_privilege() {
+    // Only INIT may change the priorities, whatever the priority of the caller is.
+    let r = syscalls::set_priority(predefined::TEST_USER_APP, Priority::SERVER);
+    assert_eq!(r, Err(SetPriorityError::PermissionDenied));
+}
+
+// Only PM and the VM server may ask the kernel to manage processes, and only the drivers may take
+// the interrupts.
+fn kernel_calls_require_privilege() {
+    let name = ProcessName::new("test_user_app").unwrap();
+    assert_eq!(
+        syscalls::create_process(name),
+        Err(SpawnError::PermissionDenied)
+    );
+
+    assert!(syscalls::destroy_process(predefined::INIT).is_err());
+
+    let this = predefined::TEST_USER_APP;
+    assert_eq!(
+        syscalls::fork_process(this),
+        Err(ForkError::PermissionDenied)
+    );
+    assert_eq!(
+        syscalls::duplicate_process(this),
+        Err(ForkError::PermissionDenied)
+    );
+
+    let request = ExecRequest::from_body(&Body(0, LONG_STRING.as_ptr() as _, 0, 0, 0)).unwrap();
+    assert_eq!(
+        syscalls::exec_process(this, request),
+        Err(ExecError::PermissionDenied)
+    );
+
+    assert_eq!(
+        syscalls::resize_heap(this, None),
+        Err(MemoryError::PermissionDenied)
+    );
+
+    assert_eq!(
+        syscalls::register_irq(IRQ_VECTOR),
+        Err(IrqError::PermissionDenied)
+    );
+}
+
+fn copy_data_from_requires_a_grant() {
+    let this = predefined::TEST_USER_APP;
+
+    let mut buffer = [0_u8; 32];
+
+    let src = VirtAddr::from_ptr(LONG_STRING.as_ptr());
+    let dst = VirtAddr::from_ptr(buffer.as_mut_ptr());
+    let len = Bytes::new(buffer.len());
+
+    // SAFETY: `buffer` is valid.
+    let copy = || unsafe { syscalls::copy_data_from(this, src, dst, len) };
+
+    assert_eq!(copy(), Err(GrantError::InvalidGrant));
+
+    // SAFETY: `LONG_STRING` is valid until the grant is revoked.
+    let grant = unsafe { syscalls::create_grant(this, src, len, GrantAccess::Read) };
+    let grant = grant.expect("Failed to create a grant.");
+
+    assert_eq!(copy(), Ok(()));
+    assert_eq!(&buffer, &LONG_STRING.as_bytes()[..buffer.len()]);
+
+    syscalls::revoke_grant(grant).expect("Failed to revoke a grant.");
+}
+
+// Reports to the parent that the arguments are correct, and exits.
+//
+// # Safety
+//
+// `argv` and `envp` must be the arrays passed by the kernel.
+unsafe fn check_exec_arguments(argc: usize, argv: *const *const u8, envp: *const *const u8) -> ! {
+    assert_eq!(argc, EXEC_ARGV.len());
+
+    for (i, expected) in EXEC_ARGV.iter().enumerate() {
+        // SAFETY: The caller ensures that `argv` contains `argc` strings.
+        assert_eq!(unsafe { c_str(*argv.add(i)) }, expected.as_bytes());
+    }
+
+    // SAFETY: The array is null-terminated.
+    assert!(unsafe { *argv.add(argc) }.is_null());
+
+    for (i, expected) in EXEC_ENVP.iter().enumerate() {
+        // SAFETY: The caller ensures that `envp` contains the environment variables.
+        assert_eq!(unsafe { c_str(*envp.add(i)) }, expected.as_bytes());
+    }
+
+    // SAFETY: The array is null-terminated.
+    assert!(unsafe { *envp.add(EXEC_ENVP.len()) }.is_null());
+
+    ipc::send(
+        predefined::TEST_USER_APP,
+        Message {
+            header: Header::default(),
+            body: Body(1, 0, 0, 0, 0),
+        },
+    );
+
+    syscalls::exit(0);
+}
+
+// Returns the bytes of the null-terminated string `s` without the null character.
+//
+// # Safety
+//
+// `s` must point to a null-terminated string.
+unsafe fn c_str<'a>(s: *const u8) -> &'a [u8] {
+    let mut len = 0;
+
+    // SAFETY: The caller ensures that the string is null-terminated.
+    while unsafe { *s.add(len) } != 0 {
+        len += 1;
+    }
+
+    // SAFETY: The `len` bytes from `s` are readable.
+    unsafe { slice::from_raw_parts(s, len) }
+}
+
+fn stale_pid_does_not_address_new_process() {
+    let pid = posix::unistd::fork();
+    assert!(pid >= 0, "Failed to fork.");
+
+    if pid == 0 {
+        syscalls::exit(0);
+    }
+
+    assert_eq!(wait::waitpid(pid, None, 0), pid);
+
+    let stale = Pid::new(pid.try_into().unwrap());
+
+    let pid = posix::unistd::fork();
+    assert!(pid >= 0, "Failed to fork.");
+
+    if pid == 0 {
+        // Do not exit until the parent tries to send a message with the stale PID.
+        let _ = ipc::receive(ReceiveFrom::Pid(predefined::TEST_USER_APP));
+
+        syscalls::exit(0);
+    }
+
+    let child = Pid::new(pid.try_into().unwrap());
+
+    // The new child reuses the slot of the reaped one.
+    assert_eq!(child.index(), stale.index());
+    assert_ne!(child, stale);
+
+    let r = ipc::try_send(stale, Message::default());
+    assert_eq!(r, Err(Error::NoSuchProcess(stale.into())));
+
+    ipc::send(child, Message::default());
+
+    assert_eq!(wait::waitpid(pid, None, 0), pid);
+}
+
+fn sleep_advances_monotonic_clock() {
+    const SLEEP_NSEC: i64 = 50_000_000;
+
+    let mut start = Timespec::default();
+    assert_eq!(time::clock_gettime(CLOCK_MONOTONIC, &mut start), 0);
+
+    let invalid = Timespec {
+        tv_sec: 0,
+        tv_nsec: -1,
+    };
+    assert_eq!(time::nanosleep(&invalid, None), -1);
+
+    let request = Timespec {
+        tv_sec: 0,
+        tv_nsec: SLEEP_NSEC,
+    };
+    assert_eq!(time::nanosleep(&request, None), 0);
+
+    let mut end = Timespec::default();
+    assert_eq!(time::clock_gettime(CLOCK_MONOTONIC, &mut end), 0);
+
+    let elapsed = (end.tv_sec - start.tv_sec) * 1_000_000_000 + end.tv_nsec - start.tv_nsec;
+    assert!(elapsed >= SLEEP_NSEC, "Woke up too early: {} ns", elapsed);
+
+    let start = syscalls::monotonic_time();
+
+    syscalls::sleep(Duration::from_millis(20));
+
+    assert!(syscalls::monotonic_time() - start >= Duration::from_millis(20));
+}
+
+fn fault_terminates_only_the_process() {
+    let pid = posix::unistd::fork();
+    assert!(pid >= 0, "Failed to fork.");
+
+    if pid == 0 {
+        // SAFETY: The invalid opcode exception terminates this process.
+        unsafe {
+            asm!("ud2", options(noreturn));
+        }
+    }
+
+    let mut status = 0;
+
+    assert_eq!(wait::waitpid(pid, Some(&mut status), 0), pid);
+    assert!(wait::wifsignaled(status));
+    assert_eq!(wait::wtermsig(status), SIGILL);
+}
+
+fn pages_are_mapped_on_demand() {
+    // SAFETY: Only this function accesses `LARGE_BSS`.
+    let bss = unsafe { &mut *ptr::addr_of_mut!(LARGE_BSS) };
+
+    for b in bss.iter_mut() {
+        // SAFETY: `b` is a valid reference. The volatile accesses ensure the pages are touched.
+        unsafe {
+            assert_eq!(ptr::read_volatile(b), 0, "`.bss` is not zeroed.");
+
+            ptr::write_volatile(b, 1);
+        }
+    }
+
+    assert_eq!(fill_large_stack_buffer(), 0xff);
+}
+
+// Returns the last byte of a buffer on the stack which is larger than the initial stack.
+#[inline(never)]
+fn fill_large_stack_buffer() -> u8 {
+    let mut buffer = [0_u8; LARGE_BUFFER_BYTES];
+
+    for (i, b) in buffer.iter_mut().enumerate() {
+        // SAFETY: `b` is a valid reference. The volatile access ensures the pages are touched.
+        unsafe {
+            ptr::write_volatile(b, i as u8);
+        }
+    }
+
+    hint::black_box(buffer)[LARGE_BUFFER_BYTES - 1]
+}
+
+fn access_outside_regions_terminates_the_process() {
+    // SAFETY: The page fault terminates the child process.
+    assert_child_faults(|| unsafe { ptr::write_volatile(UNMAPPED_ADDR as *mut u8, 0) });
+}
+
+fn heap_grows_and_shrinks() {
+    let start = unistd::sbrk(0);
+
+    assert_eq!(unistd::sbrk(HEAP_GROWTH_BYTES.try_into().unwrap()), start);
+
+    // SAFETY: The heap is grown to contain the region.
+    let heap = unsafe { slice::from_raw_parts_mut(start, HEAP_GROWTH_BYTES) };
+
+    fill_zeroed_memory(heap);
+
+    let end = heap.as_mut_ptr_range().end;
+
+    assert_eq!(
+        unistd::sbrk(-isize::try_from(HEAP_GROWTH_BYTES).unwrap()),
+        end
+    );
+    assert_eq!(unistd::sbrk(0), start);
+
+    // The heap does not extend below its start.
+    assert_eq!(unistd::brk(start.wrapping_sub(PAGE_SIZE)), -1);
+}
+
+fn anonymous_memory_is_mapped_and_protected() {
+    let len = PAGE_SIZE * 2;
+
+    let addr = mman::mmap(
+        ptr::null_mut(),
+        len,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        -1,
+        0,
+    );
+    assert_ne!(addr, MAP_FAILED);
+
+    // SAFETY: The region is mapped and writable.
+    fill_zeroed_memory(unsafe { slice::from_raw_parts_mut(addr, len) });
+
+    assert_eq!(mman::mprotect(addr, len, PROT_READ), 0);
+
+    // SAFETY: The region is mapped.
+    assert_eq!(unsafe { ptr::read_volatile(addr) }, 1);
+
+    // SAFETY: The write to the read-only page terminates the child process.
+    assert_child_faults(|| unsafe { ptr::write_volatile(addr, 2) });
+
+    assert_eq!(mman::munmap(addr, len), 0);
+
+    // SAFETY: The read from the unmapped page terminates the child process.
+    assert_child_faults(|| unsafe {
+        ptr::read_volatile(addr);
+    });
+
+    let shared = mman::mmap(
+        ptr::null_mut(),
+        len,
+        PROT_READ,
+        MAP_SHARED | MAP_ANONYMOUS,
+        -1,
+        0,
+    );
+    assert_eq!(shared, MAP_FAILED, "The shared mappings are not supported.");
+}
+
+fn collections_grow_on_the_heap() {
+    let v: Vec<usize> = (0..NUM_OF_HEAP_ELEMENTS).collect();
+    assert!(v.iter().enumerate().all(|(i, x)| i == *x));
+
+    let mut m = BTreeMap::new();
+
+    for i in (0..NUM_OF_HEAP_ELEMENTS).step_by(1000) {
+        m.insert(i, format!("{}", i));
+    }
+
+    assert_eq!(m.len(), NUM_OF_HEAP_ELEMENTS / 1000);
+    assert_eq!(m.get(&42000).map(String::as_str), Some("42000"));
+
+    drop(v);
+
+    // Freeing `v` does not break the other allocations.
+    let s: String = m.values().map(String::as_str).collect();
+    assert!(s.starts_with("0100020003000"));
+}
+
+// Checks that all bytes of `memory` are zero, then sets them to `1`.
+fn fill_zeroed_memory(memory: &mut [u8]) {
+    for b in memory {
+        // SAFETY: `b` is a valid reference. The volatile accesses ensure the pages are touched.
+        unsafe {
+            assert_eq!(ptr::read_volatile(b), 0, "The memory is not zeroed.");
+
+            ptr::write_volatile(b, 1);
+        }
+    }
+}
+
+// Runs `f` in a child process, and checks that a memory fault terminates the child.
+fn assert_child_faults(f: impl FnOnce()) {
+    let pid = posix::unistd::fork();
+    assert!(pid >= 0, "Failed to fork.");
+
+    if pid == 0 {
+        f();
+
+        unreachable!("The child process did not fault.");
+    }
+
+    let mut status = 0;
+
+    assert_eq!(wait::waitpid(pid, Some(&mut status), 0), pid);
+    assert!(wait::wifsignaled(status));
+    assert_eq!(wait::wtermsig(status), SIGSEGV);
+}
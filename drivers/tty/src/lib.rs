@@ -9,10 +9,11 @@ mod writer;
 #[doc(hidden)]
 pub use writer::_print;
 use {
-    core::{convert::TryInto, str},
+    core::{cmp, convert::TryInto, str},
     ipc::{Message, ReceiveFrom},
     num_traits::FromPrimitive,
     os_units::Bytes,
+    syscalls::GrantId,
     x86_64::VirtAddr,
 };
 
@@ -39,24 +40,56 @@ fn loop_iteration() {
 }
 
 fn handle_write(message: &Message) {
-    let src = VirtAddr::new(message.body.1);
+    let grant = GrantId::from_u64(message.body.1);
     let len = Bytes::new(message.body.2.try_into().unwrap());
 
+    if print_granted_str(grant, len).is_err() {
+        println!("Received non-UTF-8 string.");
+    }
+
+    ipc::send(message.header.sender_pid, Message::default());
+}
+
+// The string is copied chunk by chunk. A multi-byte character may be split between two chunks, so
+// the incomplete bytes at the end of a chunk are moved to the head of the buffer and printed with
+// the next chunk.
+fn print_granted_str(grant: GrantId, len: Bytes) -> Result<(), ()> {
     let mut buffer = [0_u8; 128];
 
-    let dst_addr = VirtAddr::from_ptr(buffer.as_mut_ptr());
+    let mut pending = 0;
+    let mut offset = 0;
+
+    while offset < len.as_usize() {
+        let n = cmp::min(len.as_usize() - offset, buffer.len() - pending);
+
+        let dst = VirtAddr::from_ptr(buffer[pending..].as_mut_ptr());
 
-    unsafe {
-        syscalls::copy_data_from(message.header.sender_pid, src, dst_addr, len);
+        // SAFETY: `dst` is valid for writes of `n` bytes.
+        let r = unsafe { syscalls::copy_from_grant(grant, Bytes::new(offset), dst, Bytes::new(n)) };
+        r.map_err(|_| ())?;
+
+        offset += n;
+
+        let filled = pending + n;
+
+        let valid = match str::from_utf8(&buffer[..filled]) {
+            Ok(_) => filled,
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => return Err(()),
+        };
+
+        // SAFETY: `str::from_utf8` ensures that the first `valid` bytes are a valid UTF-8 string.
+        print!("{}", unsafe { str::from_utf8_unchecked(&buffer[..valid]) });
+
+        buffer.copy_within(valid..filled, 0);
+        pending = filled - valid;
     }
 
-    if let Ok(s) = str::from_utf8(&buffer[..len.as_usize()]) {
-        print!("{}", s);
+    if pending == 0 {
+        Ok(())
     } else {
-        println!("Received non-UTF-8 string.");
+        Err(())
     }
-
-    ipc::send(message.header.sender_pid, Message::default());
 }
 
 #[panic_handler]
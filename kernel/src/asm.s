@@ -4,8 +4,23 @@
 .code64
 .intel_syntax noprefix
 
-.macro  generic_handler vector fxsave_offset
+// The kinds of the handlers. A handler of `HANDLER` calls
+// `interrupt_handler_\vector`. A handler of `IRQ` calls
+// `interrupt_handler_irq` with the vector. A handler of `EXCEPTION` calls
+// `handle_exception` with the vector, the error code (or 0 if the exception
+// does not push it), and the address of the interrupt stack frame.
+.equ HANDLER, 0
+.equ IRQ, 1
+.equ EXCEPTION, 2
+
+.macro  generic_handler vector fxsave_offset error_code_size kind=HANDLER
+.if \kind == IRQ
+.extern interrupt_handler_irq
+.elseif \kind == EXCEPTION
+.extern handle_exception
+.else
 .extern interrupt_handler_\vector
+.endif
 .global asm_interrupt_handler_\vector
 
 asm_interrupt_handler_\vector:
@@ -31,7 +46,22 @@ asm_interrupt_handler_\vector:
 
 	fxsave [rsp]
 
+	.if \kind == IRQ
+	mov  edi, \vector
+	call interrupt_handler_irq
+	.elseif \kind == EXCEPTION
+	mov  edi, \vector
+	.if \error_code_size
+	mov  rsi, [rbp+8]
+	lea  rdx, [rbp+16]
+	.else
+	xor  esi, esi
+	lea  rdx, [rbp+8]
+	.endif
+	call handle_exception
+	.else
 	call interrupt_handler_\vector
+	.endif
 
 	fxrstor [rsp]
 
@@ -50,19 +80,68 @@ asm_interrupt_handler_\vector:
 	mov rsp, rbp
 	pop rbp
 
+	// `iretq` does not remove the error code.
+	add rsp, \error_code_size
+
 	iretq
 	.endm
 
 	.macro handler vector
-	generic_handler \vector 8
+	generic_handler \vector 8 0
 	.endm
 
 	.macro handler_with_error_code vector
-	generic_handler \vector 0
+	generic_handler \vector 0 8
+	.endm
+
+	.macro irq_handler vector
+	generic_handler \vector 8 0 IRQ
+	.endm
+
+	.macro exception_handler vector
+	generic_handler \vector 8 0 EXCEPTION
+	.endm
+
+	.macro exception_handler_with_error_code vector
+	generic_handler \vector 0 8 EXCEPTION
 	.endm
 
-	handler_with_error_code 0x0e
+	// The exceptions. The vectors 0x09, 0x0f, 0x16-0x1b, and 0x1f are
+	// reserved, but their handlers are defined to keep
+	// `asm_exception_handlers` indexed by the vectors.
+	.irp vector, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x09, 0x0f, 0x10, 0x12, 0x13, 0x14, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1f
+	exception_handler \vector
+	.endr
+
+	.irp vector, 0x08, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x11, 0x15, 0x1d, 0x1e
+	exception_handler_with_error_code \vector
+	.endr
+
 	handler 0x20
+	handler 0xf0
+
+	// The interrupts from the devices. See `ioapic::VECTOR_BASE` and
+	// `ioapic::NUM_OF_VECTORS`.
+	.irp vector, 0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x3b, 0x3c, 0x3d, 0x3e, 0x3f, 0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x4b, 0x4c, 0x4d, 0x4e, 0x4f
+	irq_handler \vector
+	.endr
+
+	.section .rodata
+	.global asm_exception_handlers
+	.global asm_irq_handlers
+
+	// The addresses of the handlers above in the order of the vectors.
+asm_exception_handlers:
+	.irp vector, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f
+	.quad asm_interrupt_handler_\vector
+	.endr
+
+asm_irq_handlers:
+	.irp vector, 0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x3b, 0x3c, 0x3d, 0x3e, 0x3f, 0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x4b, 0x4c, 0x4d, 0x4e, 0x4f
+	.quad asm_interrupt_handler_\vector
+	.endr
+
+	.text
 
 	.global asm_switch_context
 
@@ -102,6 +181,9 @@ asm_switch_context:
 
 	fxsave [rdi+0xc0]
 
+	// The old context is saved, so another processor may switch to it.
+	mov byte ptr [rdx], 0
+
 	mov rax, [rsi+0x00]
 	mov rbx, [rsi+0x08]
 	mov rcx, [rsi+0x10]
@@ -147,6 +229,7 @@ asm_handle_syscall:
 	// `rdi`: index
 	// `rsi`: a1
 	// `rdx`: a2
+	// `r10`: a3 (`rcx` is used by `syscall`.)
 
 	push rcx
 	push r11
@@ -157,17 +240,59 @@ asm_handle_syscall:
 	push rdi
 	push rsi
 	push rdx
+	push r10
 
 	call current_kernel_stack_bottom
 
+	pop r10
 	pop rdx
 	pop rsi
 	pop rdi
 
 	mov rsp, rax
 
+	// Save the registers to return to the user mode from a forked
+	// process. See `SyscallFrame`.
+	push rsi
+	push rdx
+	push rbp
+	push rbx
+	push r12
+	push r13
+	push r14
+	push r15
+
+	mov rcx, r10
+
 	call handle_syscall
 
+	add rsp, 0x40
+
+	mov rsp, rbp
+	pop rbp
+
+	pop r11
+	pop rcx
+
+	sysretq
+
+	.global asm_return_from_forked_syscall
+
+	// A forked process starts from here with `rsp` pointing to the
+	// `SyscallFrame` copied from the parent.
+asm_return_from_forked_syscall:
+	pop r15
+	pop r14
+	pop r13
+	pop r12
+	pop rbx
+	pop rbp
+
+	add rsp, 0x10
+
+	// The system call succeeded.
+	xor eax, eax
+
 	mov rsp, rbp
 	pop rbp
 
@@ -175,3 +300,106 @@ asm_handle_syscall:
 	pop rcx
 
 	sysretq
+
+	// The code to start an application processor. It is copied to a page
+	// below 1 MiB, and the processor starts it in real mode from the start
+	// of the page. See `smp::Trampoline`.
+	.global trampoline_start
+	.global trampoline_parameters
+	.global trampoline_end
+
+	.code16
+	.align 16
+
+trampoline_start:
+	cli
+	cld
+
+	mov ax, cs
+	mov ds, ax
+
+	// Fill the addresses which depend on where the code is copied.
+	xor ebx, ebx
+	mov bx, ax
+	shl ebx, 4
+
+	lea eax, [ebx+trampoline_gdt-trampoline_start]
+	mov [trampoline_gdtr_base-trampoline_start], eax
+
+	lea eax, [ebx+trampoline_long_mode-trampoline_start]
+	mov [trampoline_long_mode_addr-trampoline_start], eax
+
+	lgdt [trampoline_gdtr-trampoline_start]
+
+	// PAE, PGE, OSFXSR, and OSXMMEXCPT.
+	mov eax, 0x6a0
+	mov cr4, eax
+
+	mov eax, [trampoline_pml4-trampoline_start]
+	mov cr3, eax
+
+	// LME and NXE of IA32_EFER.
+	mov ecx, 0xc0000080
+	rdmsr
+	or  eax, 0x900
+	wrmsr
+
+	// PE, MP, ET, NE, WP, and PG. Enabling the protected mode and paging
+	// at once switches the processor from real mode to the compatibility
+	// mode.
+	mov eax, 0x80010033
+	mov cr0, eax
+
+	jmp fword ptr [trampoline_long_mode_addr-trampoline_start]
+
+	.code64
+
+trampoline_long_mode:
+	mov ax, 0x10
+	mov ds, ax
+	mov es, ax
+	mov ss, ax
+
+	mov rsp, [rip+trampoline_stack]
+	mov rdi, [rip+trampoline_kernel_pml4]
+
+	// The entry is in the higher half, which is mapped by both the page
+	// tables of the trampoline and the kernel's PML4.
+	call [rip+trampoline_entry]
+
+	ud2
+
+	.align 8
+
+trampoline_gdt:
+	.quad 0
+	// The 64-bit code segment.
+	.quad 0x00af9a000000ffff
+	// The data segment.
+	.quad 0x00cf92000000ffff
+
+trampoline_gdtr:
+	.word trampoline_gdtr-trampoline_gdt-1
+trampoline_gdtr_base:
+	.long 0
+
+trampoline_long_mode_addr:
+	.long 0
+	.word 0x08
+
+	// Written by the bootstrap processor. See `smp::Parameters`.
+	.align 8
+
+trampoline_parameters:
+trampoline_kernel_pml4:
+	.quad 0
+trampoline_stack:
+	.quad 0
+trampoline_entry:
+	.quad 0
+trampoline_pml4:
+	.long 0
+
+	.align 8
+
+trampoline_end:
@@ -1,5 +1,8 @@
 use {
-    crate::tss,
+    crate::{
+        smp::{self, MAX_CPUS},
+        tss,
+    },
     conquer_once::spin::OnceCell,
     x86_64::{
         instructions::{
@@ -10,8 +13,10 @@ use {
     },
 };
 
-static GDT: OnceCell<GlobalDescriptorTable> = OnceCell::uninit();
+// Each processor has its own GDT because it contains the descriptor of the TSS of the processor.
+static GDT: [OnceCell<GlobalDescriptorTable>; MAX_CPUS] = [const { OnceCell::uninit() }; MAX_CPUS];
 
+// The GDTs of all processors have the same layout, so the selectors are shared.
 static SELECTORS: OnceCell<Selectors> = OnceCell::uninit();
 
 struct Selectors {
@@ -39,7 +44,7 @@ pub(super) fn user_data_selector() -> SegmentSelector {
 }
 
 pub(super) fn init() {
-    init_gdt();
+    init_selectors(init_gdt());
     load();
     load_segments();
 
@@ -47,8 +52,19 @@ pub(super) fn init() {
     tests::main();
 }
 
-fn init_gdt() {
-    let r = GDT.try_init_once(|| {
+/// Initializes and loads the GDT of the current application processor.
+pub(super) fn init_ap() {
+    init_gdt();
+    load();
+    load_segments();
+}
+
+fn init_gdt() -> Selectors {
+    let mut selectors = None;
+
+    tss::init();
+
+    let r = GDT[smp::index()].try_init_once(|| {
         let mut gdt = GlobalDescriptorTable::new();
 
         let kernel_code = gdt.add_entry(Descriptor::kernel_code_segment());
@@ -57,7 +73,7 @@ fn init_gdt() {
         let user_code = gdt.add_entry(Descriptor::user_code_segment());
         let tss = gdt.add_entry(Descriptor::tss_segment(unsafe { tss::as_ref() }));
 
-        init_selectors(Selectors {
+        selectors = Some(Selectors {
             kernel_code,
             kernel_data,
             user_code,
@@ -68,6 +84,8 @@ fn init_gdt() {
         gdt
     });
     r.expect("Failed to initialize GDT.");
+
+    selectors.unwrap()
 }
 
 fn init_selectors(selectors: Selectors) {
@@ -101,7 +119,7 @@ fn load_segments() {
 }
 
 fn gdt<'a>() -> &'a GlobalDescriptorTable {
-    let gdt = GDT.try_get();
+    let gdt = GDT[smp::index()].try_get();
     gdt.expect("GDT is not initialized.")
 }
 
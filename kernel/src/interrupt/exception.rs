@@ -0,0 +1,138 @@
+//! The exceptions.
+//!
+//! A page fault on the first access to a page in the regions of a user process maps the page, and
+//! the access is retried. Any other exception which the process causes in the user mode terminates
+//! only the process. The kernel reports the fault to PM on behalf of the process, and PM destroys it
+//! instead of replying. An exception in the kernel mode is a bug of the kernel, so it panics.
+//!
+//! The non-maskable interrupts, the machine checks, and the exceptions from the hypervisor are not
+//! caused by the interrupted process. A non-maskable interrupt is only logged, and the others
+//! panic.
+
+use {
+    crate::process::{self, ipc::send_receive},
+    core::fmt,
+    num_traits::FromPrimitive,
+    pid::predefined,
+    syscalls::{FaultKind, FaultReport},
+    x86_64::{
+        registers::control::Cr2,
+        structures::idt::{InterruptStackFrameValue, PageFaultErrorCode, SelectorErrorCode},
+        VirtAddr,
+    },
+};
+
+#[no_mangle]
+fn handle_exception(vector: u64, error_code: u64, frame: *const InterruptStackFrameValue) {
+    let kind = FromPrimitive::from_u64(vector).expect("Unknown exception.");
+
+    // SAFETY: `asm_interrupt_handler_*` passes the address of the interrupt stack frame.
+    let frame = unsafe { &*frame };
+
+    let address = if kind == FaultKind::PageFault {
+        Cr2::read()
+    } else {
+        VirtAddr::zero()
+    };
+
+    // A write to a copy-on-write page. The access is retried after returning from this handler.
+    if kind == FaultKind::PageFault && vm::copy_on_write(address) {
+        return;
+    }
+
+    let from_user_mode = is_from_user_mode(frame);
+
+    // The first access to a page in the regions of the process, such as the stack and `.bss`. The
+    // kernel accesses these pages after mapping them explicitly.
+    if kind == FaultKind::PageFault
+        && from_user_mode
+        && !PageFaultErrorCode::from_bits_truncate(error_code)
+            .contains(PageFaultErrorCode::PROTECTION_VIOLATION)
+        && process::populate(process::running(), address)
+    {
+        return;
+    }
+
+    let report = FaultReport::new(kind, frame.instruction_pointer, error_code, address);
+
+    if kind == FaultKind::NonMaskableInterrupt {
+        log::warn!("{}.", Description(report));
+
+        return;
+    }
+
+    if !is_caused_by_running_process(kind) {
+        panic!("{}.", Description(report));
+    }
+
+    if from_user_mode {
+        terminate(report);
+    } else {
+        panic!("{} in the kernel mode.", Description(report));
+    }
+}
+
+fn is_caused_by_running_process(kind: FaultKind) -> bool {
+    !matches!(
+        kind,
+        FaultKind::NonMaskableInterrupt
+            | FaultKind::DoubleFault
+            | FaultKind::MachineCheck
+            | FaultKind::HypervisorInjection
+            | FaultKind::VmmCommunication
+            | FaultKind::Security
+    )
+}
+
+// The requested privilege level of the code segment is the privilege level of the interrupted
+// code.
+fn is_from_user_mode(frame: &InterruptStackFrameValue) -> bool {
+    frame.code_segment & 0b11 == 3
+}
+
+fn terminate(report: FaultReport) -> ! {
+    let pid = process::running();
+
+    log::warn!(
+        "{} in {}. The process is terminated.",
+        Description(report),
+        pid
+    );
+
+    let mut message = report.to_message();
+
+    // PM destroys the process instead of replying, so this function never returns unless PM
+    // itself faulted.
+    let r = send_receive(predefined::PM, &mut message);
+
+    panic!("Failed to report the fault of {} to PM: {:?}", pid, r);
+}
+
+struct Description(FaultReport);
+impl fmt::Display for Description {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let report = self.0;
+        let error_code = report.error_code();
+
+        write!(f, "{:?} at {:?}", report.kind(), report.rip())?;
+
+        match report.kind() {
+            FaultKind::PageFault => write!(
+                f,
+                " accessing {:?} ({:?})",
+                report.address(),
+                PageFaultErrorCode::from_bits_truncate(error_code)
+            ),
+            // The error code is `0` unless a segment selector caused the exception.
+            FaultKind::InvalidTss
+            | FaultKind::SegmentNotPresent
+            | FaultKind::StackSegmentFault
+            | FaultKind::GeneralProtection
+                if error_code != 0 =>
+            {
+                write!(f, " with {:?}", SelectorErrorCode::new(error_code))
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -1,17 +1,63 @@
-use {crate::process, apic::local::EOI, vm::accessor::single::write_only};
+use {
+    super::irq,
+    crate::{process, smp, timer},
+    apic::local::EOI,
+    core::convert::TryInto,
+    vm::accessor::single::write_only,
+};
 
 #[no_mangle]
-fn interrupt_handler_0x0e() {
-    panic!("Page fault!");
+fn interrupt_handler_0x20() {
+    // SAFETY: This OS does not change the start address of the Local APIC registers, thus `EOI` is
+    // the correct address.
+    unsafe {
+        write_only(EOI).write_volatile(0_u32);
+    };
+
+    #[cfg(test_on_qemu)]
+    smp::check_in();
+
+    let now = timer::tick();
+
+    // The deadlines are checked against the tick count, which the bootstrap processor keeps.
+    if smp::is_bsp() {
+        process::wake_expired(now);
+    }
+
+    timer::set_next_interrupt(process::ticks_until_next_event(now));
+
+    process::tick();
 }
 
 #[no_mangle]
-fn interrupt_handler_0x20() {
+fn interrupt_handler_0xf0() {
+    // SAFETY: This OS does not change the start address of the Local APIC registers, thus `EOI` is
+    // the correct address.
+    unsafe {
+        write_only(EOI).write_volatile(0_u32);
+    };
+
+    // The bootstrap processor is interrupted when another processor starts running a process while
+    // it idles without ticks. It must tick again to keep the tick count.
+    if smp::is_bsp() {
+        timer::resume_ticking();
+    }
+
+    process::reschedule();
+}
+
+#[no_mangle]
+fn interrupt_handler_irq(vector: u64) {
+    // A level-triggered input must be masked before the EOI. Otherwise, the device interrupts again
+    // immediately.
+    irq::handle(vector.try_into().unwrap());
+
     // SAFETY: This OS does not change the start address of the Local APIC registers, thus `EOI` is
     // the correct address.
     unsafe {
         write_only(EOI).write_volatile(0_u32);
     };
 
-    process::switch();
+    // The owner of the interrupt may be runnable now.
+    process::reschedule();
 }
@@ -1,26 +1,88 @@
 use core::convert::TryInto;
 
 use {
+    crate::{ioapic, smp, tss},
     conquer_once::spin::Lazy,
-    x86_64::{structures::idt::InterruptDescriptorTable, VirtAddr},
+    x86_64::{
+        structures::idt::{Entry, HandlerFunc, InterruptDescriptorTable},
+        VirtAddr,
+    },
 };
 
+// The number of the entries of `asm_exception_handlers` in `asm.s`.
+const NUM_OF_EXCEPTIONS: usize = 0x20;
+
 static IDT: Lazy<InterruptDescriptorTable> = Lazy::new(|| {
     extern "sysv64" {
-        fn asm_interrupt_handler_0x0e();
         fn asm_interrupt_handler_0x20();
+        fn asm_interrupt_handler_0xf0();
+
+        static asm_exception_handlers: [usize; NUM_OF_EXCEPTIONS];
+        static asm_irq_handlers: [usize; ioapic::NUM_OF_VECTORS as usize];
     }
 
+    // SAFETY: `asm_exception_handlers` is initialized in `asm.s`.
+    let exception = |vector: usize| {
+        VirtAddr::new(
+            unsafe { asm_exception_handlers[vector] }
+                .try_into()
+                .unwrap(),
+        )
+    };
+
     let mut idt = InterruptDescriptorTable::new();
 
     // SAFETY: The addresses are correct.
     unsafe {
-        idt.page_fault.set_handler_addr(VirtAddr::new(
-            (asm_interrupt_handler_0x0e as usize).try_into().unwrap(),
-        ));
+        idt.divide_error.set_handler_addr(exception(0x00));
+        idt.debug.set_handler_addr(exception(0x01));
+        idt.non_maskable_interrupt.set_handler_addr(exception(0x02));
+        idt.breakpoint.set_handler_addr(exception(0x03));
+        idt.overflow.set_handler_addr(exception(0x04));
+        idt.bound_range_exceeded.set_handler_addr(exception(0x05));
+        idt.invalid_opcode.set_handler_addr(exception(0x06));
+        idt.device_not_available.set_handler_addr(exception(0x07));
+        idt.double_fault
+            .set_handler_addr(exception(0x08))
+            .set_stack_index(tss::DOUBLE_FAULT_IST_INDEX);
+        idt.invalid_tss.set_handler_addr(exception(0x0a));
+        idt.segment_not_present.set_handler_addr(exception(0x0b));
+        idt.stack_segment_fault.set_handler_addr(exception(0x0c));
+        idt.general_protection_fault
+            .set_handler_addr(exception(0x0d));
+        idt.page_fault.set_handler_addr(exception(0x0e));
+        idt.x87_floating_point.set_handler_addr(exception(0x10));
+        idt.alignment_check.set_handler_addr(exception(0x11));
+        idt.machine_check.set_handler_addr(exception(0x12));
+        idt.simd_floating_point.set_handler_addr(exception(0x13));
+        idt.virtualization.set_handler_addr(exception(0x14));
+        idt.vmm_communication_exception
+            .set_handler_addr(exception(0x1d));
+        idt.security_exception.set_handler_addr(exception(0x1e));
+
+        // `x86_64` treats the vectors of #CP and #HV as reserved, so these entries are set through
+        // the array of all entries. `InterruptDescriptorTable` is `repr(C)` and consists of 256
+        // entries, whose layout does not depend on the type of the handler.
+        {
+            let entries = &mut *(&mut idt as *mut InterruptDescriptorTable)
+                .cast::<[Entry<HandlerFunc>; 256]>();
+
+            entries[0x15].set_handler_addr(exception(0x15));
+            entries[0x1c].set_handler_addr(exception(0x1c));
+        }
+
         idt[0x20].set_handler_addr(VirtAddr::new(
             (asm_interrupt_handler_0x20 as usize).try_into().unwrap(),
         ));
+        idt[usize::from(smp::RESCHEDULE_VECTOR)].set_handler_addr(VirtAddr::new(
+            (asm_interrupt_handler_0xf0 as *const () as usize)
+                .try_into()
+                .unwrap(),
+        ));
+
+        for (vector, &handler) in (ioapic::VECTOR_BASE..).zip(asm_irq_handlers.iter()) {
+            idt[usize::from(vector)].set_handler_addr(VirtAddr::new(handler.try_into().unwrap()));
+        }
     }
 
     idt
@@ -33,6 +95,11 @@ pub(crate) fn init() {
     tests::main();
 }
 
+/// Loads the IDT on the current application processor. All processors share the IDT.
+pub(crate) fn init_ap() {
+    IDT.load();
+}
+
 #[cfg(test_on_qemu)]
 mod tests {
     use {
@@ -0,0 +1,106 @@
+//! The interrupts from the devices.
+//!
+//! A driver registers the vector of its device, and each interrupt with the vector is delivered to
+//! the driver as a notification from [`pid::predefined::HARDWARE`]. A level-triggered input is
+//! masked until the driver acknowledges the interrupt. Otherwise, the device would keep
+//! interrupting until the driver handles it.
+
+use {
+    crate::{ioapic, process, sync::IrqSpinlock},
+    pid::Pid,
+    syscalls::IrqError,
+};
+
+static OWNERS: IrqSpinlock<[Option<Pid>; ioapic::NUM_OF_VECTORS as usize]> =
+    IrqSpinlock::new([None; ioapic::NUM_OF_VECTORS as usize]);
+
+/// Binds `vector` to `pid`. The input stays masked until `pid` enables it.
+///
+/// A vector may be registered again if its owner has exited.
+///
+/// # Errors
+///
+/// This function returns an error if no device interrupts with `vector` or another process owns
+/// `vector`.
+pub(crate) fn register(vector: u8, pid: Pid) -> Result<(), IrqError> {
+    let gsi = ioapic::gsi(vector).ok_or(IrqError::InvalidVector)?;
+
+    let mut owners = OWNERS.lock();
+    let owner = &mut owners[index(vector)];
+
+    match *owner {
+        Some(o) if o == pid => Ok(()),
+        Some(o) if process::process_exists(o) => Err(IrqError::AlreadyRegistered),
+        _ => {
+            ioapic::mask(gsi);
+
+            *owner = Some(pid);
+
+            Ok(())
+        }
+    }
+}
+
+/// Unmasks the input of `vector`.
+///
+/// # Errors
+///
+/// This function returns an error if `pid` does not own `vector`.
+pub(crate) fn enable(vector: u8, pid: Pid) -> Result<(), IrqError> {
+    let gsi = owned_gsi(vector, pid)?;
+
+    ioapic::unmask(gsi);
+
+    Ok(())
+}
+
+/// Unmasks the input of `vector` if it is level-triggered. The owner calls this function after
+/// handling an interrupt.
+///
+/// # Errors
+///
+/// This function returns an error if `pid` does not own `vector`.
+pub(crate) fn acknowledge(vector: u8, pid: Pid) -> Result<(), IrqError> {
+    let gsi = owned_gsi(vector, pid)?;
+
+    if ioapic::is_level_triggered(gsi) {
+        ioapic::unmask(gsi);
+    }
+
+    Ok(())
+}
+
+/// Notifies the owner of `vector` of an interrupt.
+pub(super) fn handle(vector: u8) {
+    let gsi = ioapic::gsi(vector).expect("No device interrupts with this vector.");
+
+    let owner = OWNERS.lock()[index(vector)];
+
+    if ioapic::is_level_triggered(gsi) {
+        ioapic::mask(gsi);
+    }
+
+    if !owner.is_some_and(|owner| process::notify_interrupt(owner, vector)) {
+        // Nobody will acknowledge the interrupt.
+        ioapic::mask(gsi);
+
+        log::warn!(
+            "No process handles the interrupt with vector {:#x}.",
+            vector
+        );
+    }
+}
+
+fn owned_gsi(vector: u8, pid: Pid) -> Result<u32, IrqError> {
+    let gsi = ioapic::gsi(vector).ok_or(IrqError::InvalidVector)?;
+
+    if OWNERS.lock()[index(vector)] == Some(pid) {
+        Ok(gsi)
+    } else {
+        Err(IrqError::NotOwner)
+    }
+}
+
+fn index(vector: u8) -> usize {
+    usize::from(vector - ioapic::VECTOR_BASE)
+}
@@ -1,5 +1,7 @@
+mod exception;
 mod handler;
 pub(super) mod idt;
+pub(crate) mod irq;
 
 use x86_64::instructions::interrupts;
 
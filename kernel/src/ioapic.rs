@@ -0,0 +1,284 @@
+use {
+    crate::{smp, sync::IrqSpinlock},
+    acpi::{InterruptSourceOverride, Madt, Polarity, TriggerMode},
+    alloc::vec::Vec,
+    conquer_once::spin::OnceCell,
+    core::convert::TryFrom,
+    ioapic::{DeliveryMode, RedirectionEntry, Version, IOREGSEL, IOWIN, VERSION},
+    vm::accessor::{
+        single::{read_only, write_only},
+        Mapper,
+    },
+    x86_64::PhysAddr,
+};
+
+/// The vector of the interrupts from the Global System Interrupt `0`. The interrupts from the
+/// Global System Interrupt `n` have the vector `VECTOR_BASE + n`.
+pub(crate) const VECTOR_BASE: u8 = 0x30;
+
+/// The number of the Global System Interrupts which have vectors.
+pub(crate) const NUM_OF_VECTORS: u8 = 32;
+
+// The ISA IRQs are connected to the first inputs unless the firmware overrides them.
+const NUM_OF_ISA_IRQS: u32 = 16;
+
+const ISA: u8 = 0;
+
+static IO_APICS: OnceCell<Vec<IoApic>> = OnceCell::uninit();
+
+/// Programs the I/O APICs listed in `madt` to deliver the interrupts from each Global System
+/// Interrupt to the bootstrap processor with the vector `VECTOR_BASE + GSI`. All inputs stay masked
+/// until their drivers are ready.
+pub(super) fn init(madt: Option<&Madt<Mapper>>) {
+    let io_apics: Vec<_> = madt
+        .into_iter()
+        .flat_map(Madt::io_apics)
+        .map(|a| IoApic::new(a.address, a.global_system_interrupt_base))
+        .collect();
+
+    if io_apics.is_empty() {
+        log::warn!("No I/O APIC is found. The devices cannot interrupt.");
+    }
+
+    let overrides: Vec<_> = madt
+        .into_iter()
+        .flat_map(Madt::interrupt_source_overrides)
+        .filter(|o| o.bus == ISA)
+        .collect();
+
+    for io_apic in &io_apics {
+        let mut registers = io_apic.registers.lock();
+
+        for input in 0..io_apic.len {
+            let gsi = io_apic.gsi_base + u32::from(input);
+
+            registers.write_entry(input, redirection_entry(gsi, &overrides));
+        }
+    }
+
+    IO_APICS
+        .try_init_once(|| io_apics)
+        .expect("`IO_APICS` is already initialized.");
+
+    #[cfg(test_on_qemu)]
+    tests::main();
+}
+
+/// Returns the Global System Interrupt which interrupts with `vector`, or `None` if no I/O APIC
+/// receives it.
+#[must_use]
+pub(crate) fn gsi(vector: u8) -> Option<u32> {
+    let gsi = vector
+        .checked_sub(VECTOR_BASE)
+        .filter(|&gsi| gsi < NUM_OF_VECTORS)?;
+    let gsi = u32::from(gsi);
+
+    find(gsi).map(|_| gsi)
+}
+
+pub(crate) fn mask(gsi: u32) {
+    modify_entry(gsi, |e| {
+        e.set_mask();
+    });
+}
+
+pub(crate) fn unmask(gsi: u32) {
+    modify_entry(gsi, |e| {
+        e.clear_mask();
+    });
+}
+
+/// Returns `true` if the Global System Interrupt `gsi` is level-triggered.
+#[must_use]
+pub(crate) fn is_level_triggered(gsi: u32) -> bool {
+    modify_entry(gsi, |_| {}).is_some_and(RedirectionEntry::level_triggered)
+}
+
+// Returns the I/O APIC receiving the Global System Interrupt `gsi` and the index of the input.
+fn find(gsi: u32) -> Option<(&'static IoApic, u8)> {
+    IO_APICS.try_get().ok()?.iter().find_map(|io_apic| {
+        let input = gsi.checked_sub(io_apic.gsi_base)?;
+        let input = u8::try_from(input).ok()?;
+
+        (input < io_apic.len).then_some((io_apic, input))
+    })
+}
+
+// Applies `f` to the entry of the Global System Interrupt `gsi`, and returns the new entry. The
+// entry is not written if `f` does not change it.
+fn modify_entry(gsi: u32, f: impl FnOnce(&mut RedirectionEntry)) -> Option<RedirectionEntry> {
+    let (io_apic, input) = find(gsi)?;
+
+    let mut registers = io_apic.registers.lock();
+
+    let old = registers.read_entry(input);
+    let mut new = old;
+
+    f(&mut new);
+
+    if new != old {
+        registers.write_entry(input, new);
+    }
+
+    Some(new)
+}
+
+// Returns the masked entry for the Global System Interrupt `gsi`. The ISA interrupts are
+// edge-triggered and active high, and the PCI ones are level-triggered and active low unless
+// `overrides` specify otherwise.
+fn redirection_entry(gsi: u32, overrides: &[InterruptSourceOverride]) -> RedirectionEntry {
+    let mut entry = RedirectionEntry::default();
+
+    let vector = u8::try_from(gsi)
+        .ok()
+        .filter(|&gsi| gsi < NUM_OF_VECTORS)
+        .map(|gsi| VECTOR_BASE + gsi);
+
+    let vector = if let Some(vector) = vector {
+        vector
+    } else {
+        return entry;
+    };
+
+    let overriding = overrides.iter().find(|o| o.global_system_interrupt == gsi);
+
+    let isa = overriding.is_some() || gsi < NUM_OF_ISA_IRQS;
+
+    let active_low = match overriding.map(|o| o.polarity) {
+        Some(Polarity::ActiveLow) => true,
+        Some(Polarity::ActiveHigh) => false,
+        _ => !isa,
+    };
+
+    let level_triggered = match overriding.map(|o| o.trigger_mode) {
+        Some(TriggerMode::Level) => true,
+        Some(TriggerMode::Edge) => false,
+        _ => !isa,
+    };
+
+    entry
+        .set_vector(vector)
+        .set_delivery_mode(DeliveryMode::Fixed)
+        .set_physical_destination_mode()
+        .set_destination(smp::apic_id(smp::BSP));
+
+    if active_low {
+        entry.set_active_low();
+    }
+
+    if level_triggered {
+        entry.set_level_triggered();
+    }
+
+    entry
+}
+
+struct IoApic {
+    gsi_base: u32,
+    // The number of the inputs.
+    len: u8,
+    // The interrupt handlers may mask or unmask the inputs.
+    registers: IrqSpinlock<Registers>,
+}
+impl IoApic {
+    fn new(base: PhysAddr, gsi_base: u32) -> Self {
+        let mut registers = Registers(base);
+
+        let len = Version::new(registers.read(VERSION)).num_of_redirection_entries();
+
+        Self {
+            gsi_base,
+            len,
+            registers: IrqSpinlock::new(registers),
+        }
+    }
+}
+
+// The start address of the registers. The index register and the data register must be accessed
+// with the lock held.
+struct Registers(PhysAddr);
+impl Registers {
+    fn read_entry(&mut self, input: u8) -> RedirectionEntry {
+        let (low, high) = ioapic::redirection_entry(input);
+
+        RedirectionEntry::new(self.read(low), self.read(high))
+    }
+
+    // The upper half is written first so that the entry is not unmasked with the old destination.
+    fn write_entry(&mut self, input: u8, entry: RedirectionEntry) {
+        let (low, high) = ioapic::redirection_entry(input);
+
+        self.write(high, entry.high());
+        self.write(low, entry.low());
+    }
+
+    fn read(&mut self, index: u32) -> u32 {
+        self.select(index);
+
+        // SAFETY: The address is the I/O Window Register of the I/O APIC listed in MADT.
+        unsafe { read_only(self.0 + IOWIN).read_volatile() }
+    }
+
+    fn write(&mut self, index: u32, value: u32) {
+        self.select(index);
+
+        // SAFETY: The address is the I/O Window Register of the I/O APIC listed in MADT.
+        unsafe {
+            write_only(self.0 + IOWIN).write_volatile(value);
+        }
+    }
+
+    fn select(&mut self, index: u32) {
+        // SAFETY: The address is the I/O Register Select Register of the I/O APIC listed in MADT.
+        unsafe {
+            write_only(self.0 + IOREGSEL).write_volatile(index);
+        }
+    }
+}
+
+#[cfg(test_on_qemu)]
+mod tests {
+    use super::{IO_APICS, VECTOR_BASE};
+
+    pub(super) fn main() {
+        io_apic_has_isa_interrupts();
+        all_inputs_are_masked();
+        timer_irq_is_overridden();
+    }
+
+    fn io_apic_has_isa_interrupts() {
+        let io_apic = IO_APICS.try_get().unwrap().first();
+        let io_apic = io_apic.expect("No I/O APIC is found.");
+
+        assert_eq!(io_apic.gsi_base, 0, "The first GSI is not 0.");
+        assert!(
+            io_apic.len >= 16,
+            "The I/O APIC cannot receive the ISA IRQs."
+        );
+    }
+
+    fn all_inputs_are_masked() {
+        for io_apic in IO_APICS.try_get().unwrap() {
+            let mut registers = io_apic.registers.lock();
+
+            for input in 0..io_apic.len {
+                assert!(
+                    registers.read_entry(input).mask(),
+                    "The input {} is not masked.",
+                    input
+                );
+            }
+        }
+    }
+
+    // QEMU connects the ISA IRQ 0 to the GSI 2, which is edge-triggered like the other ISA IRQs.
+    fn timer_irq_is_overridden() {
+        let io_apic = &IO_APICS.try_get().unwrap()[0];
+
+        let entry = io_apic.registers.lock().read_entry(2);
+
+        assert_eq!(entry.vector(), VECTOR_BASE + 2, "Wrong vector.");
+        assert!(!entry.level_triggered(), "The ISA IRQ is level-triggered.");
+        assert!(!entry.active_low(), "The ISA IRQ is active low.");
+    }
+}
@@ -1,16 +1,20 @@
 #![no_std]
 #![deny(unsafe_op_in_unsafe_fn)]
 
+extern crate alloc;
 extern crate rlibc as _;
 
 mod gdt;
 mod interrupt;
+mod ioapic;
 #[macro_use]
 mod io;
 mod boot_info;
 mod libc;
 mod log;
 mod process;
+mod smp;
+mod sync;
 mod syscall;
 mod sysproc;
 #[cfg(test_on_qemu)]
@@ -20,6 +24,10 @@ mod tss;
 
 use {::boot_info::BootInfo, core::panic::PanicInfo, interrupt::idt};
 
+// The process table and the per-process queues grow with the number of processes.
+#[global_allocator]
+static ALLOCATOR: vm::Allocator = vm::Allocator;
+
 pub fn init(boot_info: BootInfo) {
     boot_info.validate();
 
@@ -40,13 +48,22 @@ pub fn init(boot_info: BootInfo) {
 
     // SAFETY: `boot_info.rsdp()` returns the address of RSDP that is fetched from the
     // configuration table of UEFI's system table.
+    let tables = unsafe { acpi::Tables::from_rsdp_addr(boot_info.rsdp(), &vm::accessor::Mapper) };
+    let tables = tables.expect("Failed to get the information of ACPI.");
+
+    // SAFETY: This OS does not change the start address of the Local APIC registers.
     unsafe {
-        timer::init(boot_info.rsdp());
+        timer::init(&tables);
     }
 
     process::init();
 
     syscall::init();
+
+    // The application processors take over the processes added above.
+    smp::init(tables.madt.as_ref());
+
+    ioapic::init(tables.madt.as_ref());
 }
 
 pub fn idle() -> ! {
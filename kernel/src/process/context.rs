@@ -2,7 +2,7 @@ use x86_64::structures::gdt::SegmentSelector;
 
 use {
     crate::gdt,
-    core::mem::size_of,
+    core::{mem::size_of, sync::atomic::AtomicBool},
     static_assertions::const_assert_eq,
     x86_64::{registers::rflags::RFlags, structures::paging::PhysFrame, VirtAddr},
 };
@@ -64,13 +64,50 @@ impl Context {
         )
     }
 
-    pub(super) fn switch(old: *mut Context, new: *mut Context) {
+    /// Sets the arguments passed to the entry function of a user process.
+    pub(super) fn set_main_arguments(&mut self, argc: u64, argv: VirtAddr, envp: VirtAddr) {
+        self.rdi = argc;
+        self.rsi = argv.as_u64();
+        self.rdx = envp.as_u64();
+    }
+
+    /// Returns the context of a process forked from the process with this context. The forked
+    /// process returns to the user mode with the registers saved in the [`SyscallFrame`] at `rsp`.
+    pub(super) fn forked(&self, pml4: PhysFrame, rsp: VirtAddr) -> Self {
         extern "sysv64" {
-            fn asm_switch_context(old: *mut Context, new: *mut Context);
+            fn asm_return_from_forked_syscall();
+        }
+
+        let entry: unsafe extern "sysv64" fn() = asm_return_from_forked_syscall;
+
+        Self {
+            rsp: rsp.as_u64(),
+            rip: entry as usize as u64,
+            // The interrupts are disabled during system calls.
+            rflags: RFlags::PARITY_FLAG.bits(),
+            cr3: pml4.start_address().as_u64(),
+            cs: gdt::kernel_code_selector().0.into(),
+            ss: gdt::kernel_data_selector().0.into(),
+            fs: self.fs,
+            gs: self.gs,
+            fxsave_area: self.fxsave_area,
+            ..Self::default()
+        }
+    }
+
+    /// Saves the current context to `old` and switches to `new`. `old_on_cpu` is cleared after
+    /// `old` is saved so that another processor can switch to it.
+    pub(super) fn switch(old: *mut Context, new: *mut Context, old_on_cpu: *const AtomicBool) {
+        extern "sysv64" {
+            fn asm_switch_context(
+                old: *mut Context,
+                new: *mut Context,
+                old_on_cpu: *const AtomicBool,
+            );
         }
 
         unsafe {
-            asm_switch_context(old, new);
+            asm_switch_context(old, new, old_on_cpu);
         }
     }
 
@@ -131,3 +168,25 @@ impl Default for Context {
         }
     }
 }
+
+/// The registers saved on the kernel stack by `asm_handle_syscall`, from the lowest address.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(super) struct SyscallFrame {
+    r15: u64,
+    r14: u64,
+    r13: u64,
+    r12: u64,
+    rbx: u64,
+    // The user stack pointer after `asm_handle_syscall` pushes `rcx`, `r11`, and `rbp`.
+    rbp: u64,
+    a2: u64,
+    a1: u64,
+}
+const_assert_eq!(size_of::<SyscallFrame>(), 8 * 8);
+impl SyscallFrame {
+    /// Returns the second argument of the system call.
+    pub(super) fn a2(&self) -> u64 {
+        self.a2
+    }
+}
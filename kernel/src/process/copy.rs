@@ -0,0 +1,155 @@
+use {
+    super::{enter_address_space_and_do, populate, Pid},
+    core::{cmp, convert::TryInto, ptr},
+    os_units::Bytes,
+    vm::Kbox,
+    x86_64::{
+        structures::paging::{PageSize, Size4KiB},
+        VirtAddr,
+    },
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct InvalidAddress(pub(crate) VirtAddr);
+
+/// Copies `bytes` bytes from `src_addr` of the address space of `src_pid` to `dst_addr` of that of
+/// `dst_pid`.
+///
+/// The data is copied page by page through a kernel buffer. Each page must be accessible from the
+/// user mode, and the destination pages must be writable. Pages are validated before they are
+/// accessed, so the copy may stop halfway if an invalid page is found.
+pub(crate) fn copy(
+    (src_pid, src_addr): (Pid, VirtAddr),
+    (dst_pid, dst_addr): (Pid, VirtAddr),
+    bytes: Bytes,
+) -> Result<(), InvalidAddress> {
+    const PAGE_SIZE: usize = Size4KiB::SIZE as usize;
+
+    let mut buffer = Kbox::new([0_u8; PAGE_SIZE]);
+
+    let mut copied = 0;
+
+    while copied < bytes.as_usize() {
+        let src = checked_add(src_addr, copied)?;
+        let dst = checked_add(dst_addr, copied)?;
+
+        let len = cmp::min(
+            bytes.as_usize() - copied,
+            cmp::min(bytes_to_page_end(src), bytes_to_page_end(dst)),
+        );
+
+        // The pages in the regions of the processes are mapped when they are accessed for the
+        // first time.
+        populate(src_pid, src);
+
+        enter_address_space_and_do(src_pid, || {
+            validate(src, false)?;
+
+            // SAFETY: `src` is validated and `len` does not exceed the page boundary.
+            unsafe {
+                ptr::copy_nonoverlapping(src.as_ptr(), buffer.as_mut_ptr(), len);
+            }
+
+            Ok(())
+        })?;
+
+        populate(dst_pid, dst);
+
+        enter_address_space_and_do(dst_pid, || {
+            // A copy-on-write page becomes writable here.
+            let _ = vm::copy_on_write(dst);
+
+            validate(dst, true)?;
+
+            // SAFETY: `dst` is validated and `len` does not exceed the page boundary.
+            unsafe {
+                ptr::copy_nonoverlapping(buffer.as_ptr(), dst.as_mut_ptr(), len);
+            }
+
+            Ok(())
+        })?;
+
+        copied += len;
+    }
+
+    Ok(())
+}
+
+/// Copies `dst.len()` bytes from `src_addr` of the address space of `src_pid` to `dst`. The source
+/// pages must be accessible from the user mode.
+pub(crate) fn copy_from_user(
+    (src_pid, src_addr): (Pid, VirtAddr),
+    dst: &mut [u8],
+) -> Result<(), InvalidAddress> {
+    let mut copied = 0;
+
+    while copied < dst.len() {
+        let src = checked_add(src_addr, copied)?;
+
+        let len = cmp::min(dst.len() - copied, bytes_to_page_end(src));
+
+        populate(src_pid, src);
+
+        enter_address_space_and_do(src_pid, || {
+            validate(src, false)?;
+
+            // SAFETY: `src` is validated and `len` does not exceed the page boundary.
+            unsafe {
+                ptr::copy_nonoverlapping(src.as_ptr(), dst[copied..].as_mut_ptr(), len);
+            }
+
+            Ok(())
+        })?;
+
+        copied += len;
+    }
+
+    Ok(())
+}
+
+/// Checks that `bytes` bytes from `addr` of the address space of `pid` are writable from the user
+/// mode. The pages are mapped if they are not accessed yet, and the copy-on-write pages are copied
+/// so that the kernel can write to their frames.
+pub(crate) fn validate_user_writable(
+    (pid, addr): (Pid, VirtAddr),
+    bytes: Bytes,
+) -> Result<(), InvalidAddress> {
+    let mut checked = 0;
+
+    while checked < bytes.as_usize() {
+        let page = checked_add(addr, checked)?;
+
+        populate(pid, page);
+
+        enter_address_space_and_do(pid, || {
+            let _ = vm::copy_on_write(page);
+
+            validate(page, true)
+        })?;
+
+        checked += bytes_to_page_end(page);
+    }
+
+    Ok(())
+}
+
+fn validate(addr: VirtAddr, writable: bool) -> Result<(), InvalidAddress> {
+    if vm::is_user_accessible(addr, writable) {
+        Ok(())
+    } else {
+        Err(InvalidAddress(addr))
+    }
+}
+
+fn checked_add(addr: VirtAddr, offset: usize) -> Result<VirtAddr, InvalidAddress> {
+    addr.as_u64()
+        .checked_add(offset.try_into().unwrap())
+        .and_then(|a| VirtAddr::try_new(a).ok())
+        .ok_or(InvalidAddress(addr))
+}
+
+fn bytes_to_page_end(addr: VirtAddr) -> usize {
+    (Size4KiB::SIZE - addr.as_u64() % Size4KiB::SIZE)
+        .try_into()
+        .unwrap()
+}
@@ -0,0 +1,47 @@
+use {
+    alloc::{collections::TryReserveError, vec::Vec},
+    pid::Pid,
+};
+
+// The processes waiting for a message with a deadline or sleeping, sorted by the deadline in
+// ascending order. Each process appears at most once because it can block on only one system call
+// at a time.
+#[derive(Debug)]
+pub(super) struct Queue(Vec<(u64, Pid)>);
+impl Queue {
+    pub(super) const fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Allocates the memory for `n` processes so that `push` does not allocate memory while there
+    /// are at most `n` processes.
+    pub(super) fn reserve(&mut self, n: usize) -> Result<(), TryReserveError> {
+        self.0.try_reserve(n.saturating_sub(self.0.len()))
+    }
+
+    pub(super) fn push(&mut self, pid: Pid, deadline: u64) {
+        self.remove(pid);
+
+        let index = self.0.partition_point(|&(d, _)| d <= deadline);
+
+        self.0.insert(index, (deadline, pid));
+    }
+
+    pub(super) fn remove(&mut self, pid: Pid) {
+        if let Some(index) = self.0.iter().position(|&(_, p)| p == pid) {
+            self.0.remove(index);
+        }
+    }
+
+    /// Returns the earliest deadline, if any.
+    pub(super) fn earliest(&self) -> Option<u64> {
+        self.0.first().map(|&(deadline, _)| deadline)
+    }
+
+    /// Removes and returns the PID of a process whose deadline is not later than `now`.
+    pub(super) fn pop_expired(&mut self, now: u64) -> Option<Pid> {
+        let &(deadline, pid) = self.0.first()?;
+
+        (deadline <= now).then(|| self.0.remove(0)).map(|_| pid)
+    }
+}
@@ -0,0 +1,76 @@
+use {core::convert::TryInto, pid::Pid, syscalls::GrantAccess, x86_64::VirtAddr};
+
+const MAX_GRANTS: usize = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Grant {
+    grantee: Pid,
+    start: VirtAddr,
+    len: usize,
+    access: GrantAccess,
+}
+impl Grant {
+    pub(crate) fn new(grantee: Pid, start: VirtAddr, len: usize, access: GrantAccess) -> Self {
+        Self {
+            grantee,
+            start,
+            len,
+            access,
+        }
+    }
+
+    /// Returns the start address of `[offset, offset + len)` of the granted region if `pid` is
+    /// allowed to access it with `access`.
+    pub(crate) fn resolve(
+        &self,
+        pid: Pid,
+        offset: usize,
+        len: usize,
+        access: GrantAccess,
+    ) -> Option<VirtAddr> {
+        let end = offset.checked_add(len)?;
+
+        (self.grantee == pid && self.access.allows(access) && end <= self.len)
+            .then(|| self.start + offset)
+    }
+
+    /// Returns `true` if `pid` is allowed to access `[start, start + len)` with `access`.
+    pub(crate) fn covers(
+        &self,
+        pid: Pid,
+        start: VirtAddr,
+        len: usize,
+        access: GrantAccess,
+    ) -> bool {
+        start
+            .as_u64()
+            .checked_sub(self.start.as_u64())
+            .and_then(|offset| offset.try_into().ok())
+            .and_then(|offset| self.resolve(pid, offset, len, access))
+            .is_some()
+    }
+}
+
+#[derive(Debug, Default)]
+pub(super) struct Table([Option<Grant>; MAX_GRANTS]);
+impl Table {
+    pub(super) fn add(&mut self, grant: Grant) -> Option<usize> {
+        let (index, slot) = self.0.iter_mut().enumerate().find(|(_, s)| s.is_none())?;
+
+        *slot = Some(grant);
+
+        Some(index)
+    }
+
+    pub(super) fn remove(&mut self, index: usize) -> Option<Grant> {
+        self.0.get_mut(index)?.take()
+    }
+
+    pub(super) fn get(&self, index: usize) -> Option<&Grant> {
+        self.0.get(index)?.as_ref()
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = &Grant> {
+        self.0.iter().flatten()
+    }
+}
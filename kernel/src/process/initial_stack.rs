@@ -0,0 +1,122 @@
+use {
+    super::context::Context,
+    aligned_ptr::ptr,
+    core::{convert::TryInto, mem::size_of},
+    syscalls::ExecArgs,
+    x86_64::{
+        structures::paging::{PageSize, PhysFrame, Size4KiB},
+        VirtAddr,
+    },
+};
+
+// The types of the entries of the auxiliary vector.
+const AT_NULL: u64 = 0;
+const AT_PAGESZ: u64 = 6;
+const AT_ENTRY: u64 = 9;
+
+const NUM_OF_AUXV_ENTRIES: usize = 3;
+
+/// Returns the maximum number of bytes written by [`build`].
+pub(super) fn size(args: &ExecArgs<'_>) -> usize {
+    // The alignment may need up to 15 bytes and a padding word.
+    args.strings_len() + 16 + size_of::<u64>() * (num_of_words(args) + 1)
+}
+
+/// Writes the System V style initial stack below `top` of the current address space, and returns
+/// the context to start the image from `entry` with the stack.
+///
+/// The entry is a function, so `[rsp]` is a null return address. The stack from `rsp + 8` contains
+/// `argc`, the null-terminated arrays of the pointers to the arguments and the environment
+/// variables, the auxiliary vector, and the strings. `argc`, `argv`, and `envp` are also passed in
+/// `rdi`, `rsi`, and `rdx`.
+///
+/// # Safety
+///
+/// `[top - size(args), top)` must be writable.
+pub(super) unsafe fn build(
+    top: VirtAddr,
+    entry: VirtAddr,
+    pml4: PhysFrame,
+    args: &ExecArgs<'_>,
+) -> Context {
+    let strings_start = top - args.strings_len();
+
+    let mut rsp = strings_start.align_down(16_u64) - size_of::<u64>() * num_of_words(args);
+
+    // `rsp + 8` must be 16-byte aligned as it is just after calling a function.
+    if rsp.is_aligned(16_u64) {
+        rsp -= size_of::<u64>();
+    }
+
+    let argv = rsp + size_of::<u64>() * 2;
+    let envp = argv + size_of::<u64>() * (args.argc() + 1);
+    let auxv = envp + size_of::<u64>() * (args.envc() + 1);
+
+    // SAFETY: The caller ensures that the stack is writable.
+    unsafe {
+        write(rsp, 0);
+        write(rsp + size_of::<u64>(), args.argc().try_into().unwrap());
+
+        let strings = write_strings(argv, strings_start, args.argv());
+
+        write_strings(envp, strings, args.envp());
+
+        let auxv_entries = [
+            (AT_PAGESZ, Size4KiB::SIZE),
+            (AT_ENTRY, entry.as_u64()),
+            (AT_NULL, 0),
+        ];
+
+        for (i, (ty, value)) in auxv_entries.into_iter().enumerate() {
+            write(auxv + size_of::<u64>() * 2 * i, ty);
+            write(auxv + size_of::<u64>() * (2 * i + 1), value);
+        }
+    }
+
+    let mut context = Context::user(entry, pml4, rsp);
+
+    context.set_main_arguments(args.argc().try_into().unwrap(), argv, envp);
+
+    context
+}
+
+// `[rsp]`, `argc`, the two pointer arrays with the null terminators, and the auxiliary vector.
+fn num_of_words(args: &ExecArgs<'_>) -> usize {
+    2 + (args.argc() + 1) + (args.envc() + 1) + 2 * NUM_OF_AUXV_ENTRIES
+}
+
+// Writes `strings` from `string_addr`, and their addresses from `pointers` followed by a null
+// pointer. Returns the address just after the last string.
+unsafe fn write_strings<'a>(
+    pointers: VirtAddr,
+    mut string_addr: VirtAddr,
+    strings: impl Iterator<Item = &'a [u8]>,
+) -> VirtAddr {
+    let mut pointer = pointers;
+
+    for s in strings {
+        // SAFETY: The caller ensures that the stack is writable.
+        unsafe {
+            core::ptr::copy_nonoverlapping(s.as_ptr(), string_addr.as_mut_ptr(), s.len());
+
+            write(pointer, string_addr.as_u64());
+        }
+
+        string_addr += s.len();
+        pointer += size_of::<u64>();
+    }
+
+    // SAFETY: The caller ensures that the stack is writable.
+    unsafe {
+        write(pointer, 0);
+    }
+
+    string_addr
+}
+
+unsafe fn write(addr: VirtAddr, value: u64) {
+    // SAFETY: The caller ensures that `addr` is writable.
+    unsafe {
+        ptr::write(addr.as_mut_ptr(), value);
+    }
+}
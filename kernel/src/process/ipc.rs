@@ -1,4 +1,6 @@
 pub(crate) use {
-    super::manager::{receive, send},
+    super::manager::{
+        notify, receive, receive_nonblock, receive_timeout, send, send_nonblock, send_receive,
+    },
     ipc_api::ReceiveFrom,
 };
@@ -1,69 +1,293 @@
 use {
-    super::{
-        context::Context, Priority, Process, ReceiveFrom, State, LEAST_PRIORITY_LEVEL, MAX_PID,
+    super::{context::Context, deadline, grant::Grant, Process, ReceiveFrom, State, MAX_PID},
+    crate::{
+        interrupt,
+        smp::{self, MAX_CPUS},
+        sync::{IrqSpinlock, IrqSpinlockGuard},
+        timer, tss,
     },
-    crate::{interrupt, tss},
-    heapless::{Deque, Vec},
-    ipc_api::{Error, Message},
-    pid::Pid,
-    spinning_top::{const_spinlock, Spinlock, SpinlockGuard},
+    alloc::{alloc::alloc, boxed::Box, vec::Vec},
+    core::{
+        alloc::Layout,
+        ptr,
+        sync::atomic::{AtomicBool, Ordering},
+    },
+    ipc_api::{
+        message::{Body, Header},
+        Error, Message,
+    },
+    pid::{predefined, Pid},
+    scheduler::{Priority, Scheduler},
+    syscalls::{ExecArgs, ExecError, GrantAccess, GrantId, MemoryError},
     vm::accessor::single::{read_write, ReadWrite},
-    x86_64::VirtAddr,
+    x86_64::{
+        structures::paging::{page::PageRange, Page, PageTableFlags},
+        VirtAddr,
+    },
 };
 
-static MANAGER: Spinlock<Manager<MAX_PID>> = const_spinlock(Manager::new());
+static MANAGER: IrqSpinlock<Manager<MAX_PID>> = IrqSpinlock::new(Manager::new());
+
+const _: () = assert!(
+    MAX_PID <= Pid::NUM_OF_INDICES,
+    "PIDs cannot address all slots."
+);
 
 pub(crate) fn switch() {
-    // It is impossible to inline `manager`. If it is inlined, the lock will not be unlocked until
-    // the end of the `if` statement. When the next process tries to switch to another, it fails to
-    // lock `MANAGER` because it is already locked.
-    let mut manager = lock();
+    switch_locked(lock());
+}
 
-    if let Some((current_context, next_context)) = manager.try_switch() {
+// Switches to another process, and unlocks `manager` before switching the context.
+//
+// The caller must block the running process and call this function without unlocking `manager`.
+// Otherwise, another processor may wake up the process and try to run it before this processor
+// switches from it.
+fn switch_locked(mut manager: IrqSpinlockGuard<'_, Manager<MAX_PID>>) {
+    if let Some((current_context, next_context, current_on_cpu)) = manager.try_switch() {
         // The lock is never unlocked after the context switch unless `manager` is dropped here,
         // causing a deadlock on the following process switch.
         drop(manager);
 
-        Context::switch(current_context, next_context);
+        Context::switch(current_context, next_context, current_on_cpu);
     }
 }
 
+/// Consumes a timer tick for the running process, and switches to another process if the running
+/// one has used up its time slice or a process with a higher priority is runnable.
+pub(crate) fn tick() {
+    let preempted = lock().tick();
+
+    if preempted {
+        switch();
+    }
+}
+
+/// Switches to a runnable process if the current processor is idle. Another processor interrupts
+/// this one with [`smp::RESCHEDULE_VECTOR`] when it assigns a process to this one.
+pub(crate) fn reschedule() {
+    let manager = lock();
+
+    if manager.running() == predefined::IDLE {
+        switch_locked(manager);
+    }
+}
+
+/// Changes the base priority of `pid`. Returns `false` if there is no such process.
+pub(crate) fn set_priority(pid: Pid, priority: Priority) -> bool {
+    interrupt::disable_interrupts_and_do(|| lock().set_priority(pid, priority))
+}
+
 pub(crate) fn send(to: Pid, message: Message) -> Result<(), Error> {
     // The kernel-privileged processes call this function directly, so at this point, the
-    // interrupts may not be disabled. If a process switch occurs during the execution of this
-    // function because of the timer interrupt, the kernel-privileged process still locks the
-    // process manager. When the following process tries to switch, it fails to lock the process
-    // manager because the preceding kernel-privileged process has already locked it. That is why
-    // we disable interrupts while sending a message to avoid a process switch during the execution
-    // of this function.
+    // interrupts may not be disabled. The process manager is unlocked just before the context
+    // switch, and if the timer interrupt occurs between them, the handler switches the context
+    // again while the process manager regards the next process as running. That is why we disable
+    // interrupts while sending a message.
     interrupt::disable_interrupts_and_do(|| send_without_disabling_interrupts(to, message))
 }
 
 pub(crate) fn receive(from: ReceiveFrom, buffer: *mut Message) -> Result<(), Error> {
     // The kernel-privileged processes call this function directly, so at this point, the
-    // interrupts may not be disabled. If a process switch occurs during the execution of this
-    // function because of the timer interrupt, the kernel-privileged process still locks the
-    // process manager. When the following process tries to switch, it fails to lock the process
-    // manager because the preceding kernel-privileged process has already locked it. That is why
-    // we disable interrupts while receiving a message to avoid a process switch during the
-    // execution of this function.
+    // interrupts may not be disabled. The process manager is unlocked just before the context
+    // switch, and if the timer interrupt occurs between them, the handler switches the context
+    // again while the process manager regards the next process as running. That is why we disable
+    // interrupts while receiving a message.
     interrupt::disable_interrupts_and_do(|| receive_without_disabling_interrupts(from, buffer))
 }
 
+/// Receives a message like [`receive`], but fails with [`Error::Timeout`] if no message arrives
+/// within `ticks` timer ticks.
+pub(crate) fn receive_timeout(
+    from: ReceiveFrom,
+    buffer: *mut Message,
+    ticks: u64,
+) -> Result<(), Error> {
+    // See the comment in `receive` for the reason why the interrupts are disabled.
+    interrupt::disable_interrupts_and_do(|| {
+        receive_timeout_without_disabling_interrupts(from, buffer, ticks)
+    })
+}
+
+pub(crate) fn send_receive(to: Pid, buffer: *mut Message) -> Result<(), Error> {
+    // See the comment in `send` for the reason why the interrupts are disabled.
+    interrupt::disable_interrupts_and_do(|| send_receive_without_disabling_interrupts(to, buffer))
+}
+
+/// Sends `message` to `to` only if `to` is waiting for a message from the running process.
+pub(crate) fn send_nonblock(to: Pid, mut message: Message) -> Result<(), Error> {
+    // See the comment in `send` for the reason why the interrupts are disabled.
+    interrupt::disable_interrupts_and_do(|| {
+        let mut manager = lock();
+
+        message.header = Header::new(manager.running());
+
+        manager.send_nonblock(to, message)
+    })
+}
+
+/// Receives a message only if a notification is pending or a process is sending a message to the
+/// running process.
+pub(crate) fn receive_nonblock(from: ReceiveFrom, buffer: *mut Message) -> Result<(), Error> {
+    // See the comment in `receive` for the reason why the interrupts are disabled.
+    interrupt::disable_interrupts_and_do(|| {
+        let mut manager = lock();
+
+        // SAFETY: The pointer is not dereferenced.
+        let buffer = unsafe { ptr_to_accessor(&mut manager, buffer) };
+
+        manager.receive_nonblock(from, buffer)
+    })
+}
+
+pub(crate) fn notify(to: Pid) -> Result<(), Error> {
+    // See the comment in `send` for the reason why the interrupts are disabled.
+    interrupt::disable_interrupts_and_do(|| lock().notify(to))
+}
+
+/// Returns the PID of the process running on the current processor.
+pub(crate) fn running() -> Pid {
+    lock().running()
+}
+
+/// Notifies `to` of an interrupt with `vector`. Returns `false` if there is no process with PID
+/// `to`.
+pub(crate) fn notify_interrupt(to: Pid, vector: u8) -> bool {
+    lock().notify_interrupt(to, vector)
+}
+
+/// Wakes up the processes whose deadlines to receive a message or to stop sleeping are not later
+/// than `now`.
+pub(crate) fn wake_expired(now: u64) {
+    lock().wake_expired(now);
+}
+
+/// Returns the number of ticks after which the timer must interrupt, or `None` if no process needs
+/// the timer. The running process, if not idle, needs it every tick to consume its time slice.
+pub(crate) fn ticks_until_next_event(now: u64) -> Option<u64> {
+    lock().ticks_until_next_event(now)
+}
+
+/// Lets `pid` sleep until the tick `deadline`, then replies an empty message to it on behalf of
+/// SYSPROC. Returns `false` if `pid` is not waiting for the reply from SYSPROC.
+pub(crate) fn sleep_until(pid: Pid, deadline: u64) -> bool {
+    interrupt::disable_interrupts_and_do(|| lock().sleep_until(pid, deadline))
+}
+
+/// Maps the page containing `addr` of the address space of `pid` to a zeroed frame if the page is
+/// not mapped but belongs to one of the regions of the process. Returns `true` if the page is
+/// mapped.
+pub(crate) fn populate(pid: Pid, addr: VirtAddr) -> bool {
+    interrupt::disable_interrupts_and_do(|| lock().populate(pid, addr))
+}
+
+/// Moves the end of the heap of `pid` to `end`, and returns the new end. The heap does not change
+/// if `end` is `None`. The pages removed from the heap are unmapped.
+pub(crate) fn resize_heap(pid: Pid, end: Option<Page>) -> Result<VirtAddr, MemoryError> {
+    interrupt::disable_interrupts_and_do(|| lock().resize_heap(pid, end))
+}
+
+/// Adds an anonymous region of `n` pages with `flags` to `pid`, and returns its start address. The
+/// pages are mapped when they are accessed.
+pub(crate) fn map_anonymous(
+    pid: Pid,
+    n: u64,
+    flags: PageTableFlags,
+) -> Result<VirtAddr, MemoryError> {
+    interrupt::disable_interrupts_and_do(|| lock().map_anonymous(pid, n, flags))
+}
+
+/// Removes `n` pages from `start` from the anonymous regions of `pid`, and unmaps them.
+pub(crate) fn unmap_anonymous(pid: Pid, start: Page, n: u64) -> Result<(), MemoryError> {
+    interrupt::disable_interrupts_and_do(|| lock().unmap_anonymous(pid, start, n))
+}
+
+/// Changes the flags of `n` pages from `start` in the anonymous regions of `pid` to `flags`.
+pub(crate) fn protect_anonymous(
+    pid: Pid,
+    start: Page,
+    n: u64,
+    flags: PageTableFlags,
+) -> Result<(), MemoryError> {
+    interrupt::disable_interrupts_and_do(|| lock().protect_anonymous(pid, start, n, flags))
+}
+
 pub(crate) fn enter_address_space_and_do<T>(pid: Pid, f: impl FnOnce() -> T) -> T {
     interrupt::disable_interrupts_and_do(|| lock().enter_address_space_and_do(pid, f))
 }
 
+pub(super) fn remove(pid: Pid) -> Option<Process> {
+    lock().remove(pid)
+}
+
+/// Replaces the image of `pid` with `binary`. `pid` keeps the old image if it fails.
+///
+/// # Errors
+///
+/// This function returns [`ExecError::InvalidArguments`] if `pid` is not waiting for the reply from
+/// PM, and [`ExecError::LoadFailed`] if `binary` cannot be loaded.
+pub(super) fn exec(pid: Pid, binary: &[u8], args: &ExecArgs<'_>) -> Result<(), ExecError> {
+    lock().exec(pid, binary, args)
+}
+
+/// Adds a copy of `parent` with an unused PID and returns the PID. Returns `None` if `parent` is not
+/// waiting for the reply from PM or the copy cannot be created.
+pub(super) fn fork(parent: Pid) -> Option<Pid> {
+    lock().fork(parent)
+}
+
 pub(crate) fn process_exists(pid: Pid) -> bool {
     lock().exists(pid)
 }
 
-pub(super) fn init() {
-    lock().init();
+pub(crate) fn create_grant(granter: Pid, grant: Grant) -> Option<GrantId> {
+    interrupt::disable_interrupts_and_do(|| lock().create_grant(granter, grant))
+}
+
+pub(crate) fn revoke_grant(granter: Pid, id: GrantId) -> Option<Grant> {
+    interrupt::disable_interrupts_and_do(|| lock().revoke_grant(granter, id))
+}
+
+/// Returns the granter's PID and the start address of `[offset, offset + len)` of the region
+/// granted by `id` if `grantee` is allowed to access it with `access`.
+pub(crate) fn resolve_grant(
+    id: GrantId,
+    grantee: Pid,
+    offset: usize,
+    len: usize,
+    access: GrantAccess,
+) -> Option<(Pid, VirtAddr)> {
+    interrupt::disable_interrupts_and_do(|| lock().resolve_grant(id, grantee, offset, len, access))
+}
+
+/// Returns `true` if one of the grants of `granter` allows `grantee` to access `[start, start +
+/// len)` with `access`.
+pub(crate) fn is_granted(
+    granter: Pid,
+    grantee: Pid,
+    start: VirtAddr,
+    len: usize,
+    access: GrantAccess,
+) -> bool {
+    interrupt::disable_interrupts_and_do(|| lock().is_granted(granter, grantee, start, len, access))
 }
 
+/// # Panics
+///
+/// This function panics if the kernel heap has no room for the process.
 pub(super) fn add(p: Process) {
-    lock().add(p);
+    assert!(try_add(p), "Failed to add a process.");
+}
+
+/// Adds `p`. Returns `false` and frees the address space of `p` if the kernel heap has no room for
+/// it.
+pub(super) fn try_add(p: Process) -> bool {
+    lock().try_add(p)
+}
+
+/// Assigns an unused PID to `p`, adds it, and returns the PID. Returns `None` and frees the address
+/// space of `p` if there are too many processes or the kernel heap has no room for it.
+pub(super) fn try_add_with_new_pid(p: Process) -> Option<Pid> {
+    lock().try_add_with_new_pid(p)
 }
 
 pub(super) fn add_idle() {
@@ -76,108 +300,319 @@ fn current_kernel_stack_bottom() -> u64 {
 }
 
 fn send_without_disabling_interrupts(to: Pid, mut message: Message) -> Result<(), Error> {
-    message.header.sender_pid = lock().running;
+    let mut manager = lock();
+
+    // The header is overwritten so that the sender cannot forge it.
+    message.header = Header::new(manager.running());
 
-    lock().send(to, message)?;
+    manager.send(to, message)?;
 
     // This switch is necessary because the sender may wait for the receiver.
-    switch();
+    switch_locked(manager);
 
-    Ok(())
+    take_wakeup_error()
+}
+
+fn send_receive_without_disabling_interrupts(to: Pid, buffer: *mut Message) -> Result<(), Error> {
+    // SAFETY: The caller passes a pointer to the message to send.
+    let mut message = unsafe { buffer.read() };
+
+    let mut manager = lock();
+
+    message.header = Header::new(manager.running());
+
+    // SAFETY: The pointer is not dereferenced.
+    let buffer = unsafe { ptr_to_accessor(&mut manager, buffer) };
+
+    manager.send_receive(to, message, buffer)?;
+
+    // This switch is necessary because the sender waits for the reply.
+    switch_locked(manager);
+
+    take_wakeup_error()
 }
 
 fn receive_without_disabling_interrupts(
     from: ReceiveFrom,
     buffer: *mut Message,
 ) -> Result<(), Error> {
+    let mut manager = lock();
+
+    // SAFETY: The pointer is not dereferenced.
+    let buffer = unsafe { ptr_to_accessor(&mut manager, buffer) };
+
+    manager.receive(from, buffer)?;
+
+    // This switch is necessary because the receiver may wait for the sender.
+    switch_locked(manager);
+
+    take_wakeup_error()
+}
+
+fn receive_timeout_without_disabling_interrupts(
+    from: ReceiveFrom,
+    buffer: *mut Message,
+    ticks: u64,
+) -> Result<(), Error> {
+    let deadline = timer::ticks().saturating_add(ticks);
+
+    let mut manager = lock();
+
     // SAFETY: The pointer is not dereferenced.
-    lock().receive(from, unsafe { ptr_to_accessor(buffer) })?;
+    let buffer = unsafe { ptr_to_accessor(&mut manager, buffer) };
+
+    manager.receive_timeout(from, buffer, deadline)?;
 
     // This switch is necessary because the receiver may wait for the sender.
-    switch();
+    switch_locked(manager);
 
-    Ok(())
+    take_wakeup_error()
 }
 
-fn lock<'a>() -> SpinlockGuard<'a, Manager<MAX_PID>> {
-    let m = MANAGER.try_lock();
+// Returns the error set while the running process was sleeping, such as a timeout.
+fn take_wakeup_error() -> Result<(), Error> {
+    lock()
+        .running_as_mut()
+        .wakeup_error
+        .take()
+        .map_or(Ok(()), Err)
+}
+
+// Returns `n` pages from `start` if they are in the region for the anonymous memory.
+fn anonymous_pages(start: Page, n: u64) -> Result<PageRange, MemoryError> {
+    let region = predefined_mmap::user_mmap();
 
-    m.expect("Failed to lock the process manager.")
+    if n == 0 || start < region.start || start >= region.end || region.end - start < n {
+        return Err(MemoryError::InvalidAddress);
+    }
+
+    Ok(PageRange {
+        start,
+        end: start + n,
+    })
+}
+
+// Other processors may hold the lock, so this function spins until it is unlocked.
+fn lock<'a>() -> IrqSpinlockGuard<'a, Manager<MAX_PID>> {
+    MANAGER.lock()
 }
 
 /// # Safety
 ///
 /// The caller must not dereference `p` while the returned accessor is alive.
-unsafe fn ptr_to_accessor<T>(p: *mut T) -> ReadWrite<T> {
+unsafe fn ptr_to_accessor<T>(manager: &mut Manager<MAX_PID>, p: *mut T) -> ReadWrite<T> {
     let p = VirtAddr::from_ptr(p);
+
+    // The buffer may be in a page which is not accessed yet. The address space of the running
+    // process is the current one.
+    if vm::translate(p).is_none() {
+        let running = manager.running();
+
+        manager.populate(running, p);
+    }
+
+    // The frame is written directly, so a copy-on-write page must be copied here.
+    let _ = vm::copy_on_write(p);
+
     let p = vm::translate(p);
     let p = p.expect("The address is not mapped.");
     unsafe { read_write(p) }
 }
 
 struct Manager<const N: usize> {
-    processes: [Option<Process>; N],
+    // Indexed by `Pid::index`. At most `N` slots are allocated as processes are added. The slot of
+    // `predefined::IDLE` is not used because each processor has its own idle process.
+    slots: Vec<Slot>,
 
-    // The running PID is not contained.
-    runnable_pids: RunnablePids<{ LEAST_PRIORITY_LEVEL + 1 }>,
+    // Indexed by `smp::index`.
+    processors: [Processor<N>; MAX_CPUS],
 
-    running: Pid,
+    deadlines: deadline::Queue,
 }
 impl<const N: usize> Manager<N> {
     const fn new() -> Self {
-        const UNUSED_PROCESS_ENTRY: Option<Process> = None;
-
         Self {
-            processes: [UNUSED_PROCESS_ENTRY; N],
-            runnable_pids: RunnablePids::new(),
-            running: Pid::new(0),
+            slots: Vec::new(),
+            processors: [const { Processor::new() }; MAX_CPUS],
+            deadlines: deadline::Queue::new(),
         }
     }
 
-    fn init(&mut self) {
-        self.runnable_pids.init();
-    }
-
     fn add_idle(&mut self) {
         let idle = super::Process::idle();
 
-        assert_eq!(idle.pid, Pid::new(0), "Wrong PID for the idle process.");
+        assert_eq!(
+            idle.pid,
+            predefined::IDLE,
+            "Wrong PID for the idle process."
+        );
 
         tss::set_kernel_stack_addr(idle.kernel_stack_bottom_addr());
 
-        self.add_to_process_collection(idle);
+        let num_of_slots = self.slots.len();
+
+        let processor = self.processor_mut();
+
+        assert!(
+            processor.idle.is_none(),
+            "The idle process is already added."
+        );
+
+        // See the comment in `reserve`.
+        if let Some(last) = num_of_slots.checked_sub(1) {
+            let r = processor
+                .scheduler
+                .reserve(Pid::from_index_and_generation(last, 0));
+            r.expect("No memory for the run queues.");
+        }
+
+        processor.idle = Some(idle);
     }
 
-    fn add(&mut self, p: Process) {
-        self.add_to_runnable_pid_queue(&p);
+    fn try_add(&mut self, mut p: Process) -> bool {
+        let index = self.least_loaded_processor();
+
+        // `Box::try_new` is not stable.
+        let ptr: *mut Process = if self.reserve(p.pid, index) {
+            // SAFETY: `Process` is not zero-sized.
+            unsafe { alloc(Layout::new::<Process>()) }.cast()
+        } else {
+            ptr::null_mut()
+        };
+
+        if ptr.is_null() {
+            // SAFETY: The process is not added, so no one uses its address space.
+            unsafe {
+                super::free_address_space(p.pml4);
+            }
+
+            return false;
+        }
+
+        p.processor = index;
+
+        // SAFETY: `ptr` is allocated by the global allocator with the layout of `Process`.
+        let p = unsafe {
+            ptr.write(p);
+
+            Box::from_raw(ptr)
+        };
+
+        self.processors[index].scheduler.add(p.pid, p.priority);
         self.add_to_process_collection(p);
+
+        self.kick(index);
+
+        true
+    }
+
+    fn try_add_with_new_pid(&mut self, mut p: Process) -> Option<Pid> {
+        // The PID is generated and taken under the same lock, so no other processor takes it.
+        let pid = match self.generate_pid() {
+            Some(pid) => pid,
+            None => {
+                // SAFETY: The process is not added, so no one uses its address space.
+                unsafe {
+                    super::free_address_space(p.pml4);
+                }
+
+                return None;
+            }
+        };
+
+        p.pid = pid;
+
+        self.try_add(p).then_some(pid)
     }
 
-    fn add_to_runnable_pid_queue(&mut self, process: &Process) {
-        self.runnable_pids.push(process.pid, process.priority);
+    // Allocates the memory to add `pid` to the processor `index`. Returns `false` if the kernel
+    // heap has no room.
+    //
+    // The run queues of all the processors are reserved so that waking, stealing, and migrating
+    // processes in the interrupt handlers do not allocate memory.
+    fn reserve(&mut self, pid: Pid, index: usize) -> bool {
+        assert!(pid.index() < N, "Too large PID: {}", pid);
+
+        if self.slots.len() <= pid.index() {
+            if self
+                .slots
+                .try_reserve(pid.index() + 1 - self.slots.len())
+                .is_err()
+            {
+                return false;
+            }
+
+            self.slots.resize_with(pid.index() + 1, Slot::default);
+        }
+
+        // Each slot has at most one process waiting for the deadline.
+        self.deadlines.reserve(self.slots.len()).is_ok()
+            && self
+                .processors
+                .iter_mut()
+                .enumerate()
+                .filter(|(i, p)| *i == index || p.idle.is_some())
+                .all(|(_, p)| p.scheduler.reserve(pid).is_ok())
     }
 
-    fn add_to_process_collection(&mut self, p: Process) {
+    fn add_to_process_collection(&mut self, p: Box<Process>) {
         let pid = p.pid;
 
-        let entry = &mut self.processes[pid.as_usize()];
+        let slot = &mut self.slots[pid.index()];
+
+        assert_eq!(slot.generation, pid.generation(), "Stale PID: {}", pid);
 
-        if entry.is_some() {
+        if slot.process.is_some() {
             panic!("{} is double-used.", pid);
         } else {
-            *entry = Some(p);
+            // The process is boxed so that its context does not move while a processor switches
+            // to or from it without the lock.
+            slot.process = Some(p);
         }
     }
 
+    fn generate_pid(&self) -> Option<Pid> {
+        let index = (1..self.slots.len()).find(|&i| self.slots[i].process.is_none());
+        let index = index.unwrap_or_else(|| self.slots.len().max(1));
+
+        let generation = self.slots.get(index).map_or(0, |s| s.generation);
+
+        (index < N).then(|| Pid::from_index_and_generation(index, generation))
+    }
+
+    fn tick(&mut self) -> bool {
+        let running = self.running();
+
+        let preempted = self.processor_mut().scheduler.tick(running);
+
+        // An idle processor also takes over the processes waiting on the other processors.
+        preempted || (running == predefined::IDLE && self.busiest_processor().is_some())
+    }
+
+    fn set_priority(&mut self, pid: Pid, priority: Priority) -> bool {
+        // The idle process is not scheduled by the priority.
+        if pid == predefined::IDLE || !self.exists(pid) {
+            return false;
+        }
+
+        let process = self.process_as_mut(pid);
+
+        process.priority = priority;
+
+        let index = process.processor;
+
+        self.processors[index].scheduler.set_priority(pid, priority);
+
+        true
+    }
+
     fn exists(&self, pid: Pid) -> bool {
-        self.processes
-            .get(pid.as_usize())
-            .map_or(false, Option::is_some)
+        self.get(pid).is_some()
     }
 
     // Do not switch the context inside this method. Otherwise, the lock of `MANAGER` will never be
     // unlocked during the execution of the next process, causing a deadlock.
-    fn try_switch(&mut self) -> Option<(*mut Context, *mut Context)> {
+    fn try_switch(&mut self) -> Option<(*mut Context, *mut Context, *const AtomicBool)> {
         Switcher::from(self).try_switch()
     }
 
@@ -185,11 +620,292 @@ impl<const N: usize> Manager<N> {
         Sender::new(self, to, message)?.send()
     }
 
+    fn send_receive(
+        &mut self,
+        to: Pid,
+        message: Message,
+        buffer: ReadWrite<Message>,
+    ) -> Result<(), Error> {
+        Sender::new(self, to, message)?.send_receive(buffer)
+    }
+
     fn receive(&mut self, from: ReceiveFrom, buffer: ReadWrite<Message>) -> Result<(), Error> {
-        Receiver::new(self, from, buffer)?.receive()
+        Receiver::new(self, from, buffer)?.receive(None)
+    }
+
+    fn receive_timeout(
+        &mut self,
+        from: ReceiveFrom,
+        buffer: ReadWrite<Message>,
+        deadline: u64,
+    ) -> Result<(), Error> {
+        Receiver::new(self, from, buffer)?.receive(Some(deadline))
+    }
+
+    fn send_nonblock(&mut self, to: Pid, message: Message) -> Result<(), Error> {
+        Sender::new(self, to, message)?.send_nonblock()
+    }
+
+    fn receive_nonblock(
+        &mut self,
+        from: ReceiveFrom,
+        buffer: ReadWrite<Message>,
+    ) -> Result<(), Error> {
+        Receiver::new(self, from, buffer)?.receive_nonblock()
+    }
+
+    fn notify(&mut self, to: Pid) -> Result<(), Error> {
+        if !self.exists(to) {
+            return Err(Error::NoSuchProcess(to.into()));
+        }
+
+        let sender = self.running();
+
+        self.process_as_mut(to).pending_notifications.set(sender);
+
+        self.deliver_notification(to, sender);
+
+        Ok(())
+    }
+
+    fn notify_interrupt(&mut self, to: Pid, vector: u8) -> bool {
+        if !self.exists(to) {
+            return false;
+        }
+
+        let receiver = self.process_as_mut(to);
+
+        receiver.pending_notifications.set_interrupt(vector);
+
+        self.deliver_notification(to, predefined::HARDWARE);
+
+        true
+    }
+
+    // Delivers the pending notification from `sender` if `to` is waiting for it.
+    fn deliver_notification(&mut self, to: Pid, sender: Pid) {
+        let receiver = self.process_as_mut(to);
+
+        if !receiver.is_waiting_for_notification_from(sender) {
+            return;
+        }
+
+        let notification = receiver.pending_notifications.take(sender.into());
+        let notification = notification.expect("No pending notification.");
+
+        let message_buffer = receiver.message_buffer.take();
+        let mut message_buffer = message_buffer.expect("No message buffer.");
+
+        message_buffer.write_volatile(notification);
+
+        self.wake(to);
+    }
+
+    fn create_grant(&mut self, granter: Pid, grant: Grant) -> Option<GrantId> {
+        let index = self.process_as_mut(granter).grants.add(grant)?;
+
+        Some(GrantId::new(granter, index))
+    }
+
+    fn revoke_grant(&mut self, granter: Pid, id: GrantId) -> Option<Grant> {
+        if id.granter() == granter {
+            self.process_as_mut(granter).grants.remove(id.index())
+        } else {
+            None
+        }
+    }
+
+    fn resolve_grant(
+        &self,
+        id: GrantId,
+        grantee: Pid,
+        offset: usize,
+        len: usize,
+        access: GrantAccess,
+    ) -> Option<(Pid, VirtAddr)> {
+        let granter = id.granter();
+
+        let process = self.get(granter)?;
+        let grant = process.grants.get(id.index())?;
+
+        grant
+            .resolve(grantee, offset, len, access)
+            .map(|addr| (granter, addr))
+    }
+
+    fn is_granted(
+        &self,
+        granter: Pid,
+        grantee: Pid,
+        start: VirtAddr,
+        len: usize,
+        access: GrantAccess,
+    ) -> bool {
+        self.get(granter).is_some_and(|p| {
+            p.grants
+                .iter()
+                .any(|g| g.covers(grantee, start, len, access))
+        })
+    }
+
+    fn wake_expired(&mut self, now: u64) {
+        while let Some(pid) = self.deadlines.pop_expired(now) {
+            if self.process_as_ref(pid).state == State::Sleeping {
+                self.reply_and_wake(pid, predefined::SYSPROC);
+            } else {
+                self.wake_with_error(pid, Error::Timeout);
+            }
+        }
+    }
+
+    fn ticks_until_next_event(&self, now: u64) -> Option<u64> {
+        if self.processor().is_busy() || self.busiest_processor().is_some() {
+            return Some(1);
+        }
+
+        // The bootstrap processor keeps the tick count and checks the deadlines, so it ticks while
+        // any processor runs a process.
+        if !smp::is_bsp() {
+            return None;
+        }
+
+        if self.processors.iter().any(Processor::is_busy) {
+            return Some(1);
+        }
+
+        self.deadlines
+            .earliest()
+            .map(|deadline| deadline.saturating_sub(now))
+    }
+
+    fn sleep_until(&mut self, pid: Pid, deadline: u64) -> bool {
+        match self.get_mut(pid) {
+            // A user process waiting for the reply from SYSPROC is in the `sleep` system call.
+            Some(p) if p.state == State::ReceivingReply(predefined::SYSPROC) => {
+                p.state = State::Sleeping;
+            }
+            _ => return false,
+        }
+
+        self.deadlines.push(pid, deadline);
+
+        true
+    }
+
+    fn remove(&mut self, pid: Pid) -> Option<Process> {
+        assert!(
+            self.processors.iter().all(|p| p.running != pid),
+            "Cannot remove the running process."
+        );
+
+        let slot = self.slots.get_mut(pid.index())?;
+
+        if slot.process.as_ref()?.pid != pid {
+            return None;
+        }
+
+        let process = slot.process.take()?;
+
+        // The next process in this slot has a different PID.
+        slot.generation = pid.next_generation().generation();
+
+        self.processors[process.processor].scheduler.remove(pid);
+        self.deadlines.remove(pid);
+
+        if let Some(to) = process.state.sending_to() {
+            self.process_as_mut(to)
+                .sending_to_this
+                .retain(|p| *p != pid);
+        }
+
+        // The processes sending messages to the removed one, waiting for its reply, or receiving
+        // messages from it would never be woken up.
+        let error = Error::NoSuchProcess(pid.into());
+
+        for &sender in &process.sending_to_this {
+            self.wake_with_error(sender, error);
+        }
+
+        for i in 0..self.slots.len() {
+            let waiting = self.slots[i]
+                .process
+                .as_ref()
+                .filter(|p| p.is_waiting_for_message_only_from(pid))
+                .map(|p| p.pid);
+
+            if let Some(waiting) = waiting {
+                self.wake_with_error(waiting, error);
+            }
+        }
+
+        // The processor which ran the process last may be still on its kernel stack.
+        process.wait_until_switched_out();
+
+        Some(*process)
+    }
+
+    fn exec(&mut self, pid: Pid, binary: &[u8], args: &ExecArgs<'_>) -> Result<(), ExecError> {
+        match self.get_mut(pid) {
+            // A user process waiting for the reply from PM is in the `exec` system call.
+            Some(p) if p.state == State::ReceivingReply(predefined::PM) => {
+                if !p.exec(binary, args) {
+                    return Err(ExecError::LoadFailed);
+                }
+
+                p.message_buffer = None;
+            }
+            _ => return Err(ExecError::InvalidArguments),
+        }
+
+        // The process starts the new image instead of returning from the system call.
+        self.wake(pid);
+
+        Ok(())
+    }
+
+    fn fork(&mut self, parent: Pid) -> Option<Pid> {
+        // A user process waiting for the reply from PM is in the `fork` system call.
+        let parent = match self.get(parent) {
+            Some(p) if p.state == State::ReceivingReply(predefined::PM) => p,
+            _ => return None,
+        };
+
+        // See the comment in `try_add_with_new_pid`.
+        let pid = self.generate_pid()?;
+
+        let child = Process::try_fork(pid, parent)?;
+
+        self.try_add(child).then_some(pid)
+    }
+
+    // The blocking IPC function called by the process returns `error`.
+    fn wake_with_error(&mut self, pid: Pid, error: Error) {
+        let process = self.process_as_mut(pid);
+
+        process.message_buffer = None;
+        process.wakeup_error = Some(error);
+
+        self.wake(pid);
+    }
+
+    // The blocking IPC function called by the process receives an empty message from `sender`.
+    fn reply_and_wake(&mut self, pid: Pid, sender: Pid) {
+        let process = self.process_as_mut(pid);
+
+        let message_buffer = process.message_buffer.take();
+        let mut message_buffer = message_buffer.expect("No message buffer.");
+
+        message_buffer.write_volatile(Message {
+            header: Header::new(sender),
+            body: Body::default(),
+        });
+
+        self.wake(pid);
     }
 
     fn wake(&mut self, pid: Pid) {
+        self.deadlines.remove(pid);
+
         let proc = self.process_as_mut(pid);
 
         assert!(
@@ -199,10 +915,198 @@ impl<const N: usize> Manager<N> {
 
         proc.state = State::Runnable;
 
-        let pid = proc.pid;
-        let priority = proc.priority;
+        let last = proc.processor;
+
+        let index = self.processor_to_wake_on(last);
+
+        if index != last {
+            self.migrate(pid, last, index);
+        }
 
-        self.runnable_pids.push(pid, priority);
+        // The process blocked on IPC, so it is promoted.
+        self.processors[index].scheduler.wake(pid);
+
+        self.kick(index);
+    }
+
+    // Returns the processor to run the woken process which ran on `last`. The process stays on the
+    // same processor unless another one has fewer processes to run.
+    fn processor_to_wake_on(&self, last: usize) -> usize {
+        let least = self.least_loaded_processor();
+
+        if self.processors[least].load() < self.processors[last].load() {
+            least
+        } else {
+            last
+        }
+    }
+
+    // Returns the processor with the fewest processes to run among those running the processes.
+    // The current processor is preferred if there is a tie.
+    fn least_loaded_processor(&self) -> usize {
+        let current = smp::index();
+
+        (0..MAX_CPUS)
+            .filter(|&i| self.processors[i].idle.is_some())
+            .min_by_key(|&i| (self.processors[i].load(), i != current))
+            .unwrap_or(current)
+    }
+
+    // Returns the processor other than the current one which has the most runnable processes
+    // waiting, or `None` if no processes are waiting on the other processors.
+    fn busiest_processor(&self) -> Option<usize> {
+        let current = smp::index();
+
+        (0..MAX_CPUS)
+            .filter(|&i| i != current && !self.processors[i].scheduler.is_empty())
+            .max_by_key(|&i| self.processors[i].scheduler.len())
+    }
+
+    // Moves the process `pid` waiting on the busiest processor to the current one, and returns it.
+    fn steal(&mut self) -> Option<Pid> {
+        let from = self.busiest_processor()?;
+
+        let pid = self.processors[from].scheduler.pop()?;
+
+        self.migrate(pid, from, smp::index());
+
+        Some(pid)
+    }
+
+    fn migrate(&mut self, pid: Pid, from: usize, to: usize) {
+        let [from_processor, to_processor] = self.processors.get_disjoint_mut([from, to]).unwrap();
+
+        from_processor
+            .scheduler
+            .migrate(pid, &mut to_processor.scheduler);
+
+        self.process_as_mut(pid).processor = to;
+    }
+
+    // Interrupts the processor `index` to run its processes if it is idle. The processors which are
+    // not idle run them on the timer interrupts.
+    fn kick(&self, index: usize) {
+        if index != smp::index() && self.processors[index].running == predefined::IDLE {
+            smp::send_interrupt(index, smp::RESCHEDULE_VECTOR);
+        }
+    }
+
+    fn populate(&mut self, pid: Pid, addr: VirtAddr) -> bool {
+        let process = self.process_as_mut(pid);
+        let pml4 = process.pml4;
+
+        let map = || {
+            if vm::translate(addr).is_some() {
+                return true;
+            }
+
+            process.regions.find(addr).is_some_and(|flags| {
+                // SAFETY: The page is not mapped, and the regions are in the user region.
+                unsafe { vm::map_zeroed_page(Page::containing_address(addr), flags) }
+            })
+        };
+
+        // SAFETY: `pml4` is the correct PML4.
+        unsafe { super::switch_pml4_do(pml4, map) }
+    }
+
+    fn resize_heap(&mut self, pid: Pid, end: Option<Page>) -> Result<VirtAddr, MemoryError> {
+        let process = self.get_mut(pid).ok_or(MemoryError::InvalidArguments)?;
+        let pml4 = process.pml4;
+
+        let heap = process.regions.heap_mut();
+        let heap = heap.ok_or(MemoryError::InvalidArguments)?;
+
+        let old = heap.pages();
+
+        let end = match end {
+            Some(end) => end,
+            None => return Ok(old.end.start_address()),
+        };
+
+        if end < old.start {
+            return Err(MemoryError::InvalidAddress);
+        }
+
+        // The heap must not overlap the anonymous regions.
+        if end > predefined_mmap::user_mmap().start {
+            return Err(MemoryError::NoRegion);
+        }
+
+        heap.set_end(end);
+
+        if end < old.end {
+            let removed = PageRange {
+                start: end,
+                end: old.end,
+            };
+
+            // SAFETY: `pml4` is the correct PML4, and the pages are not in the heap anymore.
+            unsafe {
+                super::switch_pml4_do(pml4, || vm::unmap_user_pages(removed));
+            }
+        }
+
+        Ok(end.start_address())
+    }
+
+    fn map_anonymous(
+        &mut self,
+        pid: Pid,
+        n: u64,
+        flags: PageTableFlags,
+    ) -> Result<VirtAddr, MemoryError> {
+        if n == 0 {
+            return Err(MemoryError::InvalidArguments);
+        }
+
+        let process = self.get_mut(pid).ok_or(MemoryError::InvalidArguments)?;
+
+        let pages = process
+            .regions
+            .add_anonymous(n, flags, predefined_mmap::user_mmap());
+
+        pages
+            .map(|pages| pages.start.start_address())
+            .ok_or(MemoryError::NoRegion)
+    }
+
+    fn unmap_anonymous(&mut self, pid: Pid, start: Page, n: u64) -> Result<(), MemoryError> {
+        let pages = anonymous_pages(start, n)?;
+
+        let process = self.get_mut(pid).ok_or(MemoryError::InvalidArguments)?;
+
+        process.regions.remove_anonymous(pages);
+
+        // SAFETY: `pml4` is the correct PML4, and the pages are not in the regions anymore.
+        unsafe {
+            super::switch_pml4_do(process.pml4, || vm::unmap_user_pages(pages));
+        }
+
+        Ok(())
+    }
+
+    fn protect_anonymous(
+        &mut self,
+        pid: Pid,
+        start: Page,
+        n: u64,
+        flags: PageTableFlags,
+    ) -> Result<(), MemoryError> {
+        let pages = anonymous_pages(start, n)?;
+
+        let process = self.get_mut(pid).ok_or(MemoryError::InvalidArguments)?;
+
+        if !process.regions.protect_anonymous(pages, flags) {
+            return Err(MemoryError::InvalidAddress);
+        }
+
+        // SAFETY: `pml4` is the correct PML4.
+        unsafe {
+            super::switch_pml4_do(process.pml4, || vm::protect_user_pages(pages, flags));
+        }
+
+        Ok(())
     }
 
     fn enter_address_space_and_do<T>(&self, pid: Pid, f: impl FnOnce() -> T) -> T {
@@ -214,68 +1118,154 @@ impl<const N: usize> Manager<N> {
         self.running_as_ref().kernel_stack_bottom_addr()
     }
 
+    fn running(&self) -> Pid {
+        self.processor().running
+    }
+
     fn running_as_ref(&self) -> &Process {
-        self.process_as_ref(self.running)
+        self.process_as_ref(self.running())
     }
 
     fn running_as_mut(&mut self) -> &mut Process {
-        self.process_as_mut(self.running)
+        self.process_as_mut(self.running())
+    }
+
+    fn processor(&self) -> &Processor<N> {
+        &self.processors[smp::index()]
+    }
+
+    fn processor_mut(&mut self) -> &mut Processor<N> {
+        &mut self.processors[smp::index()]
     }
 
     fn process_as_ref(&self, pid: Pid) -> &Process {
-        let proc = self.processes[pid.as_usize()].as_ref();
+        let proc = self.get(pid);
 
         proc.unwrap_or_else(|| panic!("No entry for the process with {}", pid))
     }
 
     fn process_as_mut(&mut self, pid: Pid) -> &mut Process {
-        let proc = self.processes[pid.as_usize()].as_mut();
+        let proc = self.get_mut(pid);
 
         proc.unwrap_or_else(|| panic!("No entry for the process with {}", pid))
     }
+
+    // Returns `None` if `pid` is stale. `predefined::IDLE` is the idle process of the current
+    // processor.
+    fn get(&self, pid: Pid) -> Option<&Process> {
+        if pid == predefined::IDLE {
+            return self.processor().idle.as_ref();
+        }
+
+        let proc = self.slots.get(pid.index())?.process.as_deref();
+
+        proc.filter(|p| p.pid == pid)
+    }
+
+    fn get_mut(&mut self, pid: Pid) -> Option<&mut Process> {
+        if pid == predefined::IDLE {
+            return self.processor_mut().idle.as_mut();
+        }
+
+        let proc = self.slots.get_mut(pid.index())?.process.as_deref_mut();
+
+        proc.filter(|p| p.pid == pid)
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    // The generation of the PID of the current or the next process in this slot.
+    generation: usize,
+    process: Option<Box<Process>>,
+}
+
+// The processes assigned to a processor.
+struct Processor<const N: usize> {
+    // The running process and the idle process are not queued.
+    scheduler: Scheduler<N>,
+
+    running: Pid,
+
+    // `None` until the processor starts running the processes.
+    idle: Option<Process>,
+}
+impl<const N: usize> Processor<N> {
+    const fn new() -> Self {
+        Self {
+            scheduler: Scheduler::new(),
+            running: predefined::IDLE,
+            idle: None,
+        }
+    }
+
+    // The number of the processes to run, including the running one.
+    fn load(&self) -> usize {
+        self.scheduler.len() + usize::from(self.running != predefined::IDLE)
+    }
+
+    fn is_busy(&self) -> bool {
+        self.load() > 0
+    }
 }
 
 struct Switcher<'a, const N: usize>(&'a mut Manager<N>);
 impl<const N: usize> Switcher<'_, N> {
-    fn try_switch(mut self) -> Option<(*mut Context, *mut Context)> {
+    fn try_switch(mut self) -> Option<(*mut Context, *mut Context, *const AtomicBool)> {
         let next = self.update_runnable_pids_and_return_next_pid();
 
-        (self.0.running != next).then(|| self.switch_to(next))
+        (self.0.running() != next).then(|| self.switch_to(next))
     }
 
     fn update_runnable_pids_and_return_next_pid(&mut self) -> Pid {
-        if self.0.running_as_ref().state == State::Running {
-            self.push_current_process_as_runnable();
-        }
-
-        self.0.runnable_pids.pop()
-    }
+        let running = self.0.running();
 
-    fn push_current_process_as_runnable(&mut self) {
-        let process = self.0.running_as_ref();
+        if running != predefined::IDLE && self.0.running_as_ref().state == State::Running {
+            self.0.processor_mut().scheduler.requeue(running);
+        }
 
-        let pid = process.pid;
-        let priority = process.priority;
+        // The idle process runs only if no other processes are runnable on any processor.
+        let next = self.0.processor_mut().scheduler.pop();
 
-        self.0.runnable_pids.push(pid, priority);
+        next.or_else(|| self.0.steal()).unwrap_or(predefined::IDLE)
     }
 
-    fn switch_to(&mut self, next: Pid) -> (*mut Context, *mut Context) {
-        self.check_kernel_stack_guard(self.0.running);
+    fn switch_to(&mut self, next: Pid) -> (*mut Context, *mut Context, *const AtomicBool) {
+        let current = self.0.running();
+
+        self.check_kernel_stack_guard(current);
         self.check_kernel_stack_guard(next);
 
+        // The processor which ran `next` last may not have saved its context yet.
+        self.0.process_as_ref(next).wait_until_switched_out();
+
         self.switch_kernel_stack(next);
 
         if self.0.running_as_ref().state == State::Running {
             self.0.running_as_mut().state = State::Runnable;
         }
 
-        let current = self.0.running;
+        // The timer may be programmed to interrupt after a long time while the idle process runs.
+        if current == predefined::IDLE {
+            timer::resume_ticking();
+
+            // The bootstrap processor keeps the tick count, so it must tick while any processor
+            // runs a process.
+            self.0.kick(smp::BSP);
+        }
+
+        self.0.processor_mut().running = next;
+
+        let next_process = self.0.process_as_mut(next);
 
-        self.0.running = next;
-        self.0.process_as_mut(next).state = State::Running;
+        next_process.state = State::Running;
+        next_process.on_cpu.store(true, Ordering::Relaxed);
 
-        (self.context(current), self.context(next))
+        (
+            self.context(current),
+            self.context(next),
+            &self.0.process_as_ref(current).on_cpu,
+        )
     }
 
     fn check_kernel_stack_guard(&self, pid: Pid) {
@@ -322,17 +1312,58 @@ impl<'a, const N: usize> Sender<'a, N> {
         if self.is_receiver_waiting_message_from_me() {
             self.send_and_wake_receiver();
         } else {
-            self.sleep();
+            self.sleep(State::Sending {
+                to: self.to,
+                message: self.message,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn send_nonblock(mut self) -> Result<(), Error> {
+        if self.is_receiver_waiting_message_from_me() {
+            self.send_and_wake_receiver();
+
+            Ok(())
+        } else {
+            Err(Error::WouldBlock)
+        }
+    }
+
+    fn send_receive(mut self, buffer: ReadWrite<Message>) -> Result<(), Error> {
+        self.ensure_no_deadlocks()?;
+
+        if self.is_receiver_waiting_message_from_me() {
+            self.send_and_wake_receiver();
+
+            // The receiver cannot reply before this process starts waiting for the reply because
+            // the process manager is locked.
+            self.wait_for_reply(buffer);
+        } else {
+            // `Receiver::receive_and_wake_sender` lets this process wait for the reply instead of
+            // waking it up.
+            self.sleep(State::SendReceiving {
+                to: self.to,
+                message: self.message,
+            })?;
+
+            self.manager.running_as_mut().message_buffer = Some(buffer);
         }
 
         Ok(())
     }
 
     fn ensure_no_deadlocks(&self) -> Result<(), Error> {
+        // A process sending a message to itself never receives it.
+        if self.to == self.manager.running() {
+            return Err(Error::Deadlock);
+        }
+
         let mut proc_ptr = self.manager.process_as_ref(self.to);
 
-        while let State::Sending { to, .. } = proc_ptr.state {
-            if to == self.manager.running {
+        while let Some(to) = proc_ptr.state.sending_to() {
+            if to == self.manager.running() {
                 return Err(Error::Deadlock);
             }
 
@@ -343,11 +1374,9 @@ impl<'a, const N: usize> Sender<'a, N> {
     }
 
     fn is_receiver_waiting_message_from_me(&self) -> bool {
-        match self.manager.process_as_ref(self.to).state {
-            State::Receiving(ReceiveFrom::Any) => true,
-            State::Receiving(ReceiveFrom::Pid(pid)) => self.manager.running == pid,
-            _ => false,
-        }
+        let receiver = self.manager.process_as_ref(self.to);
+
+        receiver.is_waiting_for_message_from(self.manager.running())
     }
 
     fn send_and_wake_receiver(&mut self) {
@@ -363,18 +1392,29 @@ impl<'a, const N: usize> Sender<'a, N> {
         self.manager.wake(self.to);
     }
 
-    fn sleep(&mut self) {
-        let sender = self.manager.running_as_mut();
+    // Blocks the running process in `state` until the receiver receives the message. Returns
+    // `Error::OutOfMemory` without blocking if the queue of the receiver cannot grow.
+    fn sleep(&mut self, state: State) -> Result<(), Error> {
+        let running = self.manager.running();
+        let receiver = self.manager.process_as_mut(self.to);
 
-        sender.state = State::Sending {
-            to: self.to,
-            message: self.message,
-        };
+        receiver
+            .sending_to_this
+            .try_reserve(1)
+            .map_err(|_| Error::OutOfMemory)?;
 
-        let running = self.manager.running;
-        let receiver = self.manager.process_as_mut(self.to);
+        receiver.sending_to_this.push_back(running);
 
-        receiver.sending_to_this.push(running);
+        self.manager.running_as_mut().state = state;
+
+        Ok(())
+    }
+
+    fn wait_for_reply(&mut self, buffer: ReadWrite<Message>) {
+        let sender = self.manager.running_as_mut();
+
+        sender.state = State::ReceivingReply(self.to);
+        sender.message_buffer = Some(buffer);
     }
 }
 
@@ -390,7 +1430,7 @@ impl<'a, const N: usize> Receiver<'a, N> {
         buffer: ReadWrite<Message>,
     ) -> Result<Self, Error> {
         if let ReceiveFrom::Pid(pid) = from {
-            if !manager.exists(pid) {
+            if pid != predefined::HARDWARE && !manager.exists(pid) {
                 return Err(Error::NoSuchProcess(pid.into()));
             }
         }
@@ -402,29 +1442,54 @@ impl<'a, const N: usize> Receiver<'a, N> {
         })
     }
 
-    fn receive(mut self) -> Result<(), Error> {
+    fn receive(mut self, deadline: Option<u64>) -> Result<(), Error> {
+        // Notifications are delivered before messages so that a busy sender cannot starve them.
+        if let Some(notification) = self.take_notification() {
+            self.buffer.write_volatile(notification);
+
+            return Ok(());
+        }
+
         self.ensure_no_deadlocks()?;
 
         if let Some(pid) = self.pop_sender_pid() {
             self.receive_and_wake_sender(pid);
         } else {
-            self.sleep();
+            self.sleep(deadline);
         }
 
         Ok(())
     }
 
-    fn ensure_no_deadlocks(&self) -> Result<(), Error> {
-        let from = if let ReceiveFrom::Pid(pid) = self.from {
-            pid
+    fn receive_nonblock(mut self) -> Result<(), Error> {
+        if let Some(notification) = self.take_notification() {
+            self.buffer.write_volatile(notification);
+        } else if let Some(pid) = self.pop_sender_pid() {
+            self.receive_and_wake_sender(pid);
         } else {
-            return Ok(());
+            return Err(Error::WouldBlock);
+        }
+
+        Ok(())
+    }
+
+    fn ensure_no_deadlocks(&self) -> Result<(), Error> {
+        let from = match self.from {
+            ReceiveFrom::Pid(pid) if pid != predefined::HARDWARE => pid,
+            _ => return Ok(()),
         };
 
+        // A process receiving a message from itself never receives it.
+        if from == self.manager.running() {
+            return Err(Error::Deadlock);
+        }
+
         let mut proc_ptr = self.manager.process_as_ref(from);
 
-        while let State::Receiving(ReceiveFrom::Pid(from)) = proc_ptr.state {
-            if from == self.manager.running {
+        while let State::Receiving(ReceiveFrom::Pid(from)) | State::ReceivingReply(from) =
+            proc_ptr.state
+        {
+            if from == self.manager.running() {
                 return Err(Error::Deadlock);
             }
 
@@ -434,6 +1499,15 @@ impl<'a, const N: usize> Receiver<'a, N> {
         Ok(())
     }
 
+    fn take_notification(&mut self) -> Option<Message> {
+        let from = self.from;
+
+        self.manager
+            .running_as_mut()
+            .pending_notifications
+            .take(from)
+    }
+
     fn pop_sender_pid(&mut self) -> Option<Pid> {
         let pid_queue = &mut self.manager.running_as_mut().sending_to_this;
 
@@ -442,30 +1516,40 @@ impl<'a, const N: usize> Receiver<'a, N> {
             ReceiveFrom::Pid(pid) => pid_queue.iter().position(|&p| p == pid)?,
         };
 
-        pid_queue.pop_at(remove_index)
+        pid_queue.remove(remove_index)
     }
 
     fn receive_and_wake_sender(&mut self, sender_pid: Pid) {
-        let running = self.manager.running;
-
-        let sender = self.manager.process_as_ref(sender_pid);
-        let message = if let State::Sending { to, message } = sender.state {
-            assert_eq!(
-                to, running,
-                "This process is not sending a message to this process."
-            );
+        let running = self.manager.running();
 
-            message
-        } else {
-            panic!("The sender process is not sending a message.");
+        let sender = self.manager.process_as_mut(sender_pid);
+        let (to, message, waits_for_reply) = match sender.state {
+            State::Sending { to, message } => (to, message, false),
+            State::SendReceiving { to, message } => (to, message, true),
+            _ => panic!("The sender process is not sending a message."),
         };
 
+        assert_eq!(
+            to, running,
+            "This process is not sending a message to this process."
+        );
+
         self.buffer.write_volatile(message);
 
-        self.manager.wake(sender_pid);
+        if waits_for_reply {
+            sender.state = State::ReceivingReply(running);
+        } else {
+            self.manager.wake(sender_pid);
+        }
     }
 
-    fn sleep(self) {
+    fn sleep(self, deadline: Option<u64>) {
+        let running = self.manager.running();
+
+        if let Some(deadline) = deadline {
+            self.manager.deadlines.push(running, deadline);
+        }
+
         let receiver = self.manager.running_as_mut();
 
         assert!(
@@ -477,35 +1561,3 @@ impl<'a, const N: usize> Receiver<'a, N> {
         receiver.message_buffer = Some(self.buffer);
     }
 }
-
-// `Deque` is non-Copyable, so we cannot do `[Deque::new(); NUM_LEVEL]`. This is why we use the
-// `Vec`, instead of an array.
-struct RunnablePids<const NUM_LEVEL: usize>(Vec<Deque<Pid, MAX_PID>, NUM_LEVEL>);
-impl<const NUM_LEVEL: usize> RunnablePids<NUM_LEVEL> {
-    const fn new() -> Self {
-        Self(Vec::new())
-    }
-
-    fn init(&mut self) {
-        for _ in 0..NUM_LEVEL {
-            let r = self.0.push(Deque::new());
-
-            // TODO: Use `expect`. Currently it is impossible because `Deque` does not implement
-            // `Debug`.
-            r.unwrap_or_else(|_| panic!("Failed to initialize the running pids queue."));
-        }
-    }
-
-    fn push(&mut self, pid: Pid, priority: Priority) {
-        let r = self.0[priority.as_usize()].push_back(pid);
-
-        r.expect("The binary heap is full.");
-    }
-
-    fn pop(&mut self) -> Pid {
-        self.0
-            .iter_mut()
-            .find_map(Deque::pop_front)
-            .expect("No runnable PID.")
-    }
-}
@@ -1,66 +1,184 @@
 use {
-    crate::sysproc,
-    aligned_ptr::slice,
+    crate::{interrupt, smp, sysproc},
+    aligned_ptr::{ptr, slice},
+    alloc::collections::VecDeque,
     arrayvec::ArrayVec,
     config::MAX_PID,
-    context::Context,
-    core::{cell::UnsafeCell, convert::TryInto},
-    ipc_api::{Message, ReceiveFrom},
-    os_units::NumOfPages,
+    context::{Context, SyscallFrame},
+    core::{
+        cell::UnsafeCell,
+        convert::{TryFrom, TryInto},
+        mem::size_of,
+        sync::atomic::{AtomicBool, Ordering},
+    },
+    ipc_api::{
+        message::{Body, Header},
+        Error, Message, ReceiveFrom,
+    },
+    os_units::{Bytes, NumOfPages},
+    pid::predefined,
+    region::{Kind, Region, Regions},
+    scheduler::Priority,
+    syscalls::{ExecArgs, ExecError},
     vm::{
         accessor::single::{write_only, ReadWrite},
         Kbox,
     },
     x86_64::{
         registers::control::Cr3,
-        structures::paging::{FrameAllocator, PageTableFlags, PhysFrame, Size4KiB},
+        structures::paging::{
+            page::PageRange, FrameAllocator, Page, PageTableFlags, PhysFrame, Size4KiB,
+        },
         VirtAddr,
     },
 };
 
 pub(crate) use {
-    manager::{enter_address_space_and_do, process_exists, switch},
+    copy::{copy, copy_from_user, validate_user_writable, InvalidAddress},
+    grant::Grant,
+    manager::{
+        create_grant, enter_address_space_and_do, is_granted, map_anonymous, notify_interrupt,
+        populate, process_exists, protect_anonymous, reschedule, resize_heap, resolve_grant,
+        revoke_grant, running, set_priority, sleep_until, tick, ticks_until_next_event,
+        unmap_anonymous, wake_expired,
+    },
     pid::Pid,
 };
 
 mod context;
+mod copy;
+mod deadline;
+mod grant;
+mod initial_stack;
 pub(crate) mod ipc;
 mod manager;
+mod notification;
+mod region;
 
-const LEAST_PRIORITY_LEVEL: usize = 1;
 const GUARD_PAGE_SIZE: usize = 4096;
 const KERNEL_STACK_BYTES: usize = 12288;
+const NUM_OF_PAGES_SHARED_AT_ONCE: usize = 64;
+const USER_STACK_PAGES: usize = 5;
 
-pub(super) fn init() {
-    manager::init();
+// The rest of the heap holds the other data, such as the queues of the schedulers.
+const _: () = assert!(
+    MAX_PID * (KERNEL_STACK_BYTES + size_of::<Process>())
+        < predefined_mmap::NUM_OF_HEAP_PAGES * 4096 * 3 / 4,
+    "The kernel heap is too small for the processes."
+);
 
+pub(super) fn init() {
     manager::add_idle();
 
-    manager::add(Process::from_initrd("init"));
-    manager::add(Process::from_function(sysproc::main));
-    manager::add(Process::from_initrd("pm"));
-    manager::add(Process::from_initrd("vm_server"));
-    manager::add(Process::from_initrd("tty"));
-    manager::add(Process::from_initrd("vfs"));
-    manager::add(Process::from_initrd("xhci"));
+    // The other servers and drivers are spawned by `init`. The VM server is created here because
+    // PM allocates its heap through it before `init` can spawn anything.
+    manager::add(Process::from_initrd(predefined::INIT, "init"));
+    manager::add(Process::from_function(predefined::SYSPROC, sysproc::main));
+    manager::add(Process::from_initrd(predefined::PM, "pm"));
+    manager::add(Process::from_initrd(predefined::VM_SERVER, "vm_server"));
 
     #[cfg(test_on_qemu)]
-    manager::add(Process::from_function(crate::tests::main_1));
+    manager::add(Process::from_function(
+        predefined::TEST_1,
+        crate::tests::main_1,
+    ));
     #[cfg(test_on_qemu)]
-    manager::add(Process::from_function(crate::tests::main_2));
+    manager::add(Process::from_function(
+        predefined::TEST_2,
+        crate::tests::main_2,
+    ));
+
     #[cfg(test_on_qemu)]
-    manager::add(Process::from_initrd("test_user_app"));
+    tests::main();
+}
+
+/// Adds the idle process of the current application processor so that the processor runs the
+/// processes.
+pub(crate) fn init_ap() {
+    manager::add_idle();
+}
+
+/// Creates a process from the executable file `name` in the initrd and returns its PID.
+pub(crate) fn spawn(name: &str) -> Option<Pid> {
+    // `Process::try_from_initrd` switches the address space while mapping the executable file.
+    interrupt::disable_interrupts_and_do(|| {
+        // The PID is assigned when the process is added, so that another processor does not take
+        // the same PID in the meantime.
+        let process = Process::try_from_initrd(Pid::default(), name, Priority::APP)?;
+
+        manager::try_add_with_new_pid(process)
+    })
+}
+
+/// Creates a copy of the user process `parent`, which is waiting for the reply to its `fork` request
+/// to PM, and returns the PID of the copy. The two processes share the pages until either of them
+/// writes to them.
+///
+/// The copy returns from the system call as if it received the reply from PM with `0` as the PID.
+pub(crate) fn fork(parent: Pid) -> Option<Pid> {
+    interrupt::disable_interrupts_and_do(|| manager::fork(parent))
+}
+
+/// Replaces the image of the user process `pid`, which is waiting for the reply to its `exec` request
+/// to PM, with the executable file `args.name()` in the initrd.
+///
+/// # Errors
+///
+/// This function returns [`ExecError::NotFound`] if there is no such file,
+/// [`ExecError::InvalidArguments`] if the process is not waiting for the reply to `exec`, and
+/// [`ExecError::LoadFailed`] if the file cannot be loaded. The process keeps the old image on
+/// failure.
+pub(crate) fn exec(pid: Pid, args: &ExecArgs<'_>) -> Result<(), ExecError> {
+    let file = cpio_reader::iter_files(initrd()).find(|f| f.name() == args.name());
+    let file = file.ok_or(ExecError::NotFound)?;
+
+    // See the comment in `spawn`.
+    interrupt::disable_interrupts_and_do(|| manager::exec(pid, file.file(), args))
+}
+
+/// Removes the process with PID `pid`, and frees its address space and kernel stack. Returns `false`
+/// if there is no such process.
+///
+/// # Panics
+///
+/// This function panics if the process is running.
+pub(crate) fn destroy(pid: Pid) -> bool {
+    interrupt::disable_interrupts_and_do(|| {
+        let process = manager::remove(pid);
+
+        if let Some(process) = &process {
+            // SAFETY: The process is removed, so no one uses its address space.
+            unsafe {
+                free_address_space(process.pml4);
+            }
+        }
+
+        // The kernel stack is freed here.
+        process.is_some()
+    })
 }
 
 pub(super) struct Process {
     pid: Pid,
     pml4: PhysFrame,
     context: UnsafeCell<Context>,
+    // The base priority. The scheduler lowers it while the process uses up its time slices.
     priority: Priority,
     kernel_stack: Kbox<UnsafeCell<[u8; KERNEL_STACK_BYTES]>>,
-    sending_to_this: ArrayVec<Pid, MAX_PID>,
+    sending_to_this: VecDeque<Pid>,
     state: State,
     message_buffer: Option<ReadWrite<Message>>,
+    grants: grant::Table,
+    pending_notifications: notification::Pending,
+    // The regions of the user address space whose pages are mapped when they are accessed.
+    regions: Regions,
+    // The error returned to this process when it wakes up, such as a timeout.
+    wakeup_error: Option<Error>,
+    // The index of the processor whose scheduler has this process.
+    processor: usize,
+    // `true` while a processor runs this process and until the processor saves the context after
+    // switching to another process. No other processor may load or modify the context until then.
+    on_cpu: AtomicBool,
 }
 impl Process {
     const KERNEL_STACK_MAGIC: [u8; 8] = [0x73, 0x74, 0x6b, 0x67, 0x75, 0x61, 0x72, 0x64];
@@ -72,27 +190,32 @@ impl Process {
             pid: Pid::new(0),
             pml4,
             context: UnsafeCell::default(),
-            priority: Priority::new(LEAST_PRIORITY_LEVEL),
-            kernel_stack: Self::generate_kernel_stack(),
-            sending_to_this: ArrayVec::new(),
+            priority: Priority::LOWEST,
+            kernel_stack: Self::generate_kernel_stack()
+                .expect("Failed to allocate the kernel stack of the idle process."),
+            sending_to_this: VecDeque::new(),
             state: State::Running,
             message_buffer: None,
+            grants: grant::Table::default(),
+            pending_notifications: notification::Pending::default(),
+            regions: Regions::default(),
+            wakeup_error: None,
+            processor: smp::index(),
+            on_cpu: AtomicBool::new(true),
         }
     }
 
-    fn from_function(f: fn() -> !) -> Self {
-        Self::try_from_function(f).expect("Failed to create a process from a function.")
+    fn from_function(pid: Pid, f: fn() -> !) -> Self {
+        Self::try_from_function(pid, f).expect("Failed to create a process from a function.")
     }
 
-    fn try_from_function(f: fn() -> !) -> Option<Self> {
-        let pid = Self::generate_pid()?;
+    fn try_from_function(pid: Pid, f: fn() -> !) -> Option<Self> {
+        let mut kernel_stack = Self::generate_kernel_stack()?;
 
         let pml4 = Self::create_new_pml4()?;
 
         let entry = VirtAddr::new((f as usize).try_into().unwrap());
 
-        let mut kernel_stack = Self::generate_kernel_stack();
-
         let kernel_stack_len = kernel_stack.get_mut().len();
 
         let kernel_stack_end = VirtAddr::from_ptr(kernel_stack.get()) + kernel_stack_len - 8_u64;
@@ -104,56 +227,219 @@ impl Process {
             pid,
             pml4,
             context,
-            priority: Priority::new(0),
+            priority: Priority::SERVER,
             kernel_stack,
-            sending_to_this: ArrayVec::new(),
+            sending_to_this: VecDeque::new(),
             state: State::Runnable,
             message_buffer: None,
+            grants: grant::Table::default(),
+            pending_notifications: notification::Pending::default(),
+            regions: Regions::default(),
+            wakeup_error: None,
+            processor: 0,
+            on_cpu: AtomicBool::new(false),
         })
     }
 
-    fn from_initrd(name: &str) -> Self {
-        Self::try_from_initrd(name)
+    fn from_initrd(pid: Pid, name: &str) -> Self {
+        Self::try_from_initrd(pid, name, Priority::SERVER)
             .unwrap_or_else(|| panic!("Failed to create the {} process.", name))
     }
 
-    fn try_from_initrd(name: &str) -> Option<Self> {
-        let pid = Self::generate_pid()?;
+    fn try_from_initrd(pid: Pid, name: &str, priority: Priority) -> Option<Self> {
+        let file = cpio_reader::iter_files(initrd()).find(|f| f.name() == name)?;
 
-        let stack_size = NumOfPages::new(5);
+        let binary = file.file();
+
+        let kernel_stack = Self::generate_kernel_stack()?;
 
         let pml4 = Self::create_new_pml4()?;
 
-        let file = cpio_reader::iter_files(initrd()).find(|f| f.name() == name)?;
+        // SAFETY: `pml4` is generated in this method.
+        let image = unsafe {
+            switch_pml4_do(pml4, || {
+                load_image(pml4, binary, &ExecArgs::without_arguments(name))
+            })
+        };
 
-        let binary = file.file();
+        let (context, regions) = match image {
+            Some(image) => image,
+            None => {
+                // SAFETY: No process uses the new address space.
+                unsafe {
+                    free_address_space(pml4);
+                }
 
-        let stack_flags = PageTableFlags::PRESENT
-            | PageTableFlags::WRITABLE
-            | PageTableFlags::USER_ACCESSIBLE
-            | PageTableFlags::NO_EXECUTE;
+                return None;
+            }
+        };
+
+        Some(Self {
+            pid,
+            pml4,
+            context: UnsafeCell::new(context),
+            priority,
+            kernel_stack,
+            sending_to_this: VecDeque::new(),
+            state: State::Runnable,
+            message_buffer: None,
+            grants: grant::Table::default(),
+            pending_notifications: notification::Pending::default(),
+            regions,
+            wakeup_error: None,
+            processor: 0,
+            on_cpu: AtomicBool::new(false),
+        })
+    }
+
+    fn try_fork(pid: Pid, parent: &Self) -> Option<Self> {
+        let kernel_stack = Self::generate_kernel_stack()?;
+
+        let pml4 = Self::create_new_pml4()?;
+
+        let mut next = Some(Page::containing_address(VirtAddr::zero()));
+
+        while let Some(start) = next {
+            let mut pages = ArrayVec::<_, NUM_OF_PAGES_SHARED_AT_ONCE>::new();
+
+            // SAFETY: Both PML4s are correct, and the shared pages are mapped to the new address
+            // space only once.
+            let r = unsafe {
+                let r = switch_pml4_do(parent.pml4, || vm::share_user_pages(start, &mut pages));
+
+                switch_pml4_do(pml4, || vm::map_shared_pages(&pages));
+
+                r
+            };
+
+            match r {
+                Ok(n) => next = n,
+                Err(vm::TooManySharedFrames) => {
+                    // SAFETY: No process uses the new address space. Freeing it unshares the
+                    // pages shared so far.
+                    unsafe {
+                        free_address_space(pml4);
+                    }
+
+                    return None;
+                }
+            }
+        }
+
+        let frame = parent.syscall_frame();
 
         // SAFETY: `pml4` is generated in this method.
         unsafe {
-            switch_pml4_do(pml4, || {
-                let entry = vm::map_elf(binary);
-
-                let stack_range = vm::alloc_pages(stack_size, stack_flags)?;
-
-                let context = Context::user(entry, pml4, stack_range.end.start_address() - 8_u64);
-                let context = UnsafeCell::new(context);
-
-                Some(Self {
-                    pid,
-                    pml4,
-                    context,
-                    priority: Priority::new(0),
-                    kernel_stack: Self::generate_kernel_stack(),
-                    sending_to_this: ArrayVec::new(),
-                    state: State::Runnable,
-                    message_buffer: None,
-                })
-            })
+            switch_pml4_do(pml4, || write_fork_reply(VirtAddr::new(frame.a2())));
+        }
+
+        let mut process = Self {
+            pid,
+            pml4,
+            context: UnsafeCell::default(),
+            priority: parent.priority,
+            kernel_stack,
+            sending_to_this: VecDeque::new(),
+            state: State::Runnable,
+            message_buffer: None,
+            grants: grant::Table::default(),
+            pending_notifications: notification::Pending::default(),
+            // The pages not mapped in the parent are not mapped in the child either.
+            regions: parent.regions.clone(),
+            wakeup_error: None,
+            processor: 0,
+            on_cpu: AtomicBool::new(false),
+        };
+
+        let frame_addr = process.kernel_stack_bottom_addr() - size_of::<SyscallFrame>();
+
+        // SAFETY: The address is in the kernel stack of the new process, which is not used yet.
+        unsafe {
+            ptr::write(frame_addr.as_mut_ptr(), frame);
+        }
+
+        parent.wait_until_switched_out();
+
+        // SAFETY: The parent is sleeping, so no one modifies its context.
+        let parent_context = unsafe { &*parent.context.get() };
+
+        *process.context.get_mut() = parent_context.forked(pml4, frame_addr);
+
+        Some(process)
+    }
+
+    // Replaces the user region and the context with those of `binary`.
+    // The new image is loaded to a new address space so that the process keeps the old image if
+    // loading fails. Returns `false` in that case.
+    fn exec(&mut self, binary: &[u8], args: &ExecArgs<'_>) -> bool {
+        self.wait_until_switched_out();
+
+        let pml4 = match Self::create_new_pml4() {
+            Some(pml4) => pml4,
+            None => return false,
+        };
+
+        // SAFETY: `pml4` is generated here.
+        let image = unsafe { switch_pml4_do(pml4, || load_image(pml4, binary, args)) };
+
+        let (context, regions) = match image {
+            Some(image) => image,
+            None => {
+                // SAFETY: No process uses the new address space.
+                unsafe {
+                    free_address_space(pml4);
+                }
+
+                return false;
+            }
+        };
+
+        let old = core::mem::replace(&mut self.pml4, pml4);
+
+        // SAFETY: The caller ensures that the process is sleeping, and it never returns to the old
+        // image.
+        unsafe {
+            free_address_space(old);
+        }
+
+        *self.context.get_mut() = context;
+        self.regions = regions;
+
+        // The granted regions do not exist anymore.
+        self.grants = grant::Table::default();
+
+        true
+    }
+
+    fn is_waiting_for_message_from(&self, pid: Pid) -> bool {
+        match self.state {
+            State::ReceivingReply(from) => from == pid,
+            _ => self.is_waiting_for_notification_from(pid),
+        }
+    }
+
+    fn is_waiting_for_message_only_from(&self, pid: Pid) -> bool {
+        matches!(
+            self.state,
+            State::Receiving(ReceiveFrom::Pid(from)) | State::ReceivingReply(from) if from == pid
+        )
+    }
+
+    // A process waiting for a reply does not receive notifications. Otherwise, a notification will
+    // be mistaken for the reply.
+    fn is_waiting_for_notification_from(&self, pid: Pid) -> bool {
+        match self.state {
+            State::Receiving(ReceiveFrom::Any) => true,
+            State::Receiving(ReceiveFrom::Pid(from)) => from == pid,
+            _ => false,
+        }
+    }
+
+    // Waits until the processor which ran this process last saves its context. The process must not
+    // be running.
+    fn wait_until_switched_out(&self) {
+        while self.on_cpu.load(Ordering::Acquire) {
+            core::hint::spin_loop();
         }
     }
 
@@ -208,6 +494,14 @@ impl Process {
         }
     }
 
+    // Returns the registers saved when this process entered the system call it is in.
+    fn syscall_frame(&self) -> SyscallFrame {
+        let addr = self.kernel_stack_bottom_addr() - size_of::<SyscallFrame>();
+
+        // SAFETY: `asm_handle_syscall` saves the frame at the bottom of the kernel stack.
+        unsafe { ptr::get(addr.as_ptr()) }
+    }
+
     fn kernel_stack_bottom_addr(&self) -> VirtAddr {
         let ptr = self.kernel_stack.get();
 
@@ -215,18 +509,15 @@ impl Process {
         VirtAddr::from_ptr(ptr) + unsafe { (&*ptr).len() }
     }
 
-    fn generate_kernel_stack() -> Kbox<UnsafeCell<[u8; KERNEL_STACK_BYTES]>> {
-        let mut stack = Kbox::new(UnsafeCell::new([0; KERNEL_STACK_BYTES]));
+    // Returns `None` if the kernel heap has no room for the stack.
+    fn generate_kernel_stack() -> Option<Kbox<UnsafeCell<[u8; KERNEL_STACK_BYTES]>>> {
+        let mut stack = Kbox::try_new(UnsafeCell::new([0; KERNEL_STACK_BYTES]))?;
 
         for (i, c) in Self::KERNEL_STACK_MAGIC.iter().enumerate() {
             stack.get_mut()[GUARD_PAGE_SIZE + i] = *c;
         }
 
-        stack
-    }
-
-    fn generate_pid() -> Option<Pid> {
-        (0..MAX_PID).find_map(|i| (!process_exists(i.into())).then(|| Pid::new(i)))
+        Some(stack)
     }
 }
 
@@ -251,6 +542,105 @@ unsafe fn switch_pml4_do<T>(pml4: PhysFrame, f: impl FnOnce() -> T) -> T {
     r
 }
 
+/// Frees the user region and the PML4 of the address space `pml4`.
+///
+/// # Safety
+///
+/// `pml4` must be a correct PML4 created for a process. No process may use the address space, and
+/// `pml4` must not be loaded to CR3 after this call.
+unsafe fn free_address_space(pml4: PhysFrame) {
+    // SAFETY: The caller ensures that no one uses the address space.
+    unsafe {
+        switch_pml4_do(pml4, || vm::free_user_region());
+    }
+
+    vm::frame_allocator().dealloc(pml4);
+}
+
+// Maps `binary` and the stack containing `args` to the current address space, and returns the
+// context to start the image and the regions whose pages are mapped on demand.
+//
+// # Safety
+//
+// The current address space must have the PML4 `pml4`, and its user region must be unused.
+unsafe fn load_image(
+    pml4: PhysFrame,
+    binary: &[u8],
+    args: &ExecArgs<'_>,
+) -> Option<(Context, Regions)> {
+    let data_flags = PageTableFlags::PRESENT
+        | PageTableFlags::WRITABLE
+        | PageTableFlags::USER_ACCESSIBLE
+        | PageTableFlags::NO_EXECUTE;
+
+    // SAFETY: The caller ensures that the user region is unused.
+    let elf = unsafe { vm::map_elf(binary)? };
+
+    let mut regions = Regions::default();
+
+    for pages in elf.zero_filled_pages() {
+        regions.add(Region::new(pages.pages(), pages.flags(), Kind::Bss));
+    }
+
+    let heap = PageRange {
+        start: elf.end(),
+        end: elf.end(),
+    };
+
+    regions.add(Region::new(heap, data_flags, Kind::Heap));
+
+    let args_pages = Bytes::new(initial_stack::size(args)).as_num_of_pages::<Size4KiB>();
+    let args_pages = u64::try_from(args_pages.as_usize()).unwrap();
+
+    let stack = predefined_mmap::user_stack();
+    let stack_pages = u64::try_from(USER_STACK_PAGES).unwrap() + args_pages;
+
+    regions.add(Region::new(
+        PageRange {
+            start: stack.end - stack_pages,
+            end: stack.end,
+        },
+        data_flags,
+        Kind::Stack {
+            limit: stack.start + 1,
+        },
+    ));
+
+    // The arguments are written here, so their pages are mapped now.
+    for page in (PageRange {
+        start: stack.end - args_pages,
+        end: stack.end,
+    }) {
+        // SAFETY: The page is in the user stack region, which is unused.
+        if !unsafe { vm::map_zeroed_page(page, data_flags) } {
+            return None;
+        }
+    }
+
+    // SAFETY: The pages for the arguments are writable.
+    let context =
+        unsafe { initial_stack::build(stack.end.start_address(), elf.entry(), pml4, args) };
+
+    Some((context, regions))
+}
+
+// Writes the reply to `fork` to `buffer` of the current address space, which is that of the child
+// process.
+fn write_fork_reply(buffer: VirtAddr) {
+    let reply = Message {
+        header: Header::new(predefined::PM),
+        body: Body(0, 0, 0, 0, 0),
+    };
+
+    // The buffer is in a single page. See `Message`.
+    let _ = vm::copy_on_write(buffer);
+
+    // SAFETY: The parent passed `buffer` to the system call, and the child has the same pages.
+    unsafe {
+        ptr::write(buffer.as_mut_ptr(), reply);
+    }
+}
+
 fn initrd<'a>() -> &'a [u8] {
     use predefined_mmap::initrd;
 
@@ -268,19 +658,76 @@ enum State {
     Running,
     Runnable,
     Sending { to: Pid, message: Message },
+    // Sending a message in the `send_receive` system call. The process waits for the reply after
+    // the receiver receives the message.
+    SendReceiving { to: Pid, message: Message },
     Receiving(ReceiveFrom),
+    ReceivingReply(Pid),
+    // Sleeping in the `sleep` system call until the deadline.
+    Sleeping,
+}
+impl State {
+    // Returns the PID of the process to which this process is sending a message, if any.
+    fn sending_to(self) -> Option<Pid> {
+        match self {
+            Self::Sending { to, .. } | Self::SendReceiving { to, .. } => Some(to),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-struct Priority(usize);
-impl Priority {
-    fn new(priority: usize) -> Self {
-        assert!(priority <= LEAST_PRIORITY_LEVEL, "Invalid priority.");
+#[cfg(test_on_qemu)]
+mod tests {
+    use {
+        super::{destroy, manager, process_exists, Pid, Process},
+        x86_64::structures::paging::{FrameAllocator, PhysFrame},
+    };
+
+    pub(super) fn main() {
+        destroy_process_and_free_frames();
+    }
+
+    fn destroy_process_and_free_frames() {
+        // The first creation may allocate page tables for the kernel region, which are never freed.
+        create_and_destroy();
+
+        let frame = allocate_and_free_frame();
+
+        let pid = create_and_destroy();
+
+        // All frames allocated for the process are freed, so the same frame is allocated again.
+        assert_eq!(allocate_and_free_frame(), frame);
+
+        assert!(!destroy(pid));
+    }
 
-        Self(priority)
+    fn create_and_destroy() -> Pid {
+        let pid = create();
+
+        assert!(destroy(pid));
+        assert!(!process_exists(pid));
+
+        // The slot is reused with the next generation.
+        let next = create();
+        assert_eq!(next, pid.next_generation());
+
+        assert!(destroy(next));
+
+        pid
     }
 
-    fn as_usize(self) -> usize {
-        self.0
+    fn create() -> Pid {
+        let process = Process::from_initrd(Pid::default(), "vm_server");
+
+        manager::try_add_with_new_pid(process).expect("Failed to add a process.")
+    }
+
+    fn allocate_and_free_frame() -> PhysFrame {
+        let frame = vm::frame_allocator().allocate_frame();
+        let frame = frame.expect("Failed to allocate a frame.");
+
+        vm::frame_allocator().dealloc(frame);
+
+        frame
     }
 }
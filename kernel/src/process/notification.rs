@@ -0,0 +1,44 @@
+use {
+    super::ReceiveFrom,
+    alloc::collections::BTreeSet,
+    ipc_api::message::Message,
+    pid::{predefined, Pid},
+};
+
+// The PIDs of the processes whose notifications are not received yet. Notifications from the same
+// process are coalesced into one entry. The interrupts are coalesced into `vectors`, and their
+// notification is from `predefined::HARDWARE`. `HARDWARE` is not put in `senders` because the
+// interrupt handler must not allocate memory.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(super) struct Pending {
+    senders: BTreeSet<Pid>,
+    vectors: [u64; 4],
+}
+impl Pending {
+    pub(super) fn set(&mut self, pid: Pid) {
+        self.senders.insert(pid);
+    }
+
+    pub(super) fn set_interrupt(&mut self, vector: u8) {
+        self.vectors[usize::from(vector / 64)] |= 1 << (vector % 64);
+    }
+
+    /// Clears and returns a notification matching `from`. The interrupts are taken before the
+    /// notifications from the processes.
+    pub(super) fn take(&mut self, from: ReceiveFrom) -> Option<Message> {
+        let hardware = self.vectors.iter().any(|v| *v != 0);
+
+        let pid = match from {
+            ReceiveFrom::Any if hardware => predefined::HARDWARE,
+            ReceiveFrom::Any => self.senders.pop_first()?,
+            ReceiveFrom::Pid(pid) if pid == predefined::HARDWARE => hardware.then_some(pid)?,
+            ReceiveFrom::Pid(pid) => self.senders.remove(&pid).then_some(pid)?,
+        };
+
+        Some(if pid == predefined::HARDWARE {
+            Message::hardware_notification(core::mem::take(&mut self.vectors))
+        } else {
+            Message::notification(pid)
+        })
+    }
+}
@@ -0,0 +1,184 @@
+use {
+    alloc::vec::Vec,
+    x86_64::{
+        structures::paging::{page::PageRange, Page, PageTableFlags},
+        VirtAddr,
+    },
+};
+
+// The regions of the user address space whose pages are mapped to zeroed frames when they are
+// accessed for the first time. An access outside these regions and the mapped pages is invalid.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(super) struct Regions(Vec<Region>);
+impl Regions {
+    pub(super) fn add(&mut self, region: Region) {
+        self.0.push(region);
+    }
+
+    pub(super) fn heap_mut(&mut self) -> Option<&mut Region> {
+        self.0.iter_mut().find(|region| region.kind == Kind::Heap)
+    }
+
+    /// Adds an anonymous region of `n` pages in the first unused range of `within`, and returns its
+    /// pages. Returns `None` if there is no such range.
+    pub(super) fn add_anonymous(
+        &mut self,
+        n: u64,
+        flags: PageTableFlags,
+        within: PageRange,
+    ) -> Option<PageRange> {
+        let mut used: Vec<_> = self
+            .0
+            .iter()
+            .map(|region| region.pages)
+            .filter(|pages| pages.start < within.end && within.start < pages.end)
+            .collect();
+
+        used.sort_unstable_by_key(|pages| pages.start);
+
+        let mut start = within.start;
+
+        for pages in used {
+            if pages.start > start && pages.start - start >= n {
+                break;
+            }
+
+            start = start.max(pages.end);
+        }
+
+        (within.end > start && within.end - start >= n).then(|| {
+            let pages = PageRange {
+                start,
+                end: start + n,
+            };
+
+            self.add(Region::new(pages, flags, Kind::Anonymous));
+
+            pages
+        })
+    }
+
+    /// Removes `pages` from the anonymous regions.
+    pub(super) fn remove_anonymous(&mut self, pages: PageRange) {
+        self.map_anonymous_parts(pages, |_| None);
+    }
+
+    /// Changes the flags of `pages` to `flags`. Returns `false` without changing anything if a part
+    /// of `pages` is not in the anonymous regions.
+    pub(super) fn protect_anonymous(&mut self, pages: PageRange, flags: PageTableFlags) -> bool {
+        let covered: u64 = self
+            .0
+            .iter()
+            .filter(|region| region.kind == Kind::Anonymous)
+            .filter_map(|region| region.split(pages)[1])
+            .map(|region| region.pages.end - region.pages.start)
+            .sum();
+
+        if covered != pages.end - pages.start {
+            return false;
+        }
+
+        self.map_anonymous_parts(pages, |region| Some(Region { flags, ..region }));
+
+        true
+    }
+
+    // Replaces the parts of the anonymous regions in `pages` with the result of `f`.
+    fn map_anonymous_parts(&mut self, pages: PageRange, f: impl Fn(Region) -> Option<Region>) {
+        self.0 = core::mem::take(&mut self.0)
+            .into_iter()
+            .flat_map(|region| {
+                if region.kind == Kind::Anonymous {
+                    let [before, inside, after] = region.split(pages);
+
+                    [before, inside.and_then(&f), after]
+                } else {
+                    [Some(region), None, None]
+                }
+            })
+            .flatten()
+            .collect();
+    }
+
+    /// Returns the flags of the page containing `addr` if the page belongs to a region. If the
+    /// page is below a stack and not below its limit, the stack grows to contain it.
+    pub(super) fn find(&mut self, addr: VirtAddr) -> Option<PageTableFlags> {
+        let page = Page::containing_address(addr);
+
+        self.0.iter_mut().find_map(|region| {
+            if contains(region.pages, page) {
+                Some(region.flags)
+            } else if region.grows_to(page) {
+                region.pages.start = page;
+
+                Some(region.flags)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) struct Region {
+    pages: PageRange,
+    flags: PageTableFlags,
+    kind: Kind,
+}
+impl Region {
+    pub(super) fn new(pages: PageRange, flags: PageTableFlags, kind: Kind) -> Self {
+        Self { pages, flags, kind }
+    }
+
+    pub(super) fn pages(&self) -> PageRange {
+        self.pages
+    }
+
+    pub(super) fn set_end(&mut self, end: Page) {
+        self.pages.end = end;
+    }
+
+    // Splits this region into the parts before, inside, and after `pages`.
+    fn split(self, pages: PageRange) -> [Option<Self>; 3] {
+        let part = |start: Page, end: Page| {
+            (start < end).then_some(Self {
+                pages: PageRange { start, end },
+                ..self
+            })
+        };
+
+        [
+            part(self.pages.start, pages.start.min(self.pages.end)),
+            part(
+                self.pages.start.max(pages.start),
+                self.pages.end.min(pages.end),
+            ),
+            part(self.pages.start.max(pages.end), self.pages.end),
+        ]
+    }
+
+    // A stack grows to any page down to its limit so that a large stack frame, which may skip
+    // pages, does not kill the process.
+    fn grows_to(&self, page: Page) -> bool {
+        match self.kind {
+            Kind::Stack { limit } => limit <= page && page < self.pages.start,
+            Kind::Bss | Kind::Heap | Kind::Anonymous => false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum Kind {
+    // The part of a segment of the executable file which is not backed by the file.
+    Bss,
+    Heap,
+    // Mapped by `syscalls::map_anonymous`.
+    Anonymous,
+    // Grows downward, but not below `limit`. The page below `limit` is the guard page, which is
+    // never mapped so that a stack overflow kills the process.
+    Stack { limit: Page },
+}
+
+fn contains(range: PageRange, page: Page) -> bool {
+    range.start <= page && page < range.end
+}
@@ -0,0 +1,436 @@
+use {
+    crate::{gdt, interrupt::idt, process, syscall, timer},
+    acpi::Madt,
+    alloc::{vec, vec::Vec},
+    apic::local::{
+        icr::{DeliveryMode, InterruptCommand},
+        INTERRUPT_COMMAND_31_0, INTERRUPT_COMMAND_63_32, SPURIOUS_INTERRUPT_VECTOR,
+    },
+    core::{
+        arch::x86_64::__cpuid,
+        convert::{TryFrom, TryInto},
+        sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+    },
+    os_units::NumOfPages,
+    vm::accessor::{
+        single::{read_only, write_only},
+        Mapper,
+    },
+    x86_64::{
+        registers::{
+            control::{Cr3, Cr3Flags},
+            model_specific::KernelGsBase,
+        },
+        structures::paging::{
+            frame::PhysFrameRange, PageSize, PageTable, PageTableFlags, PhysFrame, Size4KiB,
+        },
+        PhysAddr, VirtAddr,
+    },
+};
+
+/// The maximum number of the processors this OS uses.
+pub(crate) const MAX_CPUS: usize = 16;
+
+/// The index of the bootstrap processor.
+pub(crate) const BSP: usize = 0;
+
+/// The vector of the interrupt which lets an idle processor run the processes assigned to it.
+pub(crate) const RESCHEDULE_VECTOR: u8 = 0xf0;
+
+const AP_STACK_BYTES: usize = 16384;
+
+// The code, the PML4, the PDPT, and the PD which identity-maps the first 2 MiB.
+const TRAMPOLINE_PAGES: usize = 4;
+
+// The processors start the trampoline in real mode.
+const TRAMPOLINE_LIMIT: PhysAddr = PhysAddr::new_truncate(0x10_0000);
+
+// The Local APIC is disabled after INIT until this bit of the Spurious Interrupt Vector Register is
+// set.
+const APIC_SOFTWARE_ENABLE: u32 = 1 << 8;
+
+// The index of each processor by its Local APIC ID. The bootstrap processor is `0`, and so are the
+// processors which this OS does not start.
+//
+// Each processor reads this once when it starts and caches its index in the KernelGsBase MSR, so
+// `index` does not run `cpuid` on every call. The MSR is `0` after reset, which is the index of the
+// bootstrap processor. The GS base itself is not used because the user processes may change it and
+// the kernel does not execute `swapgs`.
+static INDICES: [AtomicU8; 256] = [const { AtomicU8::new(0) }; 256];
+
+// The Local APIC ID of each processor by its index.
+static APIC_IDS: [AtomicU8; MAX_CPUS] = [const { AtomicU8::new(0) }; MAX_CPUS];
+
+// The number of the processors which have finished their initialization.
+static ONLINE: AtomicUsize = AtomicUsize::new(1);
+
+// The `i`-th bit is set once the `i`-th processor receives a timer interrupt.
+#[cfg(test_on_qemu)]
+static CHECKED_IN: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(0);
+
+/// Starts the enabled application processors listed in `madt`. Each of them loads its own GDT and
+/// TSS and the shared IDT, starts its timer, and runs the processes with its idle process.
+///
+/// # Panics
+///
+/// This function panics if a processor does not start.
+pub(super) fn init(madt: Option<&Madt<Mapper>>) {
+    let bsp = local_apic_id();
+
+    APIC_IDS[BSP].store(bsp, Ordering::Relaxed);
+
+    let aps: Vec<_> = madt
+        .into_iter()
+        .flat_map(Madt::local_apics)
+        .filter(|a| a.enabled && a.apic_id != bsp)
+        .map(|a| a.apic_id)
+        .collect();
+
+    if aps.len() >= MAX_CPUS {
+        log::warn!(
+            "Only {} of {} processors are used.",
+            MAX_CPUS,
+            aps.len() + 1
+        );
+    }
+
+    if !aps.is_empty() {
+        let trampoline = Trampoline::new();
+
+        for (index, &apic_id) in aps.iter().take(MAX_CPUS - 1).enumerate() {
+            INDICES[usize::from(apic_id)].store((index + 1).try_into().unwrap(), Ordering::Relaxed);
+            APIC_IDS[index + 1].store(apic_id, Ordering::Relaxed);
+
+            trampoline.set_parameters(Parameters::new(trampoline.pml4()));
+
+            start(apic_id, trampoline.vector());
+        }
+    }
+
+    #[cfg(test_on_qemu)]
+    tests::main();
+}
+
+/// Returns the index of the current processor. The bootstrap processor is `0`.
+#[must_use]
+pub(crate) fn index() -> usize {
+    KernelGsBase::read().as_u64().try_into().unwrap()
+}
+
+#[must_use]
+pub(crate) fn is_bsp() -> bool {
+    index() == BSP
+}
+
+/// Returns the Local APIC ID of the processor `index`.
+#[must_use]
+pub(crate) fn apic_id(index: usize) -> u8 {
+    APIC_IDS[index].load(Ordering::Relaxed)
+}
+
+/// Returns the number of the processors which have finished their initialization.
+#[must_use]
+pub(crate) fn count() -> usize {
+    ONLINE.load(Ordering::Acquire)
+}
+
+/// Sends the interrupt `vector` to the processor `index`.
+pub(crate) fn send_interrupt(index: usize, vector: u8) {
+    send_ipi(
+        *InterruptCommand::default()
+            .set_vector(vector)
+            .set_delivery_mode(DeliveryMode::Fixed)
+            .set_level()
+            .set_destination(apic_id(index)),
+    );
+}
+
+/// Records that the current processor has received a timer interrupt.
+#[cfg(test_on_qemu)]
+pub(crate) fn check_in() {
+    CHECKED_IN.fetch_or(1 << index(), Ordering::Relaxed);
+}
+
+/// Returns `true` if all processors have received a timer interrupt.
+#[cfg(test_on_qemu)]
+#[must_use]
+pub(crate) fn all_checked_in() -> bool {
+    CHECKED_IN.load(Ordering::Relaxed) == (1 << count()) - 1
+}
+
+// Sends INIT and two Startup IPIs to the processor, and waits for it to finish its initialization.
+fn start(apic_id: u8, vector: u8) {
+    // The milliseconds to wait after each Startup IPI.
+    const WAITS: [u32; 2] = [1, 100];
+
+    let online = count();
+
+    send_ipi(
+        *InterruptCommand::default()
+            .set_delivery_mode(DeliveryMode::Init)
+            .set_level()
+            .set_destination(apic_id),
+    );
+
+    timer::wait_milliseconds(10);
+
+    // A processor ignores the second Startup IPI if it has started with the first one.
+    for wait in WAITS {
+        send_ipi(
+            *InterruptCommand::default()
+                .set_vector(vector)
+                .set_delivery_mode(DeliveryMode::StartUp)
+                .set_destination(apic_id),
+        );
+
+        if wait_for_online(online + 1, wait) {
+            return;
+        }
+    }
+
+    panic!(
+        "The processor with Local APIC ID {} did not start.",
+        apic_id
+    );
+}
+
+fn send_ipi(command: InterruptCommand) {
+    // SAFETY: This OS does not change the start address of the Local APIC registers.
+    unsafe {
+        write_only(INTERRUPT_COMMAND_63_32).write_volatile(command.high());
+        write_only(INTERRUPT_COMMAND_31_0).write_volatile(command.low());
+    }
+
+    // SAFETY: This OS does not change the start address of the Local APIC registers.
+    while InterruptCommand::new(
+        unsafe { read_only(INTERRUPT_COMMAND_31_0).read_volatile() },
+        0,
+    )
+    .delivery_status()
+    {
+        core::hint::spin_loop();
+    }
+}
+
+// Returns `true` if `n` processors get online within `msec` milliseconds.
+fn wait_for_online(n: usize, msec: u32) -> bool {
+    (0..=msec).any(|i| {
+        if i > 0 {
+            timer::wait_milliseconds(1);
+        }
+
+        count() >= n
+    })
+}
+
+// The trampoline calls this function with the stack in `Parameters`.
+extern "sysv64" fn ap_main(kernel_pml4: u64) -> ! {
+    // SAFETY: The higher half, which contains the kernel and this stack, is shared by the page
+    // tables of the trampoline and the kernel's PML4.
+    unsafe {
+        Cr3::write(
+            PhysFrame::from_start_address(PhysAddr::new(kernel_pml4)).unwrap(),
+            Cr3Flags::empty(),
+        );
+    }
+
+    cache_index();
+
+    gdt::init_ap();
+    idt::init_ap();
+
+    // SAFETY: This OS does not change the start address of the Local APIC registers.
+    unsafe {
+        enable_local_apic();
+
+        timer::init_ap();
+    }
+
+    syscall::init();
+
+    process::init_ap();
+
+    ONLINE.fetch_add(1, Ordering::Release);
+
+    crate::idle();
+}
+
+/// # Safety
+///
+/// The start address of the Local APIC registers must be `0xfee0_0000` (the default one).
+unsafe fn enable_local_apic() {
+    // SAFETY: The caller must ensure that the start address of the Local APIC registers is
+    // `0xfee0_0000`.
+    unsafe {
+        let spurious: u32 = read_only(SPURIOUS_INTERRUPT_VECTOR).read_volatile();
+
+        write_only(SPURIOUS_INTERRUPT_VECTOR).write_volatile(spurious | APIC_SOFTWARE_ENABLE);
+    }
+}
+
+// See the comment on `INDICES`.
+fn cache_index() {
+    let index = INDICES[usize::from(local_apic_id())].load(Ordering::Relaxed);
+
+    KernelGsBase::write(VirtAddr::new(index.into()));
+}
+
+fn local_apic_id() -> u8 {
+    const FEATURE_INFORMATION: u32 = 1;
+
+    (__cpuid(FEATURE_INFORMATION).ebx >> 24).try_into().unwrap()
+}
+
+// The pages below 1 MiB which contain the code to start the application processors and the page
+// tables used until they switch to the kernel's PML4. The page tables identity-map the first 2 MiB
+// in addition to the higher half of the kernel's PML4.
+struct Trampoline {
+    frames: PhysFrameRange,
+    virt: VirtAddr,
+}
+impl Trampoline {
+    fn new() -> Self {
+        let frames =
+            vm::frame_allocator().alloc_below(NumOfPages::new(TRAMPOLINE_PAGES), TRAMPOLINE_LIMIT);
+        let frames = frames.expect("No memory below 1 MiB for the trampoline.");
+
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+
+        // SAFETY: The frames are allocated for the trampoline.
+        let virt = unsafe {
+            vm::map(
+                frames.start.start_address(),
+                NumOfPages::<Size4KiB>::new(TRAMPOLINE_PAGES).as_bytes(),
+                flags,
+            )
+        };
+
+        let trampoline = Self { frames, virt };
+
+        trampoline.copy_code();
+        trampoline.build_page_tables();
+
+        trampoline
+    }
+
+    // Returns the vector of the Startup IPI, which is the page number of the code.
+    fn vector(&self) -> u8 {
+        (self.phys(0).as_u64() / Size4KiB::SIZE).try_into().unwrap()
+    }
+
+    fn pml4(&self) -> PhysAddr {
+        self.phys(1)
+    }
+
+    fn set_parameters(&self, parameters: Parameters) {
+        let offset =
+            trampoline_parameters as *const () as usize - trampoline_start as *const () as usize;
+
+        // SAFETY: `offset` is within the copied code, and `Parameters` matches the layout.
+        unsafe {
+            (self.virt + offset)
+                .as_mut_ptr::<Parameters>()
+                .write_volatile(parameters);
+        }
+    }
+
+    fn copy_code(&self) {
+        let len = trampoline_end as *const () as usize - trampoline_start as *const () as usize;
+
+        assert!(
+            len <= Size4KiB::SIZE.try_into().unwrap(),
+            "The trampoline is too large."
+        );
+
+        // SAFETY: The code is `len` bytes, and the first page is mapped to `virt`.
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                trampoline_start as *const u8,
+                self.virt.as_mut_ptr(),
+                len,
+            );
+        }
+    }
+
+    fn build_page_tables(&self) {
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+
+        // The higher half of the kernel's PML4 is shared, so the entries can be copied.
+        *self.table(1) = vm::current_pml4();
+        self.table(1)[0].set_addr(self.phys(2), flags);
+
+        self.table(2).zero();
+        self.table(2)[0].set_addr(self.phys(3), flags);
+
+        self.table(3).zero();
+        self.table(3)[0].set_addr(PhysAddr::zero(), flags | PageTableFlags::HUGE_PAGE);
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    fn table(&self, page: usize) -> &mut PageTable {
+        let addr = self.virt + page * usize::try_from(Size4KiB::SIZE).unwrap();
+
+        // SAFETY: The page is mapped, and no other references point to it.
+        unsafe { &mut *addr.as_mut_ptr() }
+    }
+
+    fn phys(&self, page: u64) -> PhysAddr {
+        (self.frames.start + page).start_address()
+    }
+}
+impl Drop for Trampoline {
+    fn drop(&mut self) {
+        vm::unmap(
+            self.virt,
+            NumOfPages::<Size4KiB>::new(TRAMPOLINE_PAGES).as_bytes(),
+        );
+
+        vm::frame_allocator().dealloc(self.frames.start);
+    }
+}
+
+// The values which the trampoline reads. This must match the layout of `trampoline_parameters`
+// in `asm.s`.
+#[repr(C)]
+struct Parameters {
+    kernel_pml4: u64,
+    stack: u64,
+    entry: u64,
+    // The PML4 of the trampoline. It must be below 4 GiB as the processor loads it in real mode.
+    pml4: u32,
+}
+impl Parameters {
+    // Allocates a stack for the processor to start.
+    fn new(pml4: PhysAddr) -> Self {
+        let stack = vec![0_u8; AP_STACK_BYTES].leak();
+        let stack = VirtAddr::from_ptr(stack.as_ptr_range().end).align_down(16_u64);
+
+        Self {
+            kernel_pml4: Cr3::read().0.start_address().as_u64(),
+            stack: stack.as_u64(),
+            entry: ap_main as *const () as usize as u64,
+            pml4: pml4.as_u64().try_into().unwrap(),
+        }
+    }
+}
+
+extern "sysv64" {
+    fn trampoline_start();
+    fn trampoline_parameters();
+    fn trampoline_end();
+}
+
+#[cfg(test_on_qemu)]
+mod tests {
+    use super::{count, is_bsp};
+
+    pub(super) fn main() {
+        all_processors_are_online();
+    }
+
+    // `make test` runs QEMU with `-smp 4`.
+    fn all_processors_are_online() {
+        assert!(is_bsp(), "The bootstrap processor is not the index 0.");
+        assert_eq!(count(), 4, "Not all processors are online.");
+    }
+}
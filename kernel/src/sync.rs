@@ -0,0 +1,62 @@
+use {
+    core::{
+        mem::ManuallyDrop,
+        ops::{Deref, DerefMut},
+    },
+    spinning_top::{const_spinlock, Spinlock, SpinlockGuard},
+    x86_64::instructions::interrupts,
+};
+
+/// A spinlock which disables the interrupts while it is locked.
+///
+/// The interrupt handlers of the current processor cannot deadlock by locking the same lock, and
+/// the other processors wait until the lock is unlocked instead of failing.
+pub(crate) struct IrqSpinlock<T>(Spinlock<T>);
+impl<T> IrqSpinlock<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        Self(const_spinlock(value))
+    }
+
+    /// Disables the interrupts and spins until the lock is acquired. The interrupts are enabled
+    /// again when the returned guard is dropped if they were enabled before calling this method.
+    pub(crate) fn lock(&self) -> IrqSpinlockGuard<'_, T> {
+        let interrupts_enabled = interrupts::are_enabled();
+
+        interrupts::disable();
+
+        IrqSpinlockGuard {
+            guard: ManuallyDrop::new(self.0.lock()),
+            interrupts_enabled,
+        }
+    }
+}
+
+pub(crate) struct IrqSpinlockGuard<'a, T> {
+    guard: ManuallyDrop<SpinlockGuard<'a, T>>,
+    interrupts_enabled: bool,
+}
+impl<T> Deref for IrqSpinlockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+impl<T> DerefMut for IrqSpinlockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+impl<T> Drop for IrqSpinlockGuard<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: The guard is not used after this.
+        unsafe {
+            ManuallyDrop::drop(&mut self.guard);
+        }
+
+        // The lock must be unlocked before an interrupt handler tries to lock it.
+        if self.interrupts_enabled {
+            interrupts::enable();
+        }
+    }
+}
@@ -1,12 +1,17 @@
 use {
     crate::{
         gdt,
-        process::{ipc, Pid},
+        process::{self, ipc, Pid},
     },
-    aligned_ptr::ptr,
-    core::convert::TryInto,
-    ipc_api::syscalls::Ty,
+    core::{
+        convert::{TryFrom, TryInto},
+        mem,
+        mem::MaybeUninit,
+        slice,
+    },
+    ipc_api::{syscalls::Ty, Error, Message},
     num_traits::FromPrimitive,
+    os_units::Bytes,
     posix::sys::types::Pid as PosixPid,
     x86_64::{
         registers::{
@@ -35,35 +40,93 @@ pub(super) fn init() {
 }
 
 #[no_mangle]
-fn handle_syscall(index: u64, a1: u64, a2: u64) -> u64 {
+fn handle_syscall(index: u64, a1: u64, a2: u64, a3: u64) -> u64 {
     match FromPrimitive::from_u64(index) {
         Some(Ty::Send) => {
             let to = Pid::new(a1.try_into().unwrap());
-            let message = unsafe { ptr::get(a2 as *const _) };
 
-            match ipc::send(to, message) {
-                Ok(()) => 0,
-                #[allow(clippy::cast_sign_loss)]
-                Err(_) => -1_i32 as _,
-            }
+            Error::encode(message_from_user(a2).and_then(|message| ipc::send(to, message)))
+        }
+        Some(Ty::Receive) => Error::encode(
+            buffer_from_user(a2).and_then(|buffer| ipc::receive(receive_from(a1), buffer)),
+        ),
+        Some(Ty::Notify) => {
+            let to = Pid::new(a1.try_into().unwrap());
+
+            Error::encode(ipc::notify(to))
+        }
+        Some(Ty::SendReceive) => {
+            let to = Pid::new(a1.try_into().unwrap());
+
+            // `send_receive` reads the message through the pointer and writes the reply to it.
+            // Copying it first maps the pages of the buffer and validates it.
+            Error::encode(
+                message_from_user(a2)
+                    .and_then(|_| buffer_from_user(a2))
+                    .and_then(|buffer| ipc::send_receive(to, buffer)),
+            )
         }
-        Some(Ty::Receive) => {
-            // See: https://github.com/rust-lang/rust-clippy/issues/7648.
-            #[allow(clippy::cast_possible_truncation, clippy::invalid_upcast_comparisons)]
-            let from = if (a1 as PosixPid) < 0 {
-                ipc::ReceiveFrom::Any
-            } else {
-                ipc::ReceiveFrom::Pid(Pid::new(a1.try_into().unwrap()))
-            };
-
-            match ipc::receive(from, a2 as *mut _) {
-                Ok(()) => 0,
-                #[allow(clippy::cast_sign_loss)]
-                Err(_) => -1_i32 as _,
-            }
+        Some(Ty::SendNonBlock) => {
+            let to = Pid::new(a1.try_into().unwrap());
+
+            Error::encode(message_from_user(a2).and_then(|message| ipc::send_nonblock(to, message)))
         }
-        #[allow(clippy::cast_sign_loss)]
-        None => -1_i32 as _,
+        Some(Ty::ReceiveNonBlock) => Error::encode(
+            buffer_from_user(a2).and_then(|buffer| ipc::receive_nonblock(receive_from(a1), buffer)),
+        ),
+        Some(Ty::ReceiveTimeout) => Error::encode(
+            buffer_from_user(a2)
+                .and_then(|buffer| ipc::receive_timeout(receive_from(a1), buffer, a3)),
+        ),
+        None => Error::encode(Err(Error::InvalidSyscall)),
+    }
+}
+
+// Copies the message at `addr` from the address space of the running process. The pages of the
+// message are mapped if they are not accessed yet.
+fn message_from_user(addr: u64) -> Result<Message, Error> {
+    let addr = VirtAddr::try_new(addr).map_err(|_| Error::InvalidAddress)?;
+
+    let mut message = MaybeUninit::<Message>::zeroed();
+
+    // SAFETY: `message` is zeroed, so all of its bytes including the padding are initialized.
+    let bytes = unsafe {
+        slice::from_raw_parts_mut(message.as_mut_ptr().cast(), mem::size_of::<Message>())
+    };
+
+    process::copy_from_user((process::running(), addr), bytes)
+        .map_err(|_| Error::InvalidAddress)?;
+
+    // SAFETY: `Message` consists of integers, so any bit pattern is valid.
+    Ok(unsafe { message.assume_init() })
+}
+
+// Validates the buffer at `addr` to which the kernel writes a message. The kernel writes it
+// through the physical frame, so the buffer must be aligned as `Message` is, which keeps it in a
+// single page.
+fn buffer_from_user(addr: u64) -> Result<*mut Message, Error> {
+    let addr = VirtAddr::try_new(addr).map_err(|_| Error::InvalidAddress)?;
+
+    if !addr.is_aligned(u64::try_from(mem::align_of::<Message>()).unwrap()) {
+        return Err(Error::InvalidAddress);
+    }
+
+    process::validate_user_writable(
+        (process::running(), addr),
+        Bytes::new(mem::size_of::<Message>()),
+    )
+    .map_err(|_| Error::InvalidAddress)?;
+
+    Ok(addr.as_mut_ptr())
+}
+
+fn receive_from(a1: u64) -> ipc::ReceiveFrom {
+    // See: https://github.com/rust-lang/rust-clippy/issues/7648.
+    #[allow(clippy::cast_possible_truncation, clippy::invalid_upcast_comparisons)]
+    if (a1 as PosixPid) < 0 {
+        ipc::ReceiveFrom::Any
+    } else {
+        ipc::ReceiveFrom::Pid(Pid::new(a1.try_into().unwrap()))
     }
 }
 
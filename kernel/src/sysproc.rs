@@ -1,32 +1,44 @@
 use {
     crate::{
         boot_info,
+        interrupt::irq,
         process::{
             self,
             ipc::{receive, send, ReceiveFrom},
+            Grant,
         },
+        timer,
     },
+    config::MAX_PID,
     core::{
         convert::TryInto,
         mem::MaybeUninit,
-        ptr,
         sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
     },
     ipc_api::message::{Body, Header, Message},
     num_traits::FromPrimitive,
     os_units::Bytes,
     pid::Pid,
+    syscalls::{
+        ExecArgs, ExecError, ExecRequest, ForkError, GrantAccess, GrantError, GrantId, IrqError,
+        MemoryError, Priority, ProcessName, Protection, SetPriorityError, SpawnError,
+    },
     uefi::protocols::console::graphics_output::{
         PIXEL_BLUE_GREEN_RED_RESERVED_8_BIT_PER_COLOR,
         PIXEL_RED_GREEN_BLUE_RESERVED_8_BIT_PER_COLOR,
     },
+    vm::Kbox,
     x86_64::{
         instructions::port::{PortReadOnly, PortWriteOnly},
-        structures::paging::PageTableFlags,
+        structures::paging::{Page, PageTableFlags},
         PhysAddr, VirtAddr,
     },
 };
 
+// The processes which may take the interrupts of devices.
+const IRQ_OWNERS: [Pid; 2] = [pid::predefined::TTY, pid::predefined::XHCI];
+
 pub(crate) fn main() -> ! {
     loop {
         loop_iteration();
@@ -44,34 +56,166 @@ fn loop_iteration() {
         Some(syscalls::Ty::PmSyncsWithKernel) => handle_pm_syncs_with_kernel(&message),
         Some(syscalls::Ty::Inl) => handle_inl(&message),
         Some(syscalls::Ty::Outl) => handle_outl(&message),
+        Some(syscalls::Ty::CreateGrant) => handle_create_grant(&message),
+        Some(syscalls::Ty::RevokeGrant) => handle_revoke_grant(&message),
+        Some(syscalls::Ty::CopyFromGrant) => handle_copy_from_grant(&message),
+        Some(syscalls::Ty::CopyToGrant) => handle_copy_to_grant(&message),
+        Some(syscalls::Ty::CreateProcess) => handle_create_process(&message),
+        Some(syscalls::Ty::DestroyProcess) => handle_destroy_process(&message),
+        Some(syscalls::Ty::DuplicateProcess) => handle_duplicate_process(&message),
+        Some(syscalls::Ty::ExecProcess) => handle_exec_process(&message),
+        Some(syscalls::Ty::SetPriority) => handle_set_priority(&message),
+        Some(syscalls::Ty::GetMonotonicTime) => {
+            handle_get_monotonic_time(message.header.sender_pid);
+        }
+        Some(syscalls::Ty::Sleep) => handle_sleep(&message),
+        Some(syscalls::Ty::RegisterIrq) => handle_irq_request(&message, register_irq),
+        Some(syscalls::Ty::IrqEnable) => handle_irq_request(&message, irq::enable),
+        Some(syscalls::Ty::IrqAck) => handle_irq_request(&message, irq::acknowledge),
+        Some(syscalls::Ty::ResizeHeap) => handle_memory_request(&message, resize_heap),
+        Some(syscalls::Ty::MapAnonymous) => handle_memory_request(&message, map_anonymous),
+        Some(syscalls::Ty::UnmapAnonymous) => handle_memory_request(&message, unmap_anonymous),
+        Some(syscalls::Ty::ProtectAnonymous) => {
+            handle_memory_request(&message, protect_anonymous);
+        }
         _ => log::warn!("Unrecognized message: {:?}", message),
     }
 }
 
 fn handle_copy_data_from(message: &Message) {
-    let src_pid = Pid::new(message.body.1.try_into().unwrap());
-    let src_addr = VirtAddr::new(message.body.2);
-    let dst_addr = VirtAddr::new(message.body.3);
-    let bytes = Bytes::new(message.body.4.try_into().unwrap());
+    reply_grant_result(message.header.sender_pid, copy_data_from(message));
+}
 
-    // TODO: Remove this limitation.
-    assert!(bytes.as_usize() < 128, "`bytes` must be less than 128.");
+// The source process must grant the sender the read access to the copied region.
+fn copy_data_from(message: &Message) -> Result<(), GrantError> {
+    let dst_pid = message.header.sender_pid;
+    let src_pid = message.body.1.try_into().ok().map(Pid::new);
+    let src_pid = src_pid.ok_or(GrantError::InvalidGrant)?;
+    let src_addr = VirtAddr::try_new(message.body.2).map_err(|_| GrantError::InvalidAddress)?;
+    let dst_addr = VirtAddr::try_new(message.body.3).map_err(|_| GrantError::InvalidAddress)?;
+    let len = message
+        .body
+        .4
+        .try_into()
+        .map_err(|_| GrantError::InvalidAddress)?;
+
+    if !process::is_granted(src_pid, dst_pid, src_addr, len, GrantAccess::Read) {
+        return Err(GrantError::InvalidGrant);
+    }
 
-    let data = process::enter_address_space_and_do(src_pid, || {
-        let mut buffer = [0_u8; 128];
+    process::copy((src_pid, src_addr), (dst_pid, dst_addr), Bytes::new(len))
+        .map_err(|_| GrantError::InvalidAddress)
+}
 
-        unsafe {
-            ptr::copy(src_addr.as_ptr(), buffer.as_mut_ptr(), bytes.as_usize());
-        }
+fn handle_create_grant(message: &Message) {
+    let granter = message.header.sender_pid;
+
+    let r = create_grant(granter, message).map(|id| id.as_u64());
+
+    let body = match r {
+        Ok(id) => Body(0, id, 0, 0, 0),
+        Err(e) => Body(e as _, 0, 0, 0, 0),
+    };
+
+    let r = send(
+        granter,
+        Message {
+            header: Header::default(),
+            body,
+        },
+    );
+    r.unwrap_or_else(|_| log::warn!("Failed to send a message to {}", granter));
+}
+
+fn create_grant(granter: Pid, message: &Message) -> Result<GrantId, GrantError> {
+    let grantee = message
+        .body
+        .1
+        .try_into()
+        .map_err(|_| GrantError::InvalidGrant)?;
+    let grantee = Pid::new(grantee);
+    let start = VirtAddr::try_new(message.body.2).map_err(|_| GrantError::InvalidAddress)?;
+    let len = message
+        .body
+        .3
+        .try_into()
+        .map_err(|_| GrantError::InvalidAddress)?;
+    let access = FromPrimitive::from_u64(message.body.4).ok_or(GrantError::InvalidGrant)?;
+
+    let grant = Grant::new(grantee, start, len, access);
+
+    process::create_grant(granter, grant).ok_or(GrantError::NoSlot)
+}
+
+fn handle_revoke_grant(message: &Message) {
+    let granter = message.header.sender_pid;
+    let id = GrantId::from_u64(message.body.1);
+
+    let r = process::revoke_grant(granter, id).ok_or(GrantError::InvalidGrant);
+
+    reply_grant_result(granter, r.map(|_| ()));
+}
 
-        buffer
+fn handle_copy_from_grant(message: &Message) {
+    let grantee = message.header.sender_pid;
+
+    let r = copy_with_grant(message, GrantAccess::Read, |granted, local, len| {
+        process::copy(granted, local, len)
     });
 
-    process::enter_address_space_and_do(message.header.sender_pid, || unsafe {
-        ptr::copy(data.as_ptr(), dst_addr.as_mut_ptr(), bytes.as_usize());
+    reply_grant_result(grantee, r);
+}
+
+fn handle_copy_to_grant(message: &Message) {
+    let grantee = message.header.sender_pid;
+
+    let r = copy_with_grant(message, GrantAccess::Write, |granted, local, len| {
+        process::copy(local, granted, len)
     });
 
-    reply_ack(message.header.sender_pid);
+    reply_grant_result(grantee, r);
+}
+
+// `f` receives the granted region, the region of the grantee, and the number of bytes to copy.
+fn copy_with_grant(
+    message: &Message,
+    access: GrantAccess,
+    f: impl FnOnce((Pid, VirtAddr), (Pid, VirtAddr), Bytes) -> Result<(), process::InvalidAddress>,
+) -> Result<(), GrantError> {
+    let grantee = message.header.sender_pid;
+    let id = GrantId::from_u64(message.body.1);
+    let offset = message
+        .body
+        .2
+        .try_into()
+        .map_err(|_| GrantError::InvalidGrant)?;
+    let local = VirtAddr::try_new(message.body.3).map_err(|_| GrantError::InvalidAddress)?;
+    let len = message
+        .body
+        .4
+        .try_into()
+        .map_err(|_| GrantError::InvalidGrant)?;
+
+    let granted = process::resolve_grant(id, grantee, offset, len, access);
+    let granted = granted.ok_or(GrantError::InvalidGrant)?;
+
+    f(granted, (grantee, local), Bytes::new(len)).map_err(|_| GrantError::InvalidAddress)
+}
+
+fn reply_grant_result(to: Pid, r: Result<(), GrantError>) {
+    let body = match r {
+        Ok(()) => Body::default(),
+        Err(e) => Body(e as _, 0, 0, 0, 0),
+    };
+
+    let r = send(
+        to,
+        Message {
+            header: Header::default(),
+            body,
+        },
+    );
+    r.unwrap_or_else(|_| log::warn!("Failed to send a message to {}", to));
 }
 
 fn handle_get_screen_info(to: Pid) {
@@ -131,11 +275,16 @@ fn handle_pm_syncs_with_kernel(message: &Message) {
 
     assert_eq!(message.header.sender_pid, pid::predefined::PM);
 
-    let pid = Pid::new(NEXT_PID.fetch_add(1, Ordering::Relaxed));
+    // The PIDs of the processes created at boot time are not contiguous.
+    let pid = (NEXT_PID.load(Ordering::Relaxed)..MAX_PID)
+        .map(Pid::new)
+        .find(|&pid| process::process_exists(pid));
 
     let pm_msg = Message {
         header: Header::default(),
-        body: if process::process_exists(pid) {
+        body: if let Some(pid) = pid {
+            NEXT_PID.store(pid.as_usize() + 1, Ordering::Relaxed);
+
             Body(PM_PROC_INFO, pid.as_usize().try_into().unwrap(), 0, 0, 0)
         } else {
             Body(PM_PROC_END, 0, 0, 0, 0)
@@ -146,6 +295,281 @@ fn handle_pm_syncs_with_kernel(message: &Message) {
     r.expect("Failed to sync with PM.");
 }
 
+fn handle_create_process(message: &Message) {
+    let r = create_process(message);
+
+    let body = match r {
+        Ok(pid) => Body(0, pid.as_usize().try_into().unwrap(), 0, 0, 0),
+        Err(e) => Body(e as _, 0, 0, 0, 0),
+    };
+
+    let r = send(
+        message.header.sender_pid,
+        Message {
+            header: Header::default(),
+            body,
+        },
+    );
+    r.unwrap_or_else(|_| log::warn!("Failed to send a message to {}", message.header.sender_pid));
+}
+
+fn create_process(message: &Message) -> Result<Pid, SpawnError> {
+    if message.header.sender_pid != pid::predefined::PM {
+        return Err(SpawnError::PermissionDenied);
+    }
+
+    let name = ProcessName::from_body(&message.body);
+    let name = name.as_str().ok_or(SpawnError::InvalidName)?;
+
+    process::spawn(name).ok_or(SpawnError::CreationFailed)
+}
+
+// Only PM may destroy processes. The reply is `1` if the process is not destroyed.
+fn handle_destroy_process(message: &Message) {
+    let pid = message.body.1.try_into().ok().map(Pid::new);
+
+    let destroyed = message.header.sender_pid == pid::predefined::PM
+        && pid.is_some_and(|pid| pid != pid::predefined::SYSPROC && process::destroy(pid));
+
+    let reply = Message {
+        header: Header::default(),
+        body: Body((!destroyed).into(), 0, 0, 0, 0),
+    };
+
+    let r = send(message.header.sender_pid, reply);
+    r.unwrap_or_else(|_| log::warn!("Failed to send a message to {}", message.header.sender_pid));
+}
+
+fn handle_duplicate_process(message: &Message) {
+    let body = match duplicate_process(message) {
+        Ok(pid) => Body(0, pid.as_usize().try_into().unwrap(), 0, 0, 0),
+        Err(e) => Body(e as _, 0, 0, 0, 0),
+    };
+
+    let r = send(
+        message.header.sender_pid,
+        Message {
+            header: Header::default(),
+            body,
+        },
+    );
+    r.unwrap_or_else(|_| log::warn!("Failed to send a message to {}", message.header.sender_pid));
+}
+
+fn duplicate_process(message: &Message) -> Result<Pid, ForkError> {
+    if message.header.sender_pid != pid::predefined::VM_SERVER {
+        return Err(ForkError::PermissionDenied);
+    }
+
+    let parent = message.body.1.try_into().ok().map(Pid::new);
+    let parent = parent.ok_or(ForkError::CreationFailed)?;
+
+    process::fork(parent).ok_or(ForkError::CreationFailed)
+}
+
+fn handle_exec_process(message: &Message) {
+    let r = exec(message);
+
+    let reply = Message {
+        header: Header::default(),
+        body: Body(r.err().map_or(0, |e| e as _), 0, 0, 0, 0),
+    };
+
+    let r = send(message.header.sender_pid, reply);
+    r.unwrap_or_else(|_| log::warn!("Failed to send a message to {}", message.header.sender_pid));
+}
+
+fn exec(message: &Message) -> Result<(), ExecError> {
+    if message.header.sender_pid != pid::predefined::PM {
+        return Err(ExecError::PermissionDenied);
+    }
+
+    let pid = message.body.4.try_into().ok().map(Pid::new);
+    let pid = pid.ok_or(ExecError::InvalidArguments)?;
+
+    let request = ExecRequest::from_body(&message.body).ok_or(ExecError::InvalidArguments)?;
+
+    if request.len() > ExecArgs::MAX_LEN {
+        return Err(ExecError::InvalidArguments);
+    }
+
+    let mut buffer = Kbox::new([0; ExecArgs::MAX_LEN]);
+    let buffer = &mut buffer[..request.len()];
+
+    process::copy_from_user((pid, request.addr()), buffer)
+        .map_err(|_| ExecError::InvalidArguments)?;
+
+    let args = ExecArgs::parse(buffer, request.argc(), request.envc());
+    let args = args.ok_or(ExecError::InvalidArguments)?;
+
+    process::exec(pid, &args)
+}
+
+fn handle_set_priority(message: &Message) {
+    let r = set_priority(message);
+
+    let reply = Message {
+        header: Header::default(),
+        body: Body(r.err().map_or(0, |e| e as _), 0, 0, 0, 0),
+    };
+
+    let r = send(message.header.sender_pid, reply);
+    r.unwrap_or_else(|_| log::warn!("Failed to send a message to {}", message.header.sender_pid));
+}
+
+fn set_priority(message: &Message) -> Result<(), SetPriorityError> {
+    // INIT sets the priorities of the servers and the drivers it starts.
+    if message.header.sender_pid != pid::predefined::INIT {
+        return Err(SetPriorityError::PermissionDenied);
+    }
+
+    let pid = message
+        .body
+        .1
+        .try_into()
+        .map_err(|_| SetPriorityError::NoSuchProcess)?;
+    let pid = Pid::new(pid);
+    let priority = message.body.2.try_into().ok().and_then(Priority::new);
+    let priority = priority.ok_or(SetPriorityError::InvalidPriority)?;
+
+    if process::set_priority(pid, priority) {
+        Ok(())
+    } else {
+        Err(SetPriorityError::NoSuchProcess)
+    }
+}
+
+fn handle_get_monotonic_time(to: Pid) {
+    let nanos = timer::now().as_nanos();
+
+    let reply = Message {
+        header: Header::default(),
+        body: Body(0, nanos.try_into().unwrap_or(u64::MAX), 0, 0, 0),
+    };
+
+    let r = send(to, reply);
+    r.unwrap_or_else(|_| log::warn!("Failed to send a message to {}", to));
+}
+
+fn handle_sleep(message: &Message) {
+    let sender = message.header.sender_pid;
+
+    let duration = Duration::from_nanos(message.body.1);
+
+    // The kernel replies to the sender when it wakes up.
+    if duration.is_zero() || !process::sleep_until(sender, timer::tick_after(duration)) {
+        reply_ack(sender);
+    }
+}
+
+// `f` receives the vector and the PID of the sender.
+fn handle_irq_request(message: &Message, f: impl FnOnce(u8, Pid) -> Result<(), IrqError>) {
+    let sender = message.header.sender_pid;
+
+    let r = message
+        .body
+        .1
+        .try_into()
+        .map_err(|_| IrqError::InvalidVector)
+        .and_then(|vector| f(vector, sender));
+
+    let reply = Message {
+        header: Header::default(),
+        body: Body(r.err().map_or(0, |e| e as _), 0, 0, 0, 0),
+    };
+
+    let r = send(sender, reply);
+    r.unwrap_or_else(|_| log::warn!("Failed to send a message to {}", sender));
+}
+
+fn register_irq(vector: u8, pid: Pid) -> Result<(), IrqError> {
+    if !IRQ_OWNERS.contains(&pid) {
+        return Err(IrqError::PermissionDenied);
+    }
+
+    irq::register(vector, pid)
+}
+
+// `f` receives the PID of the process whose memory is changed and the message body, and returns
+// the value to reply in the second field.
+fn handle_memory_request(
+    message: &Message,
+    f: impl FnOnce(Pid, &Body) -> Result<u64, MemoryError>,
+) {
+    let sender = message.header.sender_pid;
+
+    let r = if sender == pid::predefined::VM_SERVER {
+        let pid = message.body.1.try_into().ok().map(Pid::new);
+
+        pid.ok_or(MemoryError::InvalidArguments)
+            .and_then(|pid| f(pid, &message.body))
+    } else {
+        Err(MemoryError::PermissionDenied)
+    };
+
+    let body = match r {
+        Ok(value) => Body(0, value, 0, 0, 0),
+        Err(e) => Body(e as _, 0, 0, 0, 0),
+    };
+
+    let r = send(
+        sender,
+        Message {
+            header: Header::default(),
+            body,
+        },
+    );
+    r.unwrap_or_else(|_| log::warn!("Failed to send a message to {}", sender));
+}
+
+fn resize_heap(pid: Pid, body: &Body) -> Result<u64, MemoryError> {
+    // `0` means that the heap is not changed.
+    let end = match body.2 {
+        0 => None,
+        end => Some(page_at(end)?),
+    };
+
+    process::resize_heap(pid, end).map(VirtAddr::as_u64)
+}
+
+fn map_anonymous(pid: Pid, body: &Body) -> Result<u64, MemoryError> {
+    let flags = page_table_flags(body.3)?;
+
+    process::map_anonymous(pid, body.2, flags).map(VirtAddr::as_u64)
+}
+
+fn unmap_anonymous(pid: Pid, body: &Body) -> Result<u64, MemoryError> {
+    process::unmap_anonymous(pid, page_at(body.2)?, body.3).map(|()| 0)
+}
+
+fn protect_anonymous(pid: Pid, body: &Body) -> Result<u64, MemoryError> {
+    let flags = page_table_flags(body.4)?;
+
+    process::protect_anonymous(pid, page_at(body.2)?, body.3, flags).map(|()| 0)
+}
+
+fn page_at(addr: u64) -> Result<Page, MemoryError> {
+    let addr = VirtAddr::try_new(addr).map_err(|_| MemoryError::InvalidAddress)?;
+
+    Page::from_start_address(addr).map_err(|_| MemoryError::InvalidAddress)
+}
+
+fn page_table_flags(protection: u64) -> Result<PageTableFlags, MemoryError> {
+    let protection = Protection::from_bits(protection).ok_or(MemoryError::InvalidArguments)?;
+
+    let mut flags = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+
+    if protection.contains(Protection::WRITE) {
+        flags |= PageTableFlags::WRITABLE;
+    }
+
+    if !protection.contains(Protection::EXECUTE) {
+        flags |= PageTableFlags::NO_EXECUTE;
+    }
+
+    Ok(flags)
+}
+
 fn handle_inl(message: &Message) {
     let port = message.body.1;
 
@@ -1,9 +1,20 @@
 use {
-    crate::process::ipc::{receive, send, ReceiveFrom},
-    core::{convert::TryInto, mem::MaybeUninit},
-    ipc_api::message::{Body, Header, Message},
+    crate::{
+        ioapic, process,
+        process::ipc::{
+            notify, receive, receive_nonblock, receive_timeout, send, send_nonblock, send_receive,
+            ReceiveFrom,
+        },
+        smp, timer,
+    },
+    core::{convert::TryInto, mem::MaybeUninit, time::Duration},
+    ipc_api::{
+        message::{Body, Header, Message},
+        Error,
+    },
     num_traits::FromPrimitive,
     pid::predefined,
+    syscalls::{GrantAccess, GrantError},
     x86_64::{instructions::hlt, VirtAddr},
 };
 
@@ -12,41 +23,61 @@ static DATA: &str = "Take the initiative and shoot flame. That's all.";
 pub(crate) fn main_1() -> ! {
     ipc();
 
+    nonblocking_ipc();
+
+    receive_with_timeout();
+
+    sleep_wakes_on_time();
+
+    all_processors_checked_in();
+
+    receive_notification();
+
+    irq_registration();
+
+    receive_interrupt_notification();
+
     let mut m = MaybeUninit::uninit();
     receive(predefined::TEST_2.into(), m.as_mut_ptr()).unwrap();
 
     let m = unsafe { m.assume_init() };
 
-    let mut buffer = [0; 128];
-
     let src_addr = VirtAddr::new(m.body.0);
-    let dst_addr = VirtAddr::from_ptr(buffer.as_mut_ptr());
     let count = DATA.len();
 
-    let message = Message {
-        header: Header::default(),
-        body: Body(
-            syscalls::Ty::CopyDataFrom as _,
-            predefined::TEST_2.as_usize().try_into().unwrap(),
-            src_addr.as_u64(),
-            dst_addr.as_u64(),
-            count.try_into().unwrap(),
-        ),
-    };
+    let mut buffer = [0; 128];
 
-    send(predefined::SYSPROC, message).unwrap();
+    // `TEST_2` does not grant the access to `DATA` yet.
+    assert_eq!(
+        copy_data_from_test_2(src_addr, &mut buffer[..count]),
+        Body(GrantError::InvalidGrant as _, 0, 0, 0, 0)
+    );
 
-    let mut m = MaybeUninit::uninit();
-    receive(predefined::SYSPROC.into(), m.as_mut_ptr()).unwrap();
+    assert_eq!(buffer, [0; 128]);
 
-    assert_eq!(unsafe { m.assume_init().body }, Body::default());
+    let mut m = Message {
+        header: Header::default(),
+        body: Body::default(),
+    };
+    send_receive(predefined::TEST_2, &mut m).unwrap();
+
+    assert_eq!(
+        copy_data_from_test_2(src_addr, &mut buffer[..count]),
+        Body::default()
+    );
 
     assert_eq!(&buffer[..count], DATA.as_bytes());
 
+    // The test user app may not be spawned by `init` yet, so receiving a message from its PID may
+    // fail.
     let mut m = MaybeUninit::uninit();
-    receive(predefined::TEST_USER_APP.into(), m.as_mut_ptr()).unwrap();
+    receive(ReceiveFrom::Any, m.as_mut_ptr()).unwrap();
 
-    let ty = FromPrimitive::from_u64(unsafe { m.assume_init().body.0 });
+    let m = unsafe { m.assume_init() };
+
+    assert_eq!(m.header.sender_pid, predefined::TEST_USER_APP);
+
+    let ty = FromPrimitive::from_u64(m.body.0);
 
     match ty {
         Some(syscalls::Ty::TestUserAppSucceed) => qemu::exit_success(),
@@ -56,17 +87,69 @@ pub(crate) fn main_1() -> ! {
 }
 
 pub(crate) fn main_2() -> ! {
+    let forged = Message {
+        header: Header {
+            sender_pid: predefined::TEST_2,
+            is_notification: true,
+        },
+        body: Body::default(),
+    };
+    send(predefined::TEST_1, forged).unwrap();
+
+    notify(predefined::TEST_1).unwrap();
+
     let m = Message {
         header: Header::default(),
         body: Body(DATA.as_ptr() as _, DATA.len().try_into().unwrap(), 0, 0, 0),
     };
     send(predefined::TEST_1, m).unwrap();
 
+    let mut m = MaybeUninit::uninit();
+    receive(predefined::TEST_1.into(), m.as_mut_ptr()).unwrap();
+
+    let mut grant = Message {
+        header: Header::default(),
+        body: Body(
+            syscalls::Ty::CreateGrant as _,
+            predefined::TEST_1.as_usize().try_into().unwrap(),
+            DATA.as_ptr() as _,
+            DATA.len().try_into().unwrap(),
+            GrantAccess::Read as _,
+        ),
+    };
+    send_receive(predefined::SYSPROC, &mut grant).unwrap();
+
+    assert_eq!(grant.body.0, 0, "Failed to create a grant.");
+
+    send(predefined::TEST_1, Message::default()).unwrap();
+
     loop {
         hlt();
     }
 }
 
+// Returns the body of the reply.
+fn copy_data_from_test_2(src_addr: VirtAddr, buffer: &mut [u8]) -> Body {
+    let message = Message {
+        header: Header::default(),
+        body: Body(
+            syscalls::Ty::CopyDataFrom as _,
+            predefined::TEST_2.as_usize().try_into().unwrap(),
+            src_addr.as_u64(),
+            VirtAddr::from_ptr(buffer.as_mut_ptr()).as_u64(),
+            buffer.len().try_into().unwrap(),
+        ),
+    };
+
+    send(predefined::SYSPROC, message).unwrap();
+
+    let mut m = MaybeUninit::uninit();
+    receive(predefined::SYSPROC.into(), m.as_mut_ptr()).unwrap();
+
+    // SAFETY: `receive` receives a message.
+    unsafe { m.assume_init().body }
+}
+
 fn ipc() {
     let m = Message {
         header: Header::default(),
@@ -81,4 +164,145 @@ fn ipc() {
     let m = unsafe { m.assume_init() };
 
     assert_eq!(m.body, Body::default());
+
+    let mut m = Message {
+        header: Header::default(),
+        body: Body(syscalls::Ty::Noop as _, 0, 0, 0, 0),
+    };
+    send_receive(predefined::SYSPROC, &mut m).unwrap();
+
+    assert_eq!(m.body, Body::default());
+}
+
+fn nonblocking_ipc() {
+    // SYSPROC is waiting for a message from any process, not sending one to this process.
+    let mut m = MaybeUninit::uninit();
+    let r = receive_nonblock(predefined::SYSPROC.into(), m.as_mut_ptr());
+
+    assert_eq!(r, Err(Error::WouldBlock));
+
+    // TEST_2 never receives a message.
+    let m = Message::default();
+    let r = send_nonblock(predefined::TEST_2, m);
+
+    assert_eq!(r, Err(Error::WouldBlock));
+}
+
+fn receive_with_timeout() {
+    // SYSPROC never sends a message to this process without a request.
+    let mut m = MaybeUninit::uninit();
+    let r = receive_timeout(predefined::SYSPROC.into(), m.as_mut_ptr(), 2);
+
+    assert_eq!(r, Err(Error::Timeout));
+}
+
+fn sleep_wakes_on_time() {
+    const SLEEP: Duration = Duration::from_millis(50);
+
+    // The process may not run immediately after waking up.
+    const TOLERANCE_TICKS: u64 = 3;
+
+    let start = timer::ticks();
+
+    let mut m = Message {
+        header: Header::default(),
+        body: Body(
+            syscalls::Ty::Sleep as _,
+            SLEEP.as_nanos().try_into().unwrap(),
+            0,
+            0,
+            0,
+        ),
+    };
+    send_receive(predefined::SYSPROC, &mut m).unwrap();
+
+    assert_eq!(m.body, Body::default());
+
+    let elapsed = timer::ticks() - start;
+    let expected = timer::duration_to_ticks(SLEEP);
+
+    assert!(
+        (expected..=expected + TOLERANCE_TICKS).contains(&elapsed),
+        "Slept for {} ticks instead of {}.",
+        elapsed,
+        expected
+    );
+}
+
+// Each processor receives its first timer interrupt one tick after it starts, and
+// `sleep_wakes_on_time` has waited for longer than that.
+fn all_processors_checked_in() {
+    assert!(
+        smp::all_checked_in(),
+        "Not all of the {} processors received a timer interrupt.",
+        smp::count()
+    );
+}
+
+fn receive_notification() {
+    let receive_from_test_2 = || {
+        let mut m = MaybeUninit::uninit();
+        receive(predefined::TEST_2.into(), m.as_mut_ptr()).unwrap();
+
+        // SAFETY: `receive` receives a message.
+        unsafe { m.assume_init() }
+    };
+
+    // TEST_2 sends a message disguised as a notification before notifying.
+    let m = receive_from_test_2();
+    assert!(!m.is_notification(), "A forged notification: {:?}", m);
+
+    let m = receive_from_test_2();
+    assert!(m.is_notification(), "Not a notification: {:?}", m);
+    assert_eq!(m.header.sender_pid, predefined::TEST_2);
+}
+
+fn irq_registration() {
+    // The ISA IRQ 1 of the keyboard.
+    let vector = ioapic::VECTOR_BASE + 1;
+
+    // The process does not own the vector yet.
+    assert_ne!(irq_request(syscalls::Ty::IrqAck, vector), 0);
+    assert_ne!(irq_request(syscalls::Ty::IrqEnable, vector), 0);
+
+    assert_eq!(irq_request(syscalls::Ty::RegisterIrq, vector), 0);
+    assert_eq!(irq_request(syscalls::Ty::RegisterIrq, vector), 0);
+    assert_eq!(irq_request(syscalls::Ty::IrqAck, vector), 0);
+
+    assert_ne!(
+        irq_request(syscalls::Ty::RegisterIrq, smp::RESCHEDULE_VECTOR),
+        0
+    );
+}
+
+fn receive_interrupt_notification() {
+    let vector = ioapic::VECTOR_BASE + 1;
+
+    assert!(process::notify_interrupt(predefined::TEST_1, vector));
+    assert!(process::notify_interrupt(predefined::TEST_1, vector + 1));
+
+    let mut m = MaybeUninit::uninit();
+    receive(predefined::HARDWARE.into(), m.as_mut_ptr()).unwrap();
+
+    // SAFETY: `receive` receives a message.
+    let m = unsafe { m.assume_init() };
+
+    assert!(m.is_notification(), "Not a notification: {:?}", m);
+    assert!(m.has_interrupt(vector), "No interrupt with {:#x}.", vector);
+    assert!(
+        m.has_interrupt(vector + 1),
+        "The interrupts are not coalesced."
+    );
+    assert!(!m.has_interrupt(vector + 2), "Unexpected interrupt.");
+}
+
+// Returns the error code in the reply.
+fn irq_request(ty: syscalls::Ty, vector: u8) -> u64 {
+    let mut m = Message {
+        header: Header::default(),
+        body: Body(ty as _, vector.into(), 0, 0, 0),
+    };
+    send_receive(predefined::SYSPROC, &mut m).unwrap();
+
+    m.body.0
 }
@@ -1,143 +1,175 @@
 use {
-    super::pm,
-    acpi::fadt::{TimerRegisterWidth, PM_TIMER_FREQUENCY_HZ},
+    super::{Reference, FREQUENCY_HZ},
+    crate::smp::{self, MAX_CPUS},
     apic::local::{
         lvt::{self, TimerMode},
         timer::{DivideConfiguration, DivideValue},
         CURRENT_COUNT, DIVIDE_CONFIGURATION, INITIAL_COUNT, LVT_TIMER,
     },
-    core::convert::TryInto,
+    core::{
+        convert::{TryFrom, TryInto},
+        sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    },
     vm::accessor::single::{read_only, write_only},
-    x86_64::PhysAddr,
 };
 
-/// # Safety
-///
-/// - `rsdp` must be the correct address of RSDP.
-/// - The start address of the Local APIC registers must be `0xfee0_0000` (the default one).
-pub(super) unsafe fn init(rsdp: PhysAddr) {
-    // SAFETY: The caller must ensure that `rsdp` is the correct address of RSDP and the start
-    // address of the Local APIC registers must be `0xfee0_0000`.
-    let frequency = unsafe { get_frequency(rsdp) };
+// The initial count for an interval of one tick.
+static COUNT_PER_TICK: AtomicU32 = AtomicU32::new(0);
 
-    // SAFETY: The caller must ensure that the start address of the Local APIC registers must be
-    // `0xfee0_0000`.
-    unsafe {
-        enable_interrupts(0x20, (frequency / 100).try_into().unwrap());
-    }
-}
+// The number of the ticks between the last timer interrupt and the next one of each processor.
+static PROGRAMMED_TICKS: [AtomicU64; MAX_CPUS] = [const { AtomicU64::new(1) }; MAX_CPUS];
 
+/// Measures the frequency of the Local APIC timer with `reference`.
+///
 /// # Safety
 ///
-/// - `rsdp` must be the correct address of RSDP.
-/// - The start address of the Local APIC registers must be `0xfee0_0000` (the default one).
-unsafe fn get_frequency(rsdp: PhysAddr) -> u64 {
-    // SAFETY: The caller must ensure that `rsdp` is the correct address of RSDP.
-    let measurer = unsafe { FrequencyMeasurer::from_rsdp_addr(rsdp) };
+/// The start address of the Local APIC registers must be `0xfee0_0000` (the default one).
+pub(super) unsafe fn calibrate(reference: &mut Reference) {
+    // SAFETY: The caller must ensure that the start address of the Local APIC registers must be
+    // `0xfee0_0000`.
+    let frequency = unsafe { measure_hz(reference) };
 
-    // SAFETY: The caller must not change the start adress of the Local APIC registers.
-    unsafe { measurer.measure_hz() }
+    COUNT_PER_TICK.store(
+        (frequency / FREQUENCY_HZ).try_into().unwrap(),
+        Ordering::Relaxed,
+    );
 }
 
+/// Starts the timer to interrupt with `vector` after one tick.
+///
 /// # Safety
 ///
-/// The caller must ensure that the start address of the Local APIC registers must be `0xfee0_0000`
-/// (the default one).
-unsafe fn enable_interrupts(vector: u8, initial_count: u32) {
+/// - The timer must be calibrated by [`calibrate`].
+/// - The start address of the Local APIC registers must be `0xfee0_0000` (the default one).
+pub(super) unsafe fn start(vector: u8) {
+    // The timer is reprogrammed on every interrupt so that it does not interrupt while no process
+    // needs it.
+    //
     // SAFETY: The caller must ensure that the start address of the Local APIC registers must be
     // `0xfee0_0000`. (the default one)
     unsafe {
         set_lvt_timer(
             *lvt::Timer::default()
                 .set_vector(vector)
-                .set_timer_mode(TimerMode::Periodic),
+                .set_timer_mode(TimerMode::OneShot),
         );
         set_divide_config(*DivideConfiguration::default().set_divide_value(DivideValue::DivideBy1));
-        set_initial_count(initial_count);
+        set_initial_count(count_per_tick());
     }
 }
 
-pub(super) struct FrequencyMeasurer {
-    reader: pm::RegisterReader,
-    width: TimerRegisterWidth,
+/// Returns the number of the ticks elapsed since the timer was programmed. This function must be
+/// called only on the timer interrupt.
+pub(super) fn take_elapsed_ticks() -> u64 {
+    programmed_ticks().load(Ordering::Relaxed)
 }
-impl FrequencyMeasurer {
-    /// # Safety
-    ///
-    /// `rsdp` must be the correct address of RSDP.
-    pub(super) unsafe fn from_rsdp_addr(rsdp: PhysAddr) -> Self {
-        // SAFETY: The caller must ensure that `rsdp` is the correct address of RSDP.
-        let timer = unsafe { pm::timer_info_from_rsdp_addr(rsdp) };
-
-        Self {
-            reader: pm::RegisterReader::new(&timer),
-            width: timer.width(),
-        }
+
+/// Programs the timer to interrupt after `ticks` ticks, or after the longest interval the timer
+/// supports if `ticks` is `None`.
+///
+/// # Safety
+///
+/// The start address of the Local APIC registers must be `0xfee0_0000` (the default one).
+pub(super) unsafe fn interrupt_after(ticks: Option<u64>) {
+    let ticks = ticks.unwrap_or(u64::MAX).clamp(1, max_ticks());
+
+    programmed_ticks().store(ticks, Ordering::Relaxed);
+
+    // SAFETY: The caller must ensure that the start address of the Local APIC registers must be
+    // `0xfee0_0000`.
+    unsafe {
+        set_initial_count(count_per_tick() * u32::try_from(ticks).unwrap());
     }
+}
 
-    /// # Safety
-    ///
-    /// The address of the Local APIC registers must be `0xfee0_0000` (the default one).
-    pub(super) unsafe fn measure_hz(mut self) -> u64 {
-        const COUNT_MAX: u32 = u32::MAX;
-        const TIME_TO_ELAPSE: u32 = 100;
-        const SEC_IN_MSEC: u32 = 1000;
-
-        // SAFETY: The caller must ensure that the start address of the Local APIC registers is
-        // `0xfee0_0000`.
-        unsafe {
-            set_divide_config(
-                *DivideConfiguration::default().set_divide_value(DivideValue::DivideBy1),
-            );
-            set_lvt_timer(
-                *lvt::Timer::default()
-                    .set_mask()
-                    .set_timer_mode(TimerMode::OneShot),
-            );
-            set_initial_count(COUNT_MAX);
-        }
-
-        self.wait_milliseconds(TIME_TO_ELAPSE);
-
-        // SAFETY: The caller must ensure that the start address of the Local APIC register is
-        // `0xfee0_0000`.
-        let end_count = unsafe { current_count() };
-
-        u64::from(COUNT_MAX - end_count) * u64::from(SEC_IN_MSEC / TIME_TO_ELAPSE)
+/// Reprograms the timer to interrupt at the next tick, and returns the number of the ticks elapsed
+/// since the timer was programmed.
+///
+/// # Safety
+///
+/// The start address of the Local APIC registers must be `0xfee0_0000` (the default one).
+pub(super) unsafe fn resume_ticking() -> u64 {
+    // SAFETY: The caller must ensure that the start address of the Local APIC registers must be
+    // `0xfee0_0000`.
+    let remaining = unsafe { current_count() };
+
+    // The interrupt is pending, and its handler will count the ticks and program the timer.
+    if remaining == 0 {
+        return 0;
     }
 
-    fn wait_milliseconds(&mut self, msec: u32) {
-        assert!(
-            self.width != TimerRegisterWidth::Bits24 || msec < 0x0100_0000,
-            "Overflow detected."
-        );
+    let per_tick = count_per_tick();
+    let programmed = per_tick * u32::try_from(programmed_ticks().load(Ordering::Relaxed)).unwrap();
+    let elapsed = programmed - remaining;
 
-        // Do not make `start` inline, otherwise an overflow will happen.
-        let start: u64 = self.reader.read().into();
-        let end = start + u64::from(PM_TIMER_FREQUENCY_HZ * msec / 1000);
+    programmed_ticks().store(1, Ordering::Relaxed);
 
-        while u64::from(self.reader.read()) < end {
-            core::hint::spin_loop();
-        }
+    // SAFETY: The caller must ensure that the start address of the Local APIC registers must be
+    // `0xfee0_0000`.
+    unsafe {
+        set_initial_count(per_tick - elapsed % per_tick);
     }
+
+    (elapsed / per_tick).into()
 }
 
 /// # Safety
 ///
 /// The start address of the Local APIC registers must be `0xfee0_0000`. (the default one)
-unsafe fn current_count() -> u32 {
-    unsafe { read_only(CURRENT_COUNT).read_volatile() }
+pub(super) unsafe fn set_lvt_timer(lvt_timer: lvt::Timer) {
+    // SAFETY: The caller must ensure that the start address of the Local APIC registers must be
+    // `0xfee0_0000`.
+    unsafe {
+        write_only(LVT_TIMER).write_volatile(lvt_timer);
+    }
+}
+
+fn programmed_ticks() -> &'static AtomicU64 {
+    &PROGRAMMED_TICKS[smp::index()]
+}
+
+fn count_per_tick() -> u32 {
+    COUNT_PER_TICK.load(Ordering::Relaxed)
+}
+
+fn max_ticks() -> u64 {
+    (u32::MAX / count_per_tick()).into()
 }
 
 /// # Safety
 ///
-/// The start address of the Local APIC registers must be `0xfee0_0000`. (the default one)
-unsafe fn set_lvt_timer(lvt_timer: lvt::Timer) {
-    // SAFETY: The caller must ensure that the start address of the Local APIC registers must be
+/// The address of the Local APIC registers must be `0xfee0_0000` (the default one).
+unsafe fn measure_hz(reference: &mut Reference) -> u64 {
+    const COUNT_MAX: u32 = u32::MAX;
+    const TIME_TO_ELAPSE: u32 = 100;
+    const SEC_IN_MSEC: u32 = 1000;
+
+    // SAFETY: The caller must ensure that the start address of the Local APIC registers is
     // `0xfee0_0000`.
     unsafe {
-        write_only(LVT_TIMER).write_volatile(lvt_timer);
+        set_divide_config(*DivideConfiguration::default().set_divide_value(DivideValue::DivideBy1));
+        set_lvt_timer(
+            *lvt::Timer::default()
+                .set_mask()
+                .set_timer_mode(TimerMode::OneShot),
+        );
+        set_initial_count(COUNT_MAX);
     }
+
+    reference.wait_milliseconds(TIME_TO_ELAPSE);
+
+    // SAFETY: The caller must ensure that the start address of the Local APIC register is
+    // `0xfee0_0000`.
+    let end_count = unsafe { current_count() };
+
+    u64::from(COUNT_MAX - end_count) * u64::from(SEC_IN_MSEC / TIME_TO_ELAPSE)
+}
+
+/// # Safety
+///
+/// The start address of the Local APIC registers must be `0xfee0_0000`. (the default one)
+unsafe fn current_count() -> u32 {
+    unsafe { read_only(CURRENT_COUNT).read_volatile() }
 }
 
 /// # Safety
@@ -0,0 +1,131 @@
+//! The High Precision Event Timer.
+//!
+//! Only the main counter is used, as the reference to calibrate the other timers if the ACPI PM
+//! Timer is absent, and as a clock. The comparators are not used because the Local APIC timer of
+//! each processor generates the ticks.
+
+use {
+    conquer_once::spin::OnceCell,
+    core::{
+        convert::TryInto,
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    },
+    vm::accessor::single::{read_only, read_write, ReadOnly},
+    x86_64::PhysAddr,
+};
+
+// The offsets of the registers from the start address of the timer block.
+const GENERAL_CAPABILITIES_AND_ID: u64 = 0x00;
+const GENERAL_CONFIGURATION: u64 = 0x10;
+const MAIN_COUNTER_VALUE: u64 = 0xf0;
+
+// The bits of the registers.
+const COUNT_SIZE_CAP: u64 = 1 << 13;
+const ENABLE_CNF: u64 = 1;
+
+// The specification limits the period of the main counter to 100 ns.
+const MAX_PERIOD_FS: u64 = 100_000_000;
+
+const FEMTOS_PER_NANO: u128 = 1_000_000;
+const FEMTOS_PER_MILLI: u64 = 1_000_000_000_000;
+
+static HPET: OnceCell<Hpet> = OnceCell::uninit();
+
+// The main counter value when the tick count was zero.
+static ORIGIN: AtomicU64 = AtomicU64::new(0);
+
+/// Enables the main counter of the HPET whose registers start at `base`, and returns it. Returns
+/// `None` if the HPET reports an invalid period.
+///
+/// # Safety
+///
+/// `base` must be the start address of the registers of an HPET.
+pub(super) unsafe fn init(base: PhysAddr) -> Option<&'static Hpet> {
+    // SAFETY: The caller must ensure that `base` is the correct address.
+    let hpet = unsafe { Hpet::new(base) }?;
+
+    HPET.try_init_once(|| hpet)
+        .expect("`HPET` is already initialized.");
+
+    Some(HPET.try_get().expect("`HPET` is not initialized."))
+}
+
+/// Makes the current counter value the origin of [`elapsed`]. It does nothing if there is no HPET.
+pub(super) fn set_origin() {
+    if let Ok(hpet) = HPET.try_get() {
+        ORIGIN.store(hpet.read(), Ordering::Relaxed);
+    }
+}
+
+/// Returns the time elapsed since the origin, or `None` if there is no HPET with a 64-bit main
+/// counter. A 32-bit one wraps around in a few minutes.
+pub(super) fn elapsed() -> Option<Duration> {
+    let hpet = HPET.try_get().ok().filter(|h| h.is_64_bit)?;
+
+    let counts = hpet.read() - ORIGIN.load(Ordering::Relaxed);
+    let nanos = u128::from(counts) * u128::from(hpet.period_fs) / FEMTOS_PER_NANO;
+
+    Some(Duration::from_nanos(nanos.try_into().unwrap_or(u64::MAX)))
+}
+
+pub(super) struct Hpet {
+    main_counter: ReadOnly<u64>,
+    // The period of the main counter in femtoseconds.
+    period_fs: u64,
+    is_64_bit: bool,
+}
+impl Hpet {
+    /// # Safety
+    ///
+    /// `base` must be the start address of the registers of an HPET.
+    unsafe fn new(base: PhysAddr) -> Option<Self> {
+        // SAFETY: The caller must ensure that `base` is the correct address.
+        let capabilities: u64 =
+            unsafe { read_only(base + GENERAL_CAPABILITIES_AND_ID) }.read_volatile();
+
+        let period_fs = capabilities >> 32;
+
+        if !(1..=MAX_PERIOD_FS).contains(&period_fs) {
+            log::warn!("Invalid period of the HPET: {} fs", period_fs);
+
+            return None;
+        }
+
+        // SAFETY: The caller must ensure that `base` is the correct address.
+        let mut configuration = unsafe { read_write::<u64>(base + GENERAL_CONFIGURATION) };
+
+        // The interrupts are not used, so only the main counter is enabled.
+        configuration.update_volatile(|c| *c |= ENABLE_CNF);
+
+        Some(Self {
+            // SAFETY: The caller must ensure that `base` is the correct address.
+            main_counter: unsafe { read_only(base + MAIN_COUNTER_VALUE) },
+            period_fs,
+            is_64_bit: capabilities & COUNT_SIZE_CAP != 0,
+        })
+    }
+
+    pub(super) fn wait_milliseconds(&self, msec: u32) {
+        let mask = if self.is_64_bit {
+            u64::MAX
+        } else {
+            u32::MAX.into()
+        };
+
+        let count = u64::from(msec) * FEMTOS_PER_MILLI / self.period_fs;
+
+        assert!(count < mask, "Overflow detected.");
+
+        let start = self.read();
+
+        // The counter may wrap around while waiting.
+        while self.read().wrapping_sub(start) & mask < count {
+            core::hint::spin_loop();
+        }
+    }
+
+    fn read(&self) -> u64 {
+        self.main_counter.read_volatile()
+    }
+}
@@ -1,15 +1,280 @@
-use {acpi::GenericAddressStructure, x86_64::PhysAddr};
+use {
+    crate::smp,
+    acpi::GenericAddressStructure,
+    conquer_once::spin::OnceCell,
+    core::{
+        convert::TryInto,
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    },
+    spinning_top::{Spinlock, SpinlockGuard},
+    vm::accessor::Mapper,
+};
 
 mod apic;
+mod hpet;
 mod pm;
+mod tsc;
 
+/// The number of the timer interrupts per second.
+pub(crate) const FREQUENCY_HZ: u64 = 100;
+
+const NANOS_PER_TICK: u64 = 1_000_000_000 / FREQUENCY_HZ;
+
+const VECTOR: u8 = 0x20;
+
+// The number of the ticks since the boot. A timer interrupt may represent multiple ticks while no
+// process is runnable.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+static SOURCE: OnceCell<Source> = OnceCell::uninit();
+
+static REFERENCE: OnceCell<Spinlock<Reference>> = OnceCell::uninit();
+
+/// # Safety
+///
+/// The start address of the Local APIC registers must be `0xfee0_0000` (the default one).
+pub(super) unsafe fn init(tables: &acpi::Tables<Mapper>) {
+    // An HPET whose registers are not memory-mapped is not used, and the ACPI PM Timer is used
+    // instead.
+    let hpet = tables.hpet.as_ref().and_then(|table| {
+        let base = table.base_address().ok()?;
+
+        // SAFETY: `base` is taken from the HPET table.
+        unsafe { hpet::init(base) }
+    });
+
+    // Likewise, the HPET is used if the information of the ACPI PM Timer is broken.
+    let pm = tables.fadt.as_ref().and_then(|fadt| fadt.pm_timer());
+    let pm = pm.and_then(Result::ok);
+
+    let reference = match (pm, hpet) {
+        (Some(pm), _) => Reference::Pm(pm::Timer::new(&pm)),
+        (None, Some(hpet)) => Reference::Hpet(hpet),
+        (None, None) => panic!("Neither the ACPI PM Timer nor the HPET is available."),
+    };
+
+    // The TSC may change its rate unless it is invariant.
+    let invariant = tsc::is_invariant();
+
+    REFERENCE
+        .try_init_once(|| Spinlock::new(reference))
+        .expect("`REFERENCE` is already initialized.");
+
+    if invariant {
+        tsc::calibrate(&mut self::reference());
+    }
+
+    let source = if invariant && tsc::supports_deadline_mode() {
+        Source::TscDeadline
+    } else {
+        // SAFETY: The caller must ensure that the start address of the Local APIC registers is
+        // `0xfee0_0000`.
+        unsafe {
+            apic::calibrate(&mut self::reference());
+        }
+
+        Source::Apic
+    };
+
+    SOURCE
+        .try_init_once(|| source)
+        .expect("`SOURCE` is already initialized.");
+
+    tsc::set_origin();
+    hpet::set_origin();
+
+    // SAFETY: The timer is calibrated, and the caller must ensure that the start address of the
+    // Local APIC registers is `0xfee0_0000`.
+    unsafe {
+        match source {
+            Source::Apic => apic::start(VECTOR),
+            Source::TscDeadline => tsc::start_deadline_timer(VECTOR),
+        }
+    }
+
+    #[cfg(test_on_qemu)]
+    tests::main();
+}
+
+/// Starts the timer of the current application processor to interrupt after one tick.
+///
 /// # Safety
 ///
-/// - `rsdp` must be the correct address of RSDP.
-/// - The start address of the Local APIC registers must be `0xfee0_0000` (the default one).
-pub(super) unsafe fn init(rsdp: PhysAddr) {
-    // SAFETY: The caller must uphold the safety requirements.
+/// The start address of the Local APIC registers must be `0xfee0_0000` (the default one).
+pub(crate) unsafe fn init_ap() {
+    // SAFETY: The bootstrap processor has calibrated the timer, and the caller must ensure that
+    // the start address of the Local APIC registers is `0xfee0_0000`.
     unsafe {
-        apic::init(rsdp);
+        match source() {
+            Source::Apic => apic::start(VECTOR),
+            Source::TscDeadline => {
+                tsc::set_last_tick();
+                tsc::start_deadline_timer(VECTOR);
+            }
+        }
+    }
+}
+
+#[must_use]
+pub(crate) fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Adds the ticks elapsed since the last timer interrupt to the tick count, and returns the new
+/// count. This function must be called only on the timer interrupt.
+pub(crate) fn tick() -> u64 {
+    let elapsed = match source() {
+        Source::Apic => apic::take_elapsed_ticks(),
+        Source::TscDeadline => tsc::take_elapsed_ticks(),
+    };
+
+    advance(elapsed)
+}
+
+/// Programs the timer to interrupt after `ticks` ticks, or not to interrupt as long as possible if
+/// `ticks` is `None`. This function must be called only on the timer interrupt.
+pub(crate) fn set_next_interrupt(ticks: Option<u64>) {
+    // SAFETY: This OS does not change the start address of the Local APIC registers, and `source`
+    // returns the mode of the timer.
+    unsafe {
+        match source() {
+            Source::Apic => apic::interrupt_after(ticks),
+            Source::TscDeadline => tsc::interrupt_after(ticks),
+        }
+    }
+}
+
+/// Adds the ticks elapsed since the last timer interrupt to the tick count, and reprograms the
+/// timer to interrupt at the next tick. This function must be called with the interrupts disabled.
+pub(crate) fn resume_ticking() {
+    // SAFETY: This OS does not change the start address of the Local APIC registers, and `source`
+    // returns the mode of the timer.
+    let elapsed = unsafe {
+        match source() {
+            Source::Apic => apic::resume_ticking(),
+            Source::TscDeadline => tsc::resume_ticking(),
+        }
+    };
+
+    advance(elapsed);
+}
+
+/// Busy-waits for `msec` milliseconds. Unlike [`now`], this function works while the interrupts are
+/// disabled.
+pub(crate) fn wait_milliseconds(msec: u32) {
+    reference().wait_milliseconds(msec);
+}
+
+/// Returns the time elapsed since the timer started. The resolution is one tick.
+#[must_use]
+pub(crate) fn monotonic_time() -> Duration {
+    Duration::from_nanos(ticks().saturating_mul(NANOS_PER_TICK))
+}
+
+/// Returns the time elapsed since the timer started with the resolution of the TSC if it is
+/// invariant, of the HPET if it exists, or [`monotonic_time`] otherwise.
+///
+/// The tick count never goes ahead of this clock.
+#[must_use]
+pub(crate) fn now() -> Duration {
+    tsc::elapsed()
+        .or_else(hpet::elapsed)
+        .unwrap_or_else(monotonic_time)
+}
+
+/// Returns the first tick at which `duration` has elapsed from [`now`].
+#[must_use]
+pub(crate) fn tick_after(duration: Duration) -> u64 {
+    duration_to_ticks(now().saturating_add(duration))
+}
+
+/// Converts `duration` into the number of ticks, rounding up.
+#[must_use]
+pub(crate) fn duration_to_ticks(duration: Duration) -> u64 {
+    let ticks = duration.as_nanos().div_ceil(NANOS_PER_TICK.into());
+
+    ticks.try_into().unwrap_or(u64::MAX)
+}
+
+// The timer which generates the ticks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Source {
+    // The Local APIC timer in the one-shot mode.
+    Apic,
+    // The Local APIC timer in the TSC-deadline mode.
+    TscDeadline,
+}
+
+// Every processor has its own timer, but only the bootstrap processor advances the tick count so
+// that the same ticks are not counted more than once. Returns the new count.
+fn advance(elapsed: u64) -> u64 {
+    if smp::is_bsp() {
+        TICKS.fetch_add(elapsed, Ordering::Relaxed) + elapsed
+    } else {
+        ticks()
+    }
+}
+
+fn source() -> Source {
+    *SOURCE.try_get().expect("`SOURCE` is not initialized.")
+}
+
+fn reference<'a>() -> SpinlockGuard<'a, Reference> {
+    let reference = REFERENCE
+        .try_get()
+        .expect("`REFERENCE` is not initialized.");
+
+    reference.lock()
+}
+
+// The timer used to measure the frequencies of the other timers.
+enum Reference {
+    Pm(pm::Timer),
+    Hpet(&'static hpet::Hpet),
+}
+impl Reference {
+    fn wait_milliseconds(&mut self, msec: u32) {
+        match self {
+            Self::Pm(pm) => pm.wait_milliseconds(msec),
+            Self::Hpet(hpet) => hpet.wait_milliseconds(msec),
+        }
+    }
+}
+
+#[cfg(test_on_qemu)]
+mod tests {
+    use {
+        super::{hpet, reference, Reference},
+        core::time::Duration,
+    };
+
+    pub(super) fn main() {
+        hpet_agrees_with_pm_timer();
+    }
+
+    // QEMU provides both the ACPI PM Timer and the HPET with a 64-bit main counter.
+    fn hpet_agrees_with_pm_timer() {
+        let mut reference = reference();
+
+        const WAIT: Duration = Duration::from_millis(50);
+
+        assert!(
+            matches!(*reference, Reference::Pm(_)),
+            "The ACPI PM Timer is not used."
+        );
+
+        let start = hpet::elapsed().expect("No HPET with a 64-bit main counter.");
+
+        reference.wait_milliseconds(WAIT.as_millis().try_into().unwrap());
+
+        let elapsed = hpet::elapsed().unwrap() - start;
+
+        assert!(
+            elapsed >= WAIT * 9 / 10 && elapsed <= WAIT * 3 / 2,
+            "The HPET measured {:?} while the ACPI PM Timer measured {:?}.",
+            elapsed,
+            WAIT
+        );
     }
 }
@@ -1,14 +1,44 @@
 use {
     super::GenericAddressStructure,
-    acpi::fadt::PmTimer,
+    acpi::fadt::{PmTimer, TimerRegisterWidth, PM_TIMER_FREQUENCY_HZ},
     core::convert::TryInto,
-    vm::accessor::{
-        single::{read_only, ReadOnly},
-        Mapper,
-    },
-    x86_64::{instructions::port::PortReadOnly, PhysAddr},
+    vm::accessor::single::{read_only, ReadOnly},
+    x86_64::instructions::port::PortReadOnly,
 };
 
+/// The ACPI PM timer used to wait for a fixed time, such as to calibrate the other timers.
+pub(super) struct Timer {
+    reader: RegisterReader,
+    width: TimerRegisterWidth,
+}
+impl Timer {
+    pub(super) fn new(timer: &PmTimer) -> Self {
+        Self {
+            reader: RegisterReader::new(timer),
+            width: timer.width(),
+        }
+    }
+
+    pub(super) fn wait_milliseconds(&mut self, msec: u32) {
+        let mask = match self.width {
+            TimerRegisterWidth::Bits24 => 0x00ff_ffff,
+            TimerRegisterWidth::Bits32 => u32::MAX,
+        };
+
+        let count = u64::from(PM_TIMER_FREQUENCY_HZ) * u64::from(msec) / 1000;
+        let count: u32 = count.try_into().unwrap();
+
+        assert!(count < mask, "Overflow detected.");
+
+        let start = self.reader.read();
+
+        // The counter may wrap around while waiting.
+        while self.reader.read().wrapping_sub(start) & mask < count {
+            core::hint::spin_loop();
+        }
+    }
+}
+
 pub(super) enum RegisterReader {
     Memory(ReadOnly<u32>),
     Io(PortReadOnly<u32>),
@@ -32,19 +62,3 @@ impl RegisterReader {
         }
     }
 }
-
-/// # Safety
-///
-/// `rsdp` must be the correct address of RSDP.
-pub(super) unsafe fn timer_info_from_rsdp_addr(rsdp: PhysAddr) -> PmTimer {
-    // SAFETY: The caller must ensure that `rsdp` is the correct address of RSDP.
-    let tables = unsafe { acpi::Tables::from_rsdp_addr(rsdp, &Mapper) };
-    let tables = tables.expect("Failed to get the information of ACPI.");
-
-    let fadt = tables.fadt;
-    let fadt = fadt.expect("No FADT information.");
-
-    let timer_info = fadt.pm_timer();
-    let timer_info = timer_info.expect("Failed to get the information of the ACPI PM Timer");
-    timer_info.expect("ACPI PM Timer is not supported.")
-}
@@ -0,0 +1,178 @@
+use {
+    super::{apic::set_lvt_timer, Reference, FREQUENCY_HZ},
+    crate::smp::{self, MAX_CPUS},
+    apic::local::lvt::{self, TimerMode},
+    core::{
+        arch::x86_64::{__cpuid, _mm_mfence, _rdtsc},
+        convert::TryInto,
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    },
+    x86_64::registers::model_specific::Msr,
+};
+
+const IA32_TSC_DEADLINE: Msr = Msr::new(0x6e0);
+
+// The frequency of the TSC, or `0` if it is not calibrated.
+static HZ: AtomicU64 = AtomicU64::new(0);
+
+// The TSC value when the tick count was zero.
+static ORIGIN: AtomicU64 = AtomicU64::new(0);
+
+// The TSC value at the last tick counted by the TSC-deadline timer of each processor.
+static LAST_TICK: [AtomicU64; MAX_CPUS] = [const { AtomicU64::new(0) }; MAX_CPUS];
+
+/// Returns `true` if the TSC runs at a constant rate regardless of the power state of the
+/// processor.
+pub(super) fn is_invariant() -> bool {
+    const EXTENDED_FUNCTIONS: u32 = 0x8000_0000;
+    const ADVANCED_POWER_MANAGEMENT: u32 = 0x8000_0007;
+
+    __cpuid(EXTENDED_FUNCTIONS).eax >= ADVANCED_POWER_MANAGEMENT
+        && __cpuid(ADVANCED_POWER_MANAGEMENT).edx & (1 << 8) != 0
+}
+
+/// Returns `true` if the Local APIC timer supports the TSC-deadline mode.
+pub(super) fn supports_deadline_mode() -> bool {
+    const FEATURE_INFORMATION: u32 = 1;
+
+    __cpuid(FEATURE_INFORMATION).ecx & (1 << 24) != 0
+}
+
+/// Measures the frequency of the TSC with `reference`.
+pub(super) fn calibrate(reference: &mut Reference) {
+    const TIME_TO_ELAPSE: u32 = 100;
+    const SEC_IN_MSEC: u32 = 1000;
+
+    let start = read();
+
+    reference.wait_milliseconds(TIME_TO_ELAPSE);
+
+    let end = read();
+
+    HZ.store(
+        (end - start) * u64::from(SEC_IN_MSEC / TIME_TO_ELAPSE),
+        Ordering::Relaxed,
+    );
+}
+
+/// Makes the current TSC value the origin of [`elapsed`] and the ticks of the TSC-deadline timer.
+pub(super) fn set_origin() {
+    let now = read();
+
+    ORIGIN.store(now, Ordering::Relaxed);
+    last_tick().store(now, Ordering::Relaxed);
+}
+
+/// Makes the current TSC value the last tick counted by the TSC-deadline timer of the current
+/// processor.
+pub(super) fn set_last_tick() {
+    last_tick().store(read(), Ordering::Relaxed);
+}
+
+/// Returns the time elapsed since the origin, or `None` if the TSC is not calibrated.
+pub(super) fn elapsed() -> Option<Duration> {
+    const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+    let hz = HZ.load(Ordering::Relaxed);
+
+    (hz != 0).then(|| {
+        let cycles = read() - ORIGIN.load(Ordering::Relaxed);
+        let nanos = u128::from(cycles) * NANOS_PER_SEC / u128::from(hz);
+
+        Duration::from_nanos(nanos.try_into().unwrap_or(u64::MAX))
+    })
+}
+
+/// Starts the TSC-deadline timer to interrupt with `vector` after one tick.
+///
+/// # Safety
+///
+/// - The TSC must be calibrated by [`calibrate`], and the Local APIC timer must support the
+///   TSC-deadline mode.
+/// - The start address of the Local APIC registers must be `0xfee0_0000` (the default one).
+pub(super) unsafe fn start_deadline_timer(vector: u8) {
+    // SAFETY: The caller must ensure that the start address of the Local APIC registers must be
+    // `0xfee0_0000`.
+    unsafe {
+        set_lvt_timer(
+            *lvt::Timer::default()
+                .set_vector(vector)
+                .set_timer_mode(TimerMode::TscDeadline),
+        );
+    }
+
+    // The write to `IA32_TSC_DEADLINE` must not be reordered before the one to the LVT timer
+    // register.
+    //
+    // SAFETY: The caller ensures that the TSC-deadline mode is supported.
+    unsafe {
+        _mm_mfence();
+
+        interrupt_after(Some(1));
+    }
+}
+
+/// Returns the number of the ticks elapsed since the last tick counted.
+pub(super) fn take_elapsed_ticks() -> u64 {
+    let per_tick = cycles_per_tick();
+    let last = last_tick().load(Ordering::Relaxed);
+
+    let elapsed = (read() - last) / per_tick;
+
+    last_tick().store(last + elapsed * per_tick, Ordering::Relaxed);
+
+    elapsed
+}
+
+/// Arms the TSC-deadline timer to interrupt `ticks` ticks after the last tick counted, or disarms
+/// it if `ticks` is `None`.
+///
+/// # Safety
+///
+/// The Local APIC timer must be in the TSC-deadline mode.
+pub(super) unsafe fn interrupt_after(ticks: Option<u64>) {
+    // Writing zero disarms the timer.
+    let deadline = ticks.map_or(0, |ticks| {
+        let after = ticks.max(1).saturating_mul(cycles_per_tick());
+
+        last_tick().load(Ordering::Relaxed).saturating_add(after)
+    });
+
+    let mut msr = IA32_TSC_DEADLINE;
+
+    // SAFETY: The caller ensures that the timer is in the TSC-deadline mode.
+    unsafe {
+        msr.write(deadline);
+    }
+}
+
+/// Arms the TSC-deadline timer to interrupt at the next tick, and returns the number of the ticks
+/// elapsed since the last tick counted.
+///
+/// # Safety
+///
+/// The Local APIC timer must be in the TSC-deadline mode.
+pub(super) unsafe fn resume_ticking() -> u64 {
+    let elapsed = take_elapsed_ticks();
+
+    // SAFETY: The caller ensures that the timer is in the TSC-deadline mode.
+    unsafe {
+        interrupt_after(Some(1));
+    }
+
+    elapsed
+}
+
+fn last_tick() -> &'static AtomicU64 {
+    &LAST_TICK[smp::index()]
+}
+
+fn cycles_per_tick() -> u64 {
+    HZ.load(Ordering::Relaxed) / FREQUENCY_HZ
+}
+
+fn read() -> u64 {
+    // SAFETY: Reading the TSC does not violate memory safety.
+    unsafe { _rdtsc() }
+}
@@ -1,24 +1,46 @@
 use {
+    crate::smp::{self, MAX_CPUS},
+    alloc::vec,
     spinning_top::{const_spinlock, Spinlock, SpinlockGuard},
     x86_64::{structures::tss::TaskStateSegment, VirtAddr},
 };
 
-static TSS: Spinlock<TaskStateSegment> = const_spinlock(TaskStateSegment::new());
+/// The index of the Interrupt Stack Table entry which holds the stack for the double fault handler.
+/// The handler has its own stack so that it runs even if the stack pointer is broken. The kernel
+/// stacks of the processes have no guard pages, so their overflows are detected by the magic numbers
+/// checked on the context switches, not by double faults.
+pub(super) const DOUBLE_FAULT_IST_INDEX: u16 = 0;
 
+const DOUBLE_FAULT_STACK_BYTES: usize = 16384;
+
+// Each processor has its own TSS because it holds the stack used when the processor enters the
+// kernel mode.
+static TSS: [Spinlock<TaskStateSegment>; MAX_CPUS] =
+    [const { const_spinlock(TaskStateSegment::new()) }; MAX_CPUS];
+
+/// Allocates the stack for the double fault handler of the current processor.
+pub(super) fn init() {
+    let stack = vec![0_u8; DOUBLE_FAULT_STACK_BYTES].leak();
+    let stack = VirtAddr::from_ptr(stack.as_ptr_range().end).align_down(16_u64);
+
+    tss().interrupt_stack_table[usize::from(DOUBLE_FAULT_IST_INDEX)] = stack;
+}
+
+/// Sets the stack used when the current processor enters the kernel mode.
 pub(super) fn set_kernel_stack_addr(a: VirtAddr) {
     tss().privilege_stack_table[0] = a;
 }
 
+/// Returns the TSS of the current processor.
+///
 /// # Safety
 ///
 /// TSS must not be modified while the returned reference is alive.
 pub(super) unsafe fn as_ref() -> &'static TaskStateSegment {
     // SAFETY: The caller must ensure that TSS is not modified while this reference is alive.
-    unsafe { &*TSS.data_ptr() }
+    unsafe { &*TSS[smp::index()].data_ptr() }
 }
 
 fn tss<'a>() -> SpinlockGuard<'a, TaskStateSegment> {
-    let t = TSS.try_lock();
-
-    t.expect("Failed to lock TSS.")
+    TSS[smp::index()].lock()
 }
@@ -12,5 +12,9 @@ pub enum Error {
     FadtWrongMajorVersion,
     FadtWrongMinorVersion,
     FadtWrongChecksum,
+    HpetWrongSignature,
+    HpetWrongChecksum,
+    MadtWrongSignature,
+    MadtWrongChecksum,
     UnsupportedAddressSpaceId(u8),
 }
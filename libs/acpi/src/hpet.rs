@@ -0,0 +1,70 @@
+use {
+    crate::{error_unless, wrapping_sum_of_first_n_bytes, Error, GenericAddressStructure, Result},
+    accessor::{single::ReadOnly, Mapper},
+    core::convert::{TryFrom, TryInto},
+    r_acpi::SYSTEM_IO_SPACE,
+    x86_64::PhysAddr,
+};
+
+/// The High Precision Event Timer Description Table.
+#[derive(Debug)]
+pub struct Hpet<M: Mapper>(ReadOnly<r_acpi::Hpet, M>);
+impl<M: Mapper> Hpet<M> {
+    /// # Safety
+    ///
+    /// `base` must be the correct address of the HPET table.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the table is broken (e.g. wrong signature, wrong checksum,
+    /// etc.).
+    #[cfg_attr(target_pointer_width = "64", allow(clippy::missing_panics_doc))]
+    pub unsafe fn new(base: PhysAddr, mapper: M) -> Result<Self> {
+        // SAFETY: The caller must ensure that `base` is the correct address of the HPET table.
+        let hpet = unsafe { ReadOnly::new(base.as_u64().try_into().unwrap(), mapper) };
+
+        Validator(hpet.read_volatile()).validate()?;
+
+        Ok(Self(hpet))
+    }
+
+    /// Returns the start address of the registers of the timer block.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the registers are not memory-mapped.
+    pub fn base_address(&self) -> Result<PhysAddr> {
+        let address = self.0.read_volatile().base_address;
+
+        match GenericAddressStructure::try_from(address)? {
+            GenericAddressStructure::SystemMemory(addr) => Ok(addr),
+            GenericAddressStructure::SystemIo(_) => {
+                Err(Error::UnsupportedAddressSpaceId(SYSTEM_IO_SPACE))
+            }
+        }
+    }
+}
+
+struct Validator(r_acpi::Hpet);
+impl Validator {
+    fn validate(self) -> Result<()> {
+        self.validate_signature()?;
+        self.validate_checksum()?;
+
+        Ok(())
+    }
+
+    fn validate_signature(&self) -> Result<()> {
+        error_unless(
+            &self.0.header.signature == b"HPET",
+            Error::HpetWrongSignature,
+        )
+    }
+
+    fn validate_checksum(&self) -> Result<()> {
+        error_unless(
+            wrapping_sum_of_first_n_bytes(&self.0, self.0.header.length.try_into().unwrap()) == 0,
+            Error::HpetWrongChecksum,
+        )
+    }
+}
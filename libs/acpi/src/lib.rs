@@ -4,7 +4,12 @@
 use {accessor::Mapper, core::mem::size_of, x86_64::PhysAddr};
 
 pub use {
-    error::Error, fadt::Fadt, generic_address_structure::GenericAddressStructure, rsdp::Rsdp,
+    error::Error,
+    fadt::Fadt,
+    generic_address_structure::GenericAddressStructure,
+    hpet::Hpet,
+    madt::{InterruptSourceOverride, IoApic, LocalApic, Madt, Polarity, TriggerMode},
+    rsdp::Rsdp,
     xsdt::Xsdt,
 };
 
@@ -13,6 +18,8 @@ pub type Result<T> = core::result::Result<T, Error>;
 mod error;
 pub mod fadt;
 mod generic_address_structure;
+mod hpet;
+mod madt;
 mod rsdp;
 pub mod xsdt;
 
@@ -21,6 +28,8 @@ pub struct Tables<M: Mapper + Clone> {
     pub rsdp: Rsdp<M>,
     pub xsdt: Xsdt<M>,
     pub fadt: Option<Fadt<M>>,
+    pub hpet: Option<Hpet<M>>,
+    pub madt: Option<Madt<M>>,
 }
 impl<M: Mapper + Clone> Tables<M> {
     /// # Safety
@@ -35,8 +44,17 @@ impl<M: Mapper + Clone> Tables<M> {
         let rsdp = unsafe { Rsdp::from_addr(a, m.clone()) }?;
         let xsdt = rsdp.xsdt(m.clone())?;
         let fadt = xsdt.fadt(m)?;
+        // The HPET is optional, so a broken HPET table is ignored instead of failing the others.
+        let hpet = xsdt.hpet(m).ok().flatten();
+        let madt = xsdt.madt(m)?;
 
-        Ok(Self { rsdp, xsdt, fadt })
+        Ok(Self {
+            rsdp,
+            xsdt,
+            fadt,
+            hpet,
+            madt,
+        })
     }
 }
 
@@ -0,0 +1,198 @@
+use {
+    crate::{error_unless, Error, Result},
+    accessor::{array, single, Mapper},
+    core::{convert::TryInto, mem::size_of},
+    r_acpi::{INTERRUPT_SOURCE_OVERRIDE, IO_APIC, LOCAL_APIC_ENABLED, PROCESSOR_LOCAL_APIC},
+    x86_64::PhysAddr,
+};
+
+/// The Multiple APIC Description Table.
+#[derive(Debug)]
+pub struct Madt<M: Mapper>(array::ReadOnly<u8, M>);
+impl<M: Mapper + Clone> Madt<M> {
+    /// # Safety
+    ///
+    /// `base` must be the correct address of MADT.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the table is broken (e.g. wrong signature, wrong checksum,
+    /// etc.).
+    #[cfg_attr(target_pointer_width = "64", allow(clippy::missing_panics_doc))]
+    pub unsafe fn new(base: PhysAddr, mapper: M) -> Result<Self> {
+        let base: usize = base.as_u64().try_into().unwrap();
+
+        // SAFETY: The caller must ensure that `base` is the correct address of MADT.
+        let header = unsafe { single::ReadOnly::<r_acpi::Madt, _>::new(base, mapper.clone()) }
+            .read_volatile();
+
+        error_unless(
+            &header.header.signature == b"APIC",
+            Error::MadtWrongSignature,
+        )?;
+
+        // SAFETY: The caller must ensure that `base` is the correct address of MADT, and the length
+        // field holds the number of bytes of the whole table.
+        let bytes =
+            unsafe { array::ReadOnly::new(base, header.header.length.try_into().unwrap(), mapper) };
+
+        let checksum =
+            (0..bytes.len()).fold(0_u8, |acc, i| acc.wrapping_add(bytes.read_volatile_at(i)));
+
+        error_unless(checksum == 0, Error::MadtWrongChecksum)?;
+
+        Ok(Self(bytes))
+    }
+}
+impl<M: Mapper> Madt<M> {
+    /// Returns the Local APICs listed in the table, including the disabled ones.
+    pub fn local_apics(&self) -> impl Iterator<Item = LocalApic> + '_ {
+        self.interrupt_controllers()
+            .filter(|(ty, len, _)| {
+                *ty == PROCESSOR_LOCAL_APIC && *len >= size_of::<r_acpi::ProcessorLocalApic>()
+            })
+            .map(|(_, _, offset)| LocalApic {
+                processor_uid: self.0.read_volatile_at(offset + 2),
+                apic_id: self.0.read_volatile_at(offset + 3),
+                enabled: self.read_u32(offset + 4) & LOCAL_APIC_ENABLED != 0,
+            })
+    }
+
+    /// Returns the I/O APICs listed in the table.
+    pub fn io_apics(&self) -> impl Iterator<Item = IoApic> + '_ {
+        self.interrupt_controllers()
+            .filter(|(ty, len, _)| *ty == IO_APIC && *len >= size_of::<r_acpi::IoApic>())
+            .map(|(_, _, offset)| IoApic {
+                id: self.0.read_volatile_at(offset + 2),
+                address: PhysAddr::new(self.read_u32(offset + 4).into()),
+                global_system_interrupt_base: self.read_u32(offset + 8),
+            })
+    }
+
+    /// Returns the Interrupt Source Overrides, which describe the ISA interrupts connected to the
+    /// I/O APICs differently from the identity mapping.
+    pub fn interrupt_source_overrides(&self) -> impl Iterator<Item = InterruptSourceOverride> + '_ {
+        self.interrupt_controllers()
+            .filter(|(ty, len, _)| {
+                *ty == INTERRUPT_SOURCE_OVERRIDE
+                    && *len >= size_of::<r_acpi::InterruptSourceOverride>()
+            })
+            .map(|(_, _, offset)| {
+                let flags = u16::from_le_bytes([
+                    self.0.read_volatile_at(offset + 8),
+                    self.0.read_volatile_at(offset + 9),
+                ]);
+
+                InterruptSourceOverride {
+                    bus: self.0.read_volatile_at(offset + 2),
+                    source: self.0.read_volatile_at(offset + 3),
+                    global_system_interrupt: self.read_u32(offset + 4),
+                    polarity: Polarity::from_flags(flags),
+                    trigger_mode: TriggerMode::from_flags(flags),
+                }
+            })
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        u32::from_le_bytes([
+            self.0.read_volatile_at(offset),
+            self.0.read_volatile_at(offset + 1),
+            self.0.read_volatile_at(offset + 2),
+            self.0.read_volatile_at(offset + 3),
+        ])
+    }
+
+    // Returns the types, the lengths, and the offsets of the Interrupt Controller Structures.
+    fn interrupt_controllers(&self) -> impl Iterator<Item = (u8, usize, usize)> + '_ {
+        let mut offset = size_of::<r_acpi::Madt>();
+
+        core::iter::from_fn(move || {
+            // Each structure starts with its type and length.
+            if offset + 2 > self.0.len() {
+                return None;
+            }
+
+            let ty = self.0.read_volatile_at(offset);
+            let len = usize::from(self.0.read_volatile_at(offset + 1));
+
+            // A broken length would make this loop infinite or read beyond the table.
+            if len < 2 || offset + len > self.0.len() {
+                return None;
+            }
+
+            let current = offset;
+
+            offset += len;
+
+            Some((ty, len, current))
+        })
+    }
+}
+
+/// A Processor Local APIC Structure.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LocalApic {
+    pub processor_uid: u8,
+    pub apic_id: u8,
+    /// `false` if the processor is not usable.
+    pub enabled: bool,
+}
+
+/// An I/O APIC Structure.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct IoApic {
+    pub id: u8,
+    /// The start address of the registers.
+    pub address: PhysAddr,
+    /// The Global System Interrupt number of the first input of this I/O APIC.
+    pub global_system_interrupt_base: u32,
+}
+
+/// An Interrupt Source Override Structure.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InterruptSourceOverride {
+    /// `0` for ISA.
+    pub bus: u8,
+    /// The IRQ number on the bus.
+    pub source: u8,
+    /// The Global System Interrupt which `source` signals.
+    pub global_system_interrupt: u32,
+    pub polarity: Polarity,
+    pub trigger_mode: TriggerMode,
+}
+
+/// The polarity of an interrupt input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Polarity {
+    /// Conforms to the specification of the bus.
+    Conforming,
+    ActiveHigh,
+    ActiveLow,
+}
+impl Polarity {
+    fn from_flags(flags: u16) -> Self {
+        match flags & 0b11 {
+            0b01 => Self::ActiveHigh,
+            0b11 => Self::ActiveLow,
+            _ => Self::Conforming,
+        }
+    }
+}
+
+/// The trigger mode of an interrupt input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TriggerMode {
+    /// Conforms to the specification of the bus.
+    Conforming,
+    Edge,
+    Level,
+}
+impl TriggerMode {
+    fn from_flags(flags: u16) -> Self {
+        match (flags >> 2) & 0b11 {
+            0b01 => Self::Edge,
+            0b11 => Self::Level,
+            _ => Self::Conforming,
+        }
+    }
+}
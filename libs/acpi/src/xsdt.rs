@@ -1,5 +1,5 @@
 use {
-    crate::{error_unless, wrapping_sum_of_bytes, Error, Fadt, Result},
+    crate::{error_unless, wrapping_sum_of_bytes, Error, Fadt, Hpet, Madt, Result},
     accessor::{single, Mapper},
     core::{
         convert::{TryFrom, TryInto},
@@ -50,7 +50,7 @@ where
                 let _ = &m;
 
                 // SAFETY: The first 4 bytes of the all system description tables are always readable.
-                unsafe { is_fadt(*a, m.clone()) }
+                unsafe { has_signature(*a, m.clone(), b"FACP") }
             })
             .find_map(|a| {
                 let _ = &m;
@@ -63,6 +63,36 @@ where
             })
             .transpose()
     }
+
+    /// # Errors
+    ///
+    /// This method returns an error if the HPET table is broken (e.g., wrong checksum, etc.).
+    pub fn hpet<M2: Mapper + Clone>(&self, m: &M2) -> Result<Option<Hpet<M2>>> {
+        self.entry
+            .into_iter()
+            .find(|a| {
+                // SAFETY: The first 4 bytes of the all system description tables are always readable.
+                unsafe { has_signature(*a, m.clone(), b"HPET") }
+            })
+            // SAFETY: `a` is the address of the HPET table.
+            .map(|a| unsafe { Hpet::new(a, m.clone()) })
+            .transpose()
+    }
+
+    /// # Errors
+    ///
+    /// This method returns an error if MADT is broken (e.g., wrong checksum, etc.).
+    pub fn madt<M2: Mapper + Clone>(&self, m: &M2) -> Result<Option<Madt<M2>>> {
+        self.entry
+            .into_iter()
+            .find(|a| {
+                // SAFETY: The first 4 bytes of the all system description tables are always readable.
+                unsafe { has_signature(*a, m.clone(), b"APIC") }
+            })
+            // SAFETY: `a` is the address of MADT.
+            .map(|a| unsafe { Madt::new(a, m.clone()) })
+            .transpose()
+    }
 }
 
 #[derive(Debug)]
@@ -215,10 +245,10 @@ fn calculate_checksum(h: &r_acpi::Xsdt, entry: impl Iterator<Item = PhysAddr>) -
 /// # Safety
 ///
 /// 4 bytes from `base` must be readable.
-unsafe fn is_fadt<M2: Mapper>(base: PhysAddr, m: M2) -> bool {
+unsafe fn has_signature<M2: Mapper>(base: PhysAddr, m: M2, expected: &[u8; 4]) -> bool {
     // SAFETY: The caller must ensure that 4 bytes from `base` are readable.
     let signature =
         unsafe { single::ReadOnly::<[u8; 4], _>::new(base.as_u64().try_into().unwrap(), m) };
 
-    &signature.read_volatile() == b"FACP"
+    &signature.read_volatile() == expected
 }
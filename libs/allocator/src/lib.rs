@@ -0,0 +1,286 @@
+//! The heap allocator for the user processes.
+//!
+//! The heap grows by moving the program break with [`posix::unistd::sbrk`], so a process using
+//! this allocator must not move the break by itself. The VM server handles `brk`, so it must not
+//! use this allocator.
+//!
+//! A failed allocation panics with the default allocation error handler because
+//! `#[alloc_error_handler]` is not available on stable Rust. The panic handler of the binary
+//! handles it.
+
+#![no_std]
+
+extern crate alloc;
+
+pub use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use {
+    conquer_once::spin::Lazy,
+    core::{
+        alloc::{GlobalAlloc, Layout},
+        ptr::{self, NonNull},
+    },
+    spinning_top::Spinlock,
+};
+
+// The heap grows at least by this size so that small allocations do not call `sbrk` every time.
+const MIN_GROWTH_BYTES: usize = 0x10000;
+const PAGE_SIZE: usize = 4096;
+
+static HEAP: Lazy<Spinlock<Heap>> = Lazy::new(|| Spinlock::new(Heap::new()));
+
+/// The allocator for the `alloc` crate.
+///
+/// A binary registers it with `#[global_allocator]`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Allocator;
+unsafe impl GlobalAlloc for Allocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        HEAP.lock().alloc(layout, posix::unistd::sbrk)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: The caller must uphold the safety requirements.
+        unsafe {
+            HEAP.lock().dealloc(ptr, layout);
+        }
+    }
+}
+
+struct Heap {
+    inner: linked_list_allocator::Heap,
+    // `false` until the heap receives the first memory from `sbrk`.
+    initialized: bool,
+}
+impl Heap {
+    fn new() -> Self {
+        Self {
+            inner: linked_list_allocator::Heap::empty(),
+            initialized: false,
+        }
+    }
+
+    // `sbrk` is called to grow the heap. It is a parameter so that the tests can use their own
+    // memory.
+    fn alloc(&mut self, layout: Layout, sbrk: impl FnOnce(isize) -> *mut u8) -> *mut u8 {
+        if let Ok(ptr) = self.inner.allocate_first_fit(layout) {
+            return ptr.as_ptr();
+        }
+
+        if self.grow(layout, sbrk) {
+            self.inner
+                .allocate_first_fit(layout)
+                .map_or(ptr::null_mut(), NonNull::as_ptr)
+        } else {
+            ptr::null_mut()
+        }
+    }
+
+    unsafe fn dealloc(&mut self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: The caller must uphold the safety requirements.
+        unsafe {
+            self.inner.deallocate(NonNull::new(ptr).unwrap(), layout);
+        }
+    }
+
+    // Grows the heap so that it has a free region for `layout`. Returns `false` if `sbrk` fails.
+    fn grow(&mut self, layout: Layout, sbrk: impl FnOnce(isize) -> *mut u8) -> bool {
+        let bytes = match layout.size().checked_add(layout.align()) {
+            Some(bytes) => linked_list_allocator::align_up(bytes.max(MIN_GROWTH_BYTES), PAGE_SIZE),
+            None => return false,
+        };
+
+        let incr = match isize::try_from(bytes) {
+            Ok(incr) => incr,
+            Err(_) => return false,
+        };
+
+        let start = sbrk(incr) as usize;
+
+        if start == usize::MAX {
+            return false;
+        }
+
+        if self.initialized {
+            // The break is moved only by this allocator.
+            assert_eq!(start, self.inner.top(), "The program break was moved.");
+
+            // SAFETY: The memory above the top of the heap is mapped by `sbrk` and not used.
+            unsafe {
+                self.inner.extend(bytes);
+            }
+        } else {
+            // SAFETY: The memory is mapped by `sbrk` and not used.
+            unsafe {
+                self.inner.init(start, bytes);
+            }
+
+            self.initialized = true;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{Heap, MIN_GROWTH_BYTES, PAGE_SIZE},
+        alloc::{vec, vec::Vec},
+        core::{alloc::Layout, ptr},
+    };
+
+    // The program break of a fake process. The heap grows in `memory`.
+    struct Break {
+        memory: Vec<u64>,
+        current: usize,
+        // The arguments of each `sbrk` call.
+        incrs: Vec<isize>,
+    }
+    impl Break {
+        fn new() -> Self {
+            Self {
+                memory: vec![0; 0x10000],
+                current: 0,
+                incrs: Vec::new(),
+            }
+        }
+
+        fn sbrk(&mut self, incr: isize) -> *mut u8 {
+            self.incrs.push(incr);
+
+            let len = self.memory.len() * 8;
+            let incr = usize::try_from(incr).unwrap();
+
+            if self.current + incr > len {
+                return usize::MAX as *mut u8;
+            }
+
+            let start = self
+                .memory
+                .as_mut_ptr()
+                .cast::<u8>()
+                .wrapping_add(self.current);
+
+            self.current += incr;
+
+            start
+        }
+
+        fn contains(&self, p: *mut u8) -> bool {
+            let start = self.memory.as_ptr() as usize;
+
+            (start..start + self.current).contains(&(p as usize))
+        }
+    }
+
+    fn layout(size: usize) -> Layout {
+        Layout::from_size_align(size, 8).unwrap()
+    }
+
+    #[test]
+    fn first_allocation_initializes_heap() {
+        let mut brk = Break::new();
+        let mut heap = Heap::new();
+
+        let p = heap.alloc(layout(8), |incr| brk.sbrk(incr));
+
+        assert!(brk.contains(p));
+        assert!(heap.initialized);
+        assert_eq!(brk.incrs, [isize::try_from(MIN_GROWTH_BYTES).unwrap()]);
+    }
+
+    #[test]
+    fn small_allocations_do_not_grow_heap() {
+        let mut brk = Break::new();
+        let mut heap = Heap::new();
+
+        for _ in 0..16 {
+            let p = heap.alloc(layout(64), |incr| brk.sbrk(incr));
+
+            assert!(brk.contains(p));
+        }
+
+        assert_eq!(brk.incrs.len(), 1);
+    }
+
+    #[test]
+    fn growth_is_rounded_up_to_pages() {
+        let mut brk = Break::new();
+        let mut heap = Heap::new();
+
+        let size = MIN_GROWTH_BYTES + 1;
+
+        let p = heap.alloc(layout(size), |incr| brk.sbrk(incr));
+
+        assert!(brk.contains(p));
+
+        // The size, the alignment, and the rounding to the page size.
+        let expected = (size + 8).div_ceil(PAGE_SIZE) * PAGE_SIZE;
+
+        assert_eq!(brk.incrs, [isize::try_from(expected).unwrap()]);
+    }
+
+    #[test]
+    fn second_growth_extends_heap() {
+        let mut brk = Break::new();
+        let mut heap = Heap::new();
+
+        let first = heap.alloc(layout(MIN_GROWTH_BYTES / 2), |incr| brk.sbrk(incr));
+        let top = heap.inner.top();
+
+        let second = heap.alloc(layout(MIN_GROWTH_BYTES), |incr| brk.sbrk(incr));
+
+        assert!(brk.contains(first));
+        assert!(brk.contains(second));
+        assert_eq!(brk.incrs.len(), 2);
+        assert_eq!(
+            heap.inner.top(),
+            top + usize::try_from(brk.incrs[1]).unwrap()
+        );
+    }
+
+    #[test]
+    fn failed_sbrk_returns_null() {
+        let mut brk = Break::new();
+        let mut heap = Heap::new();
+
+        let p = heap.alloc(layout(brk.memory.len() * 8 + 1), |incr| brk.sbrk(incr));
+
+        assert_eq!(p, ptr::null_mut());
+        assert!(!heap.initialized);
+    }
+
+    #[test]
+    fn too_large_allocation_does_not_call_sbrk() {
+        let mut heap = Heap::new();
+
+        // The largest size whose growth does not fit in `isize`.
+        let layout = Layout::from_size_align(isize::MAX as usize - 7, 8).unwrap();
+
+        let p = heap.alloc(layout, |_| panic!("`sbrk` is called."));
+
+        assert_eq!(p, ptr::null_mut());
+    }
+
+    #[test]
+    #[should_panic(expected = "The program break was moved.")]
+    fn moved_break_panics() {
+        let mut brk = Break::new();
+        let mut heap = Heap::new();
+
+        heap.alloc(layout(8), |incr| brk.sbrk(incr));
+
+        // Another user of the break.
+        brk.sbrk(8);
+
+        heap.alloc(layout(MIN_GROWTH_BYTES), |incr| brk.sbrk(incr));
+    }
+}
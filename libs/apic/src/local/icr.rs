@@ -0,0 +1,122 @@
+use {
+    bit_field::BitField, core::convert::TryInto, num_derive::FromPrimitive,
+    num_traits::FromPrimitive,
+};
+
+/// The value of the Interrupt Command Register.
+///
+/// Writing [`InterruptCommand::low`] to [`super::INTERRUPT_COMMAND_31_0`] sends the interrupt, so
+/// [`InterruptCommand::high`] must be written to [`super::INTERRUPT_COMMAND_63_32`] first.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct InterruptCommand(u64);
+impl InterruptCommand {
+    #[must_use]
+    pub fn new(low: u32, high: u32) -> Self {
+        Self(u64::from(high) << 32 | u64::from(low))
+    }
+
+    #[must_use]
+    pub fn low(self) -> u32 {
+        self.0.get_bits(0..32).try_into().unwrap()
+    }
+
+    #[must_use]
+    pub fn high(self) -> u32 {
+        self.0.get_bits(32..64).try_into().unwrap()
+    }
+
+    #[must_use]
+    pub fn vector(self) -> u8 {
+        self.0.get_bits(0..8).try_into().unwrap()
+    }
+
+    pub fn set_vector(&mut self, vector: u8) -> &mut Self {
+        self.0.set_bits(0..8, vector.into());
+        self
+    }
+
+    #[must_use]
+    pub fn delivery_mode(self) -> DeliveryMode {
+        FromPrimitive::from_u64(self.0.get_bits(8..11)).expect("Invalid delivery mode")
+    }
+
+    pub fn set_delivery_mode(&mut self, delivery_mode: DeliveryMode) -> &mut Self {
+        self.0.set_bits(8..11, delivery_mode as _);
+        self
+    }
+
+    /// Returns `true` if the interrupt has not been accepted by the destination yet.
+    #[must_use]
+    pub fn delivery_status(self) -> bool {
+        self.0.get_bit(12)
+    }
+
+    #[must_use]
+    pub fn level(self) -> bool {
+        self.0.get_bit(14)
+    }
+
+    pub fn set_level(&mut self) -> &mut Self {
+        self.0.set_bit(14, true);
+        self
+    }
+
+    pub fn clear_level(&mut self) -> &mut Self {
+        self.0.set_bit(14, false);
+        self
+    }
+
+    #[must_use]
+    pub fn trigger_mode(self) -> bool {
+        self.0.get_bit(15)
+    }
+
+    pub fn set_trigger_mode(&mut self) -> &mut Self {
+        self.0.set_bit(15, true);
+        self
+    }
+
+    pub fn clear_trigger_mode(&mut self) -> &mut Self {
+        self.0.set_bit(15, false);
+        self
+    }
+
+    #[must_use]
+    pub fn destination_shorthand(self) -> DestinationShorthand {
+        FromPrimitive::from_u64(self.0.get_bits(18..20)).expect("Invalid destination shorthand")
+    }
+
+    pub fn set_destination_shorthand(&mut self, shorthand: DestinationShorthand) -> &mut Self {
+        self.0.set_bits(18..20, shorthand as _);
+        self
+    }
+
+    /// Returns the Local APIC ID of the destination.
+    #[must_use]
+    pub fn destination(self) -> u8 {
+        self.0.get_bits(56..64).try_into().unwrap()
+    }
+
+    pub fn set_destination(&mut self, apic_id: u8) -> &mut Self {
+        self.0.set_bits(56..64, apic_id.into());
+        self
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive)]
+pub enum DeliveryMode {
+    Fixed = 0b000,
+    LowestPriority = 0b001,
+    Smi = 0b010,
+    Nmi = 0b100,
+    Init = 0b101,
+    StartUp = 0b110,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive)]
+pub enum DestinationShorthand {
+    NoShorthand = 0b00,
+    SelfOnly = 0b01,
+    AllIncludingSelf = 0b10,
+    AllExcludingSelf = 0b11,
+}
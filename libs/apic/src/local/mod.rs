@@ -1,5 +1,6 @@
 use x86_64::PhysAddr;
 
+pub mod icr;
 pub mod lvt;
 pub mod timer;
 
@@ -1,3 +1,9 @@
 #![no_std]
 
-pub const MAX_PID: usize = 16;
+/// The maximum number of processes which exist at the same time. PIDs are recycled, so the PIDs
+/// themselves may exceed this value.
+pub const MAX_PID: usize = 4096;
+
+/// The number of the priority levels of the scheduler. It must be at least 3 so that the servers,
+/// the drivers, and the applications have different priorities.
+pub const NUM_OF_PRIORITY_LEVELS: usize = 4;
@@ -59,6 +59,20 @@ impl<S: PageSize, const N: usize> FrameAllocator<S, N> {
         })
     }
 
+    /// Allocates `n` frames which end at or below `limit`.
+    pub fn alloc_below(&mut self, n: NumOfPages<S>, limit: PhysAddr) -> Option<PhysFrameRange<S>> {
+        let requested: u64 = n.as_usize().try_into().unwrap();
+
+        (0..self.0.len()).find_map(|i| {
+            let fits = self.0[i].range.start.start_address() + requested * S::SIZE <= limit;
+
+            (fits && self.0[i].is_available_for_allocating(n)).then(|| {
+                let _ = &self;
+                self.alloc_from_frames_at(i, n)
+            })
+        })
+    }
+
     fn alloc_from_frames_at(&mut self, i: usize, n: NumOfPages<S>) -> PhysFrameRange<S> {
         if self.0[i].is_splittable(n) {
             self.split_frames(i, n);
@@ -81,14 +95,20 @@ impl<S: PageSize, const N: usize> FrameAllocator<S, N> {
     fn split_frames_unchecked(&mut self, i: usize, requested: NumOfPages<S>) {
         let requested: u64 = requested.as_usize().try_into().unwrap();
 
-        let new_frames_start = self.0[i].range.start + requested;
-        let new_frames_range = PhysFrameRange {
-            start: new_frames_start,
-            end: self.0[i].range.end,
+        self.split_frames_at(i, self.0[i].range.start + requested);
+    }
+
+    // Splits the frames at `i` into `[start, at)` and `[at, end)`. Both have the same availability.
+    fn split_frames_at(&mut self, i: usize, at: PhysFrame<S>) {
+        let new_frames = FrameDescriptor {
+            range: PhysFrameRange {
+                start: at,
+                end: self.0[i].range.end,
+            },
+            available: self.0[i].available,
         };
-        let new_frames = FrameDescriptor::new_for_available(new_frames_range);
 
-        self.0[i].range.end = new_frames_start;
+        self.0[i].range.end = at;
         self.0.insert(i + 1, new_frames);
     }
 }
@@ -101,6 +121,28 @@ impl<S: PageSize, const N: usize> FrameAllocator<S, N> {
         }
     }
 
+    /// Frees only `frame`, which may be any frame of allocated frames. The other frames remain
+    /// allocated.
+    pub fn dealloc_frame(&mut self, frame: PhysFrame<S>) {
+        let i = self
+            .0
+            .iter()
+            .position(|d| !d.available && d.range.start <= frame && frame < d.range.end);
+
+        if let Some(mut i) = i {
+            if self.0[i].range.start < frame {
+                self.split_frames_at(i, frame);
+                i += 1;
+            }
+
+            if frame + 1 < self.0[i].range.end {
+                self.split_frames_at(i, frame + 1);
+            }
+
+            self.free_memory_for_frames_at(i);
+        }
+    }
+
     fn free_memory_for_frames_at(&mut self, i: usize) {
         self.0[i].available = true;
         self.merge_before_and_after_frames(i);
@@ -144,7 +186,7 @@ unsafe impl<S: PageSize, const N: usize> FrameAllocatorTrait<S> for FrameAllocat
 }
 impl<S: PageSize, const N: usize> FrameDeallocator<S> for FrameAllocator<S, N> {
     unsafe fn deallocate_frame(&mut self, frame: PhysFrame<S>) {
-        self.dealloc(frame);
+        self.dealloc_frame(frame);
     }
 }
 impl<S: PageSize, const N: usize> Default for FrameAllocator<S, N> {
@@ -327,6 +369,40 @@ mod tests {
         assert_eq!(f, allocator!(U 0 => 0x3000));
     }
 
+    #[test]
+    fn allocate_below_limit() {
+        let mut f = allocator!(
+            A 0x1000 => 0x2000,
+            U 0x2000 => 0x8000,
+            A 0x8000 => 0xc000,
+        );
+
+        let a = f.alloc_below(NumOfPages::new(2), PhysAddr::new(0xa000));
+
+        assert_eq!(a, Some(phys_frame_range!(0x8000 => 0xa000)));
+        assert_eq!(
+            f,
+            allocator!(
+                A 0x1000 => 0x2000,
+                U 0x2000 => 0x8000,
+                U 0x8000 => 0xa000,
+                A 0xa000 => 0xc000,
+            )
+        )
+    }
+
+    #[test]
+    fn fail_to_allocate_below_limit() {
+        let mut f = allocator!(
+            U 0 => 0x8000,
+            A 0x8000 => 0xc000,
+        );
+
+        let a = f.alloc_below(NumOfPages::new(2), PhysAddr::new(0x9000));
+
+        assert!(a.is_none());
+    }
+
     #[test]
     fn free_single_frames() {
         let mut f = allocator!(U 0 => 0x3000);
@@ -385,6 +461,64 @@ mod tests {
         assert_eq!(f, allocator!(A 0 => 0x10000))
     }
 
+    #[test]
+    fn free_first_frame_of_frames() {
+        let mut f = allocator!(
+            A 0 => 0x1000,
+            U 0x1000 => 0x4000,
+        );
+
+        f.dealloc_frame(frame(0x1000));
+
+        assert_eq!(
+            f,
+            allocator!(
+                A 0 => 0x2000,
+                U 0x2000 => 0x4000,
+            )
+        )
+    }
+
+    #[test]
+    fn free_middle_frame_of_frames() {
+        let mut f = allocator!(U 0 => 0x3000);
+
+        f.dealloc_frame(frame(0x1000));
+
+        assert_eq!(
+            f,
+            allocator!(
+                U 0 => 0x1000,
+                A 0x1000 => 0x2000,
+                U 0x2000 => 0x3000,
+            )
+        )
+    }
+
+    #[test]
+    fn free_all_frames_one_by_one() {
+        let mut f = allocator!(
+            A 0 => 0x1000,
+            U 0x1000 => 0x4000,
+            A 0x4000 => 0x5000,
+        );
+
+        f.dealloc_frame(frame(0x3000));
+        f.dealloc_frame(frame(0x1000));
+        f.dealloc_frame(frame(0x2000));
+
+        assert_eq!(f, allocator!(A 0 => 0x5000))
+    }
+
+    #[test]
+    fn free_available_frame() {
+        let mut f = allocator!(A 0 => 0x3000);
+
+        f.dealloc_frame(frame(0x1000));
+
+        assert_eq!(f, allocator!(A 0 => 0x3000));
+    }
+
     fn frame<S: PageSize>(start: u64) -> PhysFrame<S> {
         PhysFrame::from_start_address(PhysAddr::new(start)).unwrap()
     }
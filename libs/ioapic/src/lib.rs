@@ -0,0 +1,242 @@
+#![no_std]
+
+//! The registers of the I/O APIC.
+//!
+//! The registers are accessed indirectly. The index of a register is written to [`IOREGSEL`], and
+//! then the register is read from or written to [`IOWIN`].
+
+use {
+    bit_field::BitField, core::convert::TryInto, num_derive::FromPrimitive,
+    num_traits::FromPrimitive,
+};
+
+/// The offset of the I/O Register Select Register from the start address of the registers.
+pub const IOREGSEL: usize = 0x00;
+
+/// The offset of the I/O Window Register from the start address of the registers.
+pub const IOWIN: usize = 0x10;
+
+pub const ID: u32 = 0x00;
+pub const VERSION: u32 = 0x01;
+pub const ARBITRATION: u32 = 0x02;
+
+/// Returns the indices of the lower and the upper 32 bits of the `n`-th redirection entry.
+#[must_use]
+pub fn redirection_entry(n: u8) -> (u32, u32) {
+    let low = 0x10 + 2 * u32::from(n);
+
+    (low, low + 1)
+}
+
+/// The value of the I/O APIC Version Register.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Version(u32);
+impl Version {
+    #[must_use]
+    pub fn new(value: u32) -> Self {
+        Self(value)
+    }
+
+    #[must_use]
+    pub fn version(self) -> u8 {
+        self.0.get_bits(0..8).try_into().unwrap()
+    }
+
+    /// Returns the number of the redirection entries, which is the number of the interrupt inputs.
+    #[must_use]
+    pub fn num_of_redirection_entries(self) -> u8 {
+        // The register holds the index of the last entry.
+        let last: u8 = self.0.get_bits(16..24).try_into().unwrap();
+
+        last + 1
+    }
+}
+
+/// The value of a redirection entry.
+///
+/// The entry is masked while [`RedirectionEntry::low`] has the mask bit, so when the entry is
+/// unmasked, [`RedirectionEntry::high`] must be written first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RedirectionEntry(u64);
+impl RedirectionEntry {
+    #[must_use]
+    pub fn new(low: u32, high: u32) -> Self {
+        Self(u64::from(high) << 32 | u64::from(low))
+    }
+
+    #[must_use]
+    pub fn low(self) -> u32 {
+        self.0.get_bits(0..32).try_into().unwrap()
+    }
+
+    #[must_use]
+    pub fn high(self) -> u32 {
+        self.0.get_bits(32..64).try_into().unwrap()
+    }
+
+    #[must_use]
+    pub fn vector(self) -> u8 {
+        self.0.get_bits(0..8).try_into().unwrap()
+    }
+
+    pub fn set_vector(&mut self, vector: u8) -> &mut Self {
+        self.0.set_bits(0..8, vector.into());
+        self
+    }
+
+    /// Returns `None` if the delivery mode is one of the reserved encodings.
+    #[must_use]
+    pub fn delivery_mode(self) -> Option<DeliveryMode> {
+        FromPrimitive::from_u64(self.0.get_bits(8..11))
+    }
+
+    pub fn set_delivery_mode(&mut self, delivery_mode: DeliveryMode) -> &mut Self {
+        self.0.set_bits(8..11, delivery_mode as _);
+        self
+    }
+
+    /// Returns `true` if the destination is a set of processors, or `false` if it is the Local
+    /// APIC ID of a processor.
+    #[must_use]
+    pub fn logical_destination_mode(self) -> bool {
+        self.0.get_bit(11)
+    }
+
+    pub fn set_logical_destination_mode(&mut self) -> &mut Self {
+        self.0.set_bit(11, true);
+        self
+    }
+
+    pub fn set_physical_destination_mode(&mut self) -> &mut Self {
+        self.0.set_bit(11, false);
+        self
+    }
+
+    /// Returns `true` if the interrupt has not been delivered yet.
+    #[must_use]
+    pub fn delivery_status(self) -> bool {
+        self.0.get_bit(12)
+    }
+
+    #[must_use]
+    pub fn active_low(self) -> bool {
+        self.0.get_bit(13)
+    }
+
+    pub fn set_active_low(&mut self) -> &mut Self {
+        self.0.set_bit(13, true);
+        self
+    }
+
+    pub fn set_active_high(&mut self) -> &mut Self {
+        self.0.set_bit(13, false);
+        self
+    }
+
+    /// Returns `true` while a level-triggered interrupt is accepted by a Local APIC and the EOI is
+    /// not received yet.
+    #[must_use]
+    pub fn remote_irr(self) -> bool {
+        self.0.get_bit(14)
+    }
+
+    #[must_use]
+    pub fn level_triggered(self) -> bool {
+        self.0.get_bit(15)
+    }
+
+    pub fn set_level_triggered(&mut self) -> &mut Self {
+        self.0.set_bit(15, true);
+        self
+    }
+
+    pub fn set_edge_triggered(&mut self) -> &mut Self {
+        self.0.set_bit(15, false);
+        self
+    }
+
+    #[must_use]
+    pub fn mask(self) -> bool {
+        self.0.get_bit(16)
+    }
+
+    pub fn set_mask(&mut self) -> &mut Self {
+        self.0.set_bit(16, true);
+        self
+    }
+
+    pub fn clear_mask(&mut self) -> &mut Self {
+        self.0.set_bit(16, false);
+        self
+    }
+
+    #[must_use]
+    pub fn destination(self) -> u8 {
+        self.0.get_bits(56..64).try_into().unwrap()
+    }
+
+    pub fn set_destination(&mut self, destination: u8) -> &mut Self {
+        self.0.set_bits(56..64, destination.into());
+        self
+    }
+}
+impl Default for RedirectionEntry {
+    /// Returns a masked entry.
+    fn default() -> Self {
+        *Self(0).set_mask()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive)]
+pub enum DeliveryMode {
+    Fixed = 0b000,
+    LowestPriority = 0b001,
+    Smi = 0b010,
+    Nmi = 0b100,
+    Init = 0b101,
+    ExtInt = 0b111,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{redirection_entry, DeliveryMode, RedirectionEntry, Version};
+
+    #[test]
+    fn redirection_entry_indices() {
+        assert_eq!(redirection_entry(0), (0x10, 0x11));
+        assert_eq!(redirection_entry(23), (0x3e, 0x3f));
+    }
+
+    #[test]
+    fn build_redirection_entry() {
+        let mut entry = RedirectionEntry::default();
+
+        assert!(entry.mask());
+
+        entry
+            .set_vector(0x30)
+            .set_delivery_mode(DeliveryMode::Fixed)
+            .set_active_low()
+            .set_level_triggered()
+            .set_destination(3)
+            .clear_mask();
+
+        assert_eq!(entry.low(), 0xa030);
+        assert_eq!(entry.high(), 0x0300_0000);
+        assert_eq!(RedirectionEntry::new(entry.low(), entry.high()), entry);
+        assert_eq!(entry.delivery_mode(), Some(DeliveryMode::Fixed));
+    }
+
+    #[test]
+    fn reserved_delivery_modes() {
+        assert_eq!(RedirectionEntry::new(0b011 << 8, 0).delivery_mode(), None);
+        assert_eq!(RedirectionEntry::new(0b110 << 8, 0).delivery_mode(), None);
+    }
+
+    #[test]
+    fn number_of_redirection_entries() {
+        // QEMU's I/O APIC has 24 inputs.
+        assert_eq!(Version::new(0x0017_0020).num_of_redirection_entries(), 24);
+        assert_eq!(Version::new(0x0017_0020).version(), 0x20);
+    }
+}
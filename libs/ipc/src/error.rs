@@ -1,7 +1,117 @@
-use posix::sys::types::Pid;
+use posix_types::Pid;
+
+// The return value of an IPC system call. `0` means success. Otherwise, the upper 32 bits hold the
+// error code, and the lower 32 bits hold the payload of the error, if any.
+//
+// These values are shared by the kernel and the user processes, so do not change the existing
+// codes.
+const CODE_NO_SUCH_PROCESS: u64 = 1;
+const CODE_DEADLOCK: u64 = 2;
+const CODE_WOULD_BLOCK: u64 = 3;
+const CODE_TIMEOUT: u64 = 4;
+const CODE_INVALID_ADDRESS: u64 = 5;
+const CODE_INVALID_SYSCALL: u64 = 6;
+const CODE_OUT_OF_MEMORY: u64 = 7;
+
+const CODE_SHIFT: u32 = 32;
+const PAYLOAD_MASK: u64 = 0xffff_ffff;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Error {
     NoSuchProcess(Pid),
     Deadlock,
+    /// The operation would block because the other process is not ready.
+    WouldBlock,
+    /// No message arrived before the deadline.
+    Timeout,
+    /// The message buffer is not in the user-accessible memory of the caller.
+    InvalidAddress,
+    /// The system call number is unknown.
+    InvalidSyscall,
+    /// The kernel has no memory to queue the sender.
+    OutOfMemory,
+}
+impl Error {
+    /// Encodes `r` into the return value of an IPC system call.
+    #[must_use]
+    pub fn encode(r: Result<(), Self>) -> u64 {
+        let (code, payload) = match r {
+            Ok(()) => return 0,
+            #[allow(clippy::cast_sign_loss)]
+            Err(Self::NoSuchProcess(pid)) => (CODE_NO_SUCH_PROCESS, u64::from(pid as u32)),
+            Err(Self::Deadlock) => (CODE_DEADLOCK, 0),
+            Err(Self::WouldBlock) => (CODE_WOULD_BLOCK, 0),
+            Err(Self::Timeout) => (CODE_TIMEOUT, 0),
+            Err(Self::InvalidAddress) => (CODE_INVALID_ADDRESS, 0),
+            Err(Self::InvalidSyscall) => (CODE_INVALID_SYSCALL, 0),
+            Err(Self::OutOfMemory) => (CODE_OUT_OF_MEMORY, 0),
+        };
+
+        code << CODE_SHIFT | payload
+    }
+
+    /// Decodes the return value of an IPC system call.
+    ///
+    /// # Errors
+    ///
+    /// This method returns the error encoded in `v`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `v` is not a value returned by [`Error::encode`].
+    pub fn decode(v: u64) -> Result<(), Self> {
+        let code = v >> CODE_SHIFT;
+        let payload = v & PAYLOAD_MASK;
+
+        match code {
+            0 if payload == 0 => Ok(()),
+            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            CODE_NO_SUCH_PROCESS => Err(Self::NoSuchProcess(payload as u32 as Pid)),
+            CODE_DEADLOCK => Err(Self::Deadlock),
+            CODE_WOULD_BLOCK => Err(Self::WouldBlock),
+            CODE_TIMEOUT => Err(Self::Timeout),
+            CODE_INVALID_ADDRESS => Err(Self::InvalidAddress),
+            CODE_INVALID_SYSCALL => Err(Self::InvalidSyscall),
+            CODE_OUT_OF_MEMORY => Err(Self::OutOfMemory),
+            _ => panic!("Invalid IPC return value: {:#x}", v),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, CODE_SHIFT};
+
+    #[test]
+    fn every_variant_round_trips() {
+        let errors = [
+            Error::NoSuchProcess(0),
+            Error::NoSuchProcess(42),
+            Error::NoSuchProcess(i32::MAX),
+            Error::Deadlock,
+            Error::WouldBlock,
+            Error::Timeout,
+            Error::InvalidAddress,
+            Error::InvalidSyscall,
+            Error::OutOfMemory,
+        ];
+
+        for e in errors {
+            assert_eq!(Error::decode(Error::encode(Err(e))), Err(e));
+        }
+
+        assert_eq!(Error::decode(Error::encode(Ok(()))), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid IPC return value")]
+    fn unknown_code_is_rejected() {
+        let _ = Error::decode(8 << CODE_SHIFT);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid IPC return value")]
+    fn success_with_payload_is_rejected() {
+        let _ = Error::decode(1);
+    }
 }
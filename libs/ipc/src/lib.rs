@@ -7,5 +7,8 @@ pub mod syscalls;
 pub use {
     error::Error,
     message::Message,
-    syscalls::{receive, send, try_receive, try_send, ReceiveFrom},
+    syscalls::{
+        notify, receive, receive_nonblock, receive_timeout, send, send_nonblock, send_receive,
+        try_notify, try_receive, try_send, try_send_receive, ReceiveFrom,
+    },
 };
@@ -1,16 +1,80 @@
-use pid::Pid;
+use pid::{predefined, Pid};
 
-#[repr(C)]
+const _: () = assert!(
+    core::mem::size_of::<Message>() <= core::mem::align_of::<Message>(),
+    "A message may cross a page boundary."
+);
+
+/// A message passed between processes.
+///
+/// The kernel writes a received message through the physical frame of the buffer, so the buffer
+/// must not cross a page boundary. The alignment, which is not less than the size, ensures it.
+#[repr(C, align(64))]
 #[derive(Copy, Clone, Debug, Default, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub struct Message {
     pub header: Header,
     pub body: Body,
 }
+impl Message {
+    /// Creates a notification message from `sender_pid`.
+    #[must_use]
+    pub fn notification(sender_pid: Pid) -> Self {
+        Self {
+            header: Header {
+                sender_pid,
+                is_notification: true,
+            },
+            body: Body::default(),
+        }
+    }
+
+    /// Creates a notification message of hardware interrupts from [`predefined::HARDWARE`]. The
+    /// bit `v % 64` of `vectors[v / 64]` is set if an interrupt with the vector `v` has occurred.
+    #[must_use]
+    pub fn hardware_notification(vectors: [u64; 4]) -> Self {
+        Self {
+            header: Header {
+                sender_pid: predefined::HARDWARE,
+                is_notification: true,
+            },
+            body: Body(0, vectors[0], vectors[1], vectors[2], vectors[3]),
+        }
+    }
+
+    #[must_use]
+    pub fn is_notification(&self) -> bool {
+        self.header.is_notification
+    }
+
+    /// Returns `true` if this is a notification of hardware interrupts and an interrupt with
+    /// `vector` has occurred.
+    #[must_use]
+    pub fn has_interrupt(&self, vector: u8) -> bool {
+        let fields = [self.body.1, self.body.2, self.body.3, self.body.4];
+
+        self.is_notification()
+            && self.header.sender_pid == predefined::HARDWARE
+            && fields[usize::from(vector / 64)] & (1 << (vector % 64)) != 0
+    }
+}
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Default, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub struct Header {
     pub sender_pid: Pid,
+    /// `true` if this is a notification. Only the kernel sets it, so a process cannot disguise a
+    /// message as a notification.
+    pub is_notification: bool,
+}
+impl Header {
+    /// Creates the header of a message which is not a notification.
+    #[must_use]
+    pub fn new(sender_pid: Pid) -> Self {
+        Self {
+            sender_pid,
+            is_notification: false,
+        }
+    }
 }
 
 #[repr(C)]
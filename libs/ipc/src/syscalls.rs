@@ -1,9 +1,9 @@
 use {
-    super::Message,
+    super::{Error, Message},
     core::{arch::asm, convert::TryInto, mem::MaybeUninit},
     num_derive::FromPrimitive,
     pid::Pid,
-    posix::sys::types::Pid as PosixPid,
+    posix_types::Pid as PosixPid,
 };
 
 /// # Panics
@@ -20,11 +20,9 @@ pub fn send(to: Pid, message: Message) {
 pub fn try_send(to: Pid, message: Message) -> Result<(), Error> {
     let message: *const _ = &message;
 
-    if execute_syscall(Ty::Send, to.as_usize().try_into().unwrap(), message as _) == 0 {
-        Ok(())
-    } else {
-        Err(Error)
-    }
+    let r = execute_syscall(Ty::Send, to.as_usize().try_into().unwrap(), message as _, 0);
+
+    Error::decode(r)
 }
 
 /// # Panics
@@ -50,11 +48,138 @@ pub fn try_receive(from: ReceiveFrom) -> Result<Message, Error> {
     // negative PID is valid here because it means the sender's PID is unspecified. Also, the
     // sign information will not be lost as the kernel casts it to `i32` again.
     #[allow(clippy::cast_sign_loss)]
-    if execute_syscall(Ty::Receive, from as _, m.as_mut_ptr() as _) == 0 {
-        Ok(unsafe { m.assume_init() })
-    } else {
-        Err(Error)
-    }
+    let r = execute_syscall(Ty::Receive, from as _, m.as_mut_ptr() as _, 0);
+
+    Error::decode(r)?;
+
+    // SAFETY: The kernel wrote a message to `m`.
+    Ok(unsafe { m.assume_init() })
+}
+
+/// Sends `message` to the process with PID `to` only if the process is waiting for a message from
+/// the caller. Unlike [`try_send`], this function never blocks.
+///
+/// # Errors
+///
+/// This function returns [`Error::WouldBlock`] if the process with PID `to` is not waiting for a
+/// message from the caller, and [`Error::NoSuchProcess`] if there is no such process.
+#[cfg_attr(target_pointer_width = "64", allow(clippy::missing_panics_doc))]
+pub fn send_nonblock(to: Pid, message: Message) -> Result<(), Error> {
+    let message: *const _ = &message;
+
+    let r = execute_syscall(
+        Ty::SendNonBlock,
+        to.as_usize().try_into().unwrap(),
+        message as _,
+        0,
+    );
+
+    Error::decode(r)
+}
+
+/// Receives a message only if there is a pending notification or a process sending a message to
+/// the caller. Unlike [`try_receive`], this function never blocks.
+///
+/// # Errors
+///
+/// This function returns [`Error::WouldBlock`] if there is no message to receive, and
+/// [`Error::NoSuchProcess`] if there is no process with PID `from` specifies.
+pub fn receive_nonblock(from: ReceiveFrom) -> Result<Message, Error> {
+    let mut m = MaybeUninit::uninit();
+
+    let from = match from {
+        ReceiveFrom::Any => -1,
+        ReceiveFrom::Pid(pid) => PosixPid::from(pid),
+    };
+
+    // See `try_receive` for the reason why the sign loss is allowed.
+    #[allow(clippy::cast_sign_loss)]
+    let r = execute_syscall(Ty::ReceiveNonBlock, from as _, m.as_mut_ptr() as _, 0);
+
+    Error::decode(r)?;
+
+    // SAFETY: The kernel wrote a message to `m`.
+    Ok(unsafe { m.assume_init() })
+}
+
+/// Receives a message like [`try_receive`], but gives up if no message arrives within `ticks`
+/// ticks of the Local APIC timer.
+///
+/// # Errors
+///
+/// This function returns [`Error::Timeout`] if no message arrives before the deadline, and
+/// [`Error::NoSuchProcess`] if there is no process with PID `from` specifies.
+pub fn receive_timeout(from: ReceiveFrom, ticks: u64) -> Result<Message, Error> {
+    let mut m = MaybeUninit::uninit();
+
+    let from = match from {
+        ReceiveFrom::Any => -1,
+        ReceiveFrom::Pid(pid) => PosixPid::from(pid),
+    };
+
+    // See `try_receive` for the reason why the sign loss is allowed.
+    #[allow(clippy::cast_sign_loss)]
+    let r = execute_syscall(Ty::ReceiveTimeout, from as _, m.as_mut_ptr() as _, ticks);
+
+    Error::decode(r)?;
+
+    // SAFETY: The kernel wrote a message to `m`.
+    Ok(unsafe { m.assume_init() })
+}
+
+/// Sends `message` to the process with PID `to`, then waits for the reply from the same process.
+///
+/// Unlike calling [`send`] and [`receive`] in sequence, the kernel does this in a single system
+/// call, so the receiver cannot reply before the caller starts receiving.
+///
+/// # Panics
+///
+/// This function panics if there is no process with PID `to`.
+#[must_use]
+pub fn send_receive(to: Pid, message: Message) -> Message {
+    try_send_receive(to, message).expect("Failed to send and receive a message.")
+}
+
+/// # Errors
+///
+/// This function returns an error if there is no process with PID `to`.
+#[cfg_attr(target_pointer_width = "64", allow(clippy::missing_panics_doc))]
+pub fn try_send_receive(to: Pid, message: Message) -> Result<Message, Error> {
+    // The kernel reads the message from this buffer, and writes the reply to the same buffer.
+    let mut buffer = message;
+    let p: *mut _ = &mut buffer;
+
+    let r = execute_syscall(
+        Ty::SendReceive,
+        to.as_usize().try_into().unwrap(),
+        p as _,
+        0,
+    );
+
+    Error::decode(r)?;
+
+    Ok(buffer)
+}
+
+/// Sends a notification to the process with PID `to` without blocking.
+///
+/// Notifications from the same sender are coalesced until the receiver receives one of them.
+///
+/// # Panics
+///
+/// This function panics if there is no process with PID `to`.
+pub fn notify(to: Pid) {
+    try_notify(to).expect("Failed to send a notification.");
+}
+
+/// # Errors
+///
+/// This function returns an error if there is no process with PID `to`.
+#[cfg_attr(target_pointer_width = "64", allow(clippy::missing_panics_doc))]
+pub fn try_notify(to: Pid) -> Result<(), Error> {
+    let r = execute_syscall(Ty::Notify, to.as_usize().try_into().unwrap(), 0, 0);
+
+    Error::decode(r)
 }
 
 #[repr(u64)]
@@ -62,6 +187,11 @@ pub fn try_receive(from: ReceiveFrom) -> Result<Message, Error> {
 pub enum Ty {
     Send,
     Receive,
+    Notify,
+    SendReceive,
+    SendNonBlock,
+    ReceiveNonBlock,
+    ReceiveTimeout,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -75,10 +205,7 @@ impl From<Pid> for ReceiveFrom {
     }
 }
 
-#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct Error;
-
-fn execute_syscall(index: Ty, a1: u64, a2: u64) -> u64 {
+fn execute_syscall(index: Ty, a1: u64, a2: u64, a3: u64) -> u64 {
     let r: u64;
 
     unsafe {
@@ -86,11 +213,11 @@ fn execute_syscall(index: Ty, a1: u64, a2: u64) -> u64 {
         inout("rdi") index as u64 => _,
         inout("rsi") a1 => _,
         inout("rdx") a2 => _,
+        inout("r10") a3 => _,
         out("rax") r,
         out("rcx") _,
         out("r8") _,
         out("r9") _,
-        out("r10") _,
         out("r11") _,
         out("xmm0") _,
         out("xmm1") _,
@@ -7,23 +7,65 @@ use {
         convert::{TryFrom, TryInto},
         fmt,
     },
-    posix::sys::types::Pid as PosixPid,
+    posix_types::Pid as PosixPid,
 };
 
+// PIDs are recycled. The lower bits of a PID are the index of the slot of the process, and the upper
+// bits are the generation of the slot, which is incremented when the slot is reused. This prevents
+// a stale PID from addressing a new process in the same slot.
+const INDEX_BITS: u32 = 16;
+// The generation is limited so that PIDs are positive as `PosixPid`.
+const GENERATION_BITS: u32 = 15;
+
 // We use `usize` because it is more valuable than `PosixPid`. After all, we can use it as an index
 // of an array.
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Pid(usize);
 impl Pid {
+    /// The number of the slots which PIDs can address.
+    pub const NUM_OF_INDICES: usize = 1 << INDEX_BITS;
+
     #[must_use]
     pub const fn new(pid: usize) -> Self {
         Self(pid)
     }
 
+    /// Returns the PID of the slot `index` in the generation `generation`. The generation wraps
+    /// around.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `index` is not less than [`Pid::NUM_OF_INDICES`].
+    #[must_use]
+    pub const fn from_index_and_generation(index: usize, generation: usize) -> Self {
+        assert!(index < Self::NUM_OF_INDICES, "Too large index.");
+
+        let generation = generation & ((1 << GENERATION_BITS) - 1);
+
+        Self(generation << INDEX_BITS | index)
+    }
+
     #[must_use]
     pub const fn as_usize(self) -> usize {
         self.0
     }
+
+    /// Returns the index of the slot of the process.
+    #[must_use]
+    pub const fn index(self) -> usize {
+        self.0 & (Self::NUM_OF_INDICES - 1)
+    }
+
+    #[must_use]
+    pub const fn generation(self) -> usize {
+        self.0 >> INDEX_BITS
+    }
+
+    /// Returns the PID of the next process in the same slot.
+    #[must_use]
+    pub const fn next_generation(self) -> Self {
+        Self::from_index_and_generation(self.index(), self.generation() + 1)
+    }
 }
 impl From<Pid> for usize {
     fn from(pid: Pid) -> Self {
@@ -57,3 +99,35 @@ impl fmt::Display for Pid {
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct NegativePid(PosixPid);
+
+#[cfg(test)]
+mod tests {
+    use super::Pid;
+
+    #[test]
+    fn predefined_pids_are_first_generation() {
+        let pid = super::predefined::TEST_USER_APP;
+
+        assert_eq!(pid.index(), pid.as_usize());
+        assert_eq!(pid.generation(), 0);
+    }
+
+    #[test]
+    fn next_generation_keeps_index() {
+        let pid = Pid::from_index_and_generation(42, 3);
+        let next = pid.next_generation();
+
+        assert_ne!(pid, next);
+        assert_eq!(next.index(), 42);
+        assert_eq!(next.generation(), 4);
+    }
+
+    #[test]
+    fn generation_wraps_around() {
+        let pid = Pid::from_index_and_generation(42, (1 << super::GENERATION_BITS) - 1);
+        let next = pid.next_generation();
+
+        assert_eq!(next, Pid::new(42));
+        assert!(i32::try_from(pid.as_usize()).is_ok());
+    }
+}
@@ -11,3 +11,6 @@ pub const XHCI: Pid = Pid::new(7);
 pub const TEST_1: Pid = Pid::new(8);
 pub const TEST_2: Pid = Pid::new(9);
 pub const TEST_USER_APP: Pid = Pid::new(10);
+
+/// The sender of the notifications of hardware interrupts. No process has this PID.
+pub const HARDWARE: Pid = Pid::new(Pid::NUM_OF_INDICES - 1);
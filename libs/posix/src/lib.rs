@@ -1,3 +1,6 @@
 #![no_std]
 
+pub mod signal;
 pub mod sys;
+pub mod time;
+pub mod unistd;
@@ -0,0 +1,14 @@
+/// Illegal instruction.
+pub const SIGILL: i32 = 4;
+
+/// Trace or breakpoint trap.
+pub const SIGTRAP: i32 = 5;
+
+/// Access to an undefined portion of a memory object.
+pub const SIGBUS: i32 = 7;
+
+/// Erroneous arithmetic operation.
+pub const SIGFPE: i32 = 8;
+
+/// Invalid memory reference.
+pub const SIGSEGV: i32 = 11;
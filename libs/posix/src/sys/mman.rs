@@ -0,0 +1,74 @@
+use {
+    crate::sys::types::Off, core::convert::TryFrom, os_units::Bytes, syscalls::Protection,
+    x86_64::VirtAddr,
+};
+
+pub const PROT_NONE: i32 = 0;
+pub const PROT_READ: i32 = 1;
+pub const PROT_WRITE: i32 = 2;
+pub const PROT_EXEC: i32 = 4;
+
+pub const MAP_SHARED: i32 = 0x01;
+pub const MAP_PRIVATE: i32 = 0x02;
+pub const MAP_FIXED: i32 = 0x10;
+pub const MAP_ANONYMOUS: i32 = 0x20;
+
+/// Returned by [`mmap`] on failure.
+pub const MAP_FAILED: *mut u8 = usize::MAX as *mut u8;
+
+/// Maps `len` bytes of zeroed memory with `prot`, and returns its start address.
+///
+/// Only the private anonymous mappings are supported, so `flags` must be `MAP_PRIVATE |
+/// MAP_ANONYMOUS` and `fildes` must be `-1`. `addr` is only a hint, and it is ignored. The memory is
+/// always readable, so `prot` must contain [`PROT_READ`].
+///
+/// This function returns [`MAP_FAILED`] on failure.
+pub fn mmap(_addr: *mut u8, len: usize, prot: i32, flags: i32, fildes: i32, _off: Off) -> *mut u8 {
+    let protection = match to_protection(prot) {
+        Some(protection) => protection,
+        None => return MAP_FAILED,
+    };
+
+    if flags != MAP_PRIVATE | MAP_ANONYMOUS || fildes != -1 {
+        return MAP_FAILED;
+    }
+
+    syscalls::mmap(Bytes::new(len), protection).map_or(MAP_FAILED, VirtAddr::as_mut_ptr)
+}
+
+/// Unmaps the memory mapped by [`mmap`] in `[addr, addr + len)`. `addr` must be page-aligned.
+///
+/// This function returns `0` on success, and `-1` on failure.
+pub fn munmap(addr: *mut u8, len: usize) -> i32 {
+    to_result(to_addr(addr).and_then(|addr| syscalls::munmap(addr, Bytes::new(len)).ok()))
+}
+
+/// Changes the protection of the memory mapped by [`mmap`] in `[addr, addr + len)` to `prot`.
+/// `addr` must be page-aligned, and `prot` must contain [`PROT_READ`].
+///
+/// This function returns `0` on success, and `-1` on failure.
+pub fn mprotect(addr: *mut u8, len: usize, prot: i32) -> i32 {
+    to_result(
+        to_addr(addr)
+            .zip(to_protection(prot))
+            .and_then(|(addr, protection)| {
+                syscalls::mprotect(addr, Bytes::new(len), protection).ok()
+            }),
+    )
+}
+
+fn to_protection(prot: i32) -> Option<Protection> {
+    u64::try_from(prot).ok().and_then(Protection::from_bits)
+}
+
+fn to_addr(addr: *mut u8) -> Option<VirtAddr> {
+    VirtAddr::try_new(addr as u64).ok()
+}
+
+fn to_result(r: Option<()>) -> i32 {
+    if r.is_some() {
+        0
+    } else {
+        -1
+    }
+}
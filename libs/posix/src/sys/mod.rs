@@ -1 +1,3 @@
+pub mod mman;
 pub mod types;
+pub mod wait;
@@ -1 +1,5 @@
-pub type Pid = i32;
+pub use posix_types::Pid;
+
+pub type ClockId = i32;
+pub type Off = i64;
+pub type Time = i64;
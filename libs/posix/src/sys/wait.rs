@@ -0,0 +1,85 @@
+use {
+    crate::{
+        signal::{SIGBUS, SIGFPE, SIGILL, SIGSEGV, SIGTRAP},
+        sys::types::Pid,
+    },
+    core::convert::TryInto,
+    syscalls::{FaultKind, Termination},
+};
+
+/// Do not wait if no child process has terminated yet.
+pub const WNOHANG: i32 = 1;
+
+/// Waits for any child process to terminate. See [`waitpid`].
+pub fn wait(stat_loc: Option<&mut i32>) -> Pid {
+    waitpid(-1, stat_loc, 0)
+}
+
+/// Waits for the child process `pid`, or any child process if `pid` is `-1`, to terminate, and
+/// returns its PID. The status of the child process is stored in `stat_loc`, and can be inspected
+/// by [`wifexited`], [`wexitstatus`], [`wifsignaled`], and [`wtermsig`]. A child process terminated
+/// by a fault is reported as terminated by the corresponding signal.
+///
+/// If `options` contains [`WNOHANG`] and no such child process has terminated yet, this function
+/// returns `0`. It returns `-1` if the calling process has no such child process or `pid` is not
+/// supported. Process groups are not supported.
+pub fn waitpid(pid: Pid, stat_loc: Option<&mut i32>, options: i32) -> Pid {
+    let pid = match pid {
+        -1 => None,
+        pid if pid > 0 => Some(pid.try_into().unwrap()),
+        _ => return -1,
+    };
+
+    match syscalls::wait(pid, options & WNOHANG != 0) {
+        Ok(Some((child, termination))) => {
+            if let Some(stat_loc) = stat_loc {
+                *stat_loc = match termination {
+                    // Only the lowest 8 bits of the exit status are available.
+                    Termination::Exited(status) => (status & 0xff) << 8,
+                    Termination::Faulted(kind) => signal_of(kind),
+                };
+            }
+
+            child.into()
+        }
+        Ok(None) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Returns `true` if the child process terminated normally.
+#[must_use]
+pub fn wifexited(stat_val: i32) -> bool {
+    stat_val & 0x7f == 0
+}
+
+/// Returns the exit status of the child process which terminated normally.
+#[must_use]
+pub fn wexitstatus(stat_val: i32) -> i32 {
+    (stat_val >> 8) & 0xff
+}
+
+/// Returns `true` if the child process was terminated by a signal.
+#[must_use]
+pub fn wifsignaled(stat_val: i32) -> bool {
+    !wifexited(stat_val) && stat_val & 0x7f != 0x7f
+}
+
+/// Returns the number of the signal which terminated the child process.
+#[must_use]
+pub fn wtermsig(stat_val: i32) -> i32 {
+    stat_val & 0x7f
+}
+
+fn signal_of(kind: FaultKind) -> i32 {
+    match kind {
+        FaultKind::InvalidOpcode => SIGILL,
+        FaultKind::Debug | FaultKind::Breakpoint => SIGTRAP,
+        FaultKind::DivideError
+        | FaultKind::Overflow
+        | FaultKind::X87FloatingPoint
+        | FaultKind::SimdFloatingPoint => SIGFPE,
+        FaultKind::AlignmentCheck => SIGBUS,
+        _ => SIGSEGV,
+    }
+}
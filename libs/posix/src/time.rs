@@ -0,0 +1,68 @@
+use {
+    crate::sys::types::{ClockId, Time},
+    core::{convert::TryInto, time::Duration},
+};
+
+/// The clock which measures the time elapsed since the boot. It never goes backward.
+pub const CLOCK_MONOTONIC: ClockId = 1;
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timespec {
+    pub tv_sec: Time,
+    pub tv_nsec: i64,
+}
+impl Timespec {
+    // Returns `None` if `tv_sec` is negative or `tv_nsec` is out of `[0, 1_000_000_000)`.
+    fn to_duration(self) -> Option<Duration> {
+        let secs = self.tv_sec.try_into().ok()?;
+        let nanos = (0..NANOS_PER_SEC)
+            .contains(&self.tv_nsec)
+            .then_some(self.tv_nsec)?;
+
+        Some(Duration::new(secs, nanos.try_into().ok()?))
+    }
+}
+impl From<Duration> for Timespec {
+    fn from(d: Duration) -> Self {
+        Self {
+            tv_sec: d.as_secs().try_into().unwrap_or(Time::MAX),
+            tv_nsec: d.subsec_nanos().into(),
+        }
+    }
+}
+
+/// Stores the current time of the clock `clock_id` in `tp`. Only [`CLOCK_MONOTONIC`] is
+/// supported.
+///
+/// This function returns `0` on success, and `-1` if the clock is not supported.
+pub fn clock_gettime(clock_id: ClockId, tp: &mut Timespec) -> i32 {
+    if clock_id != CLOCK_MONOTONIC {
+        return -1;
+    }
+
+    *tp = syscalls::monotonic_time().into();
+
+    0
+}
+
+/// Suspends the calling process for at least `rqtp`.
+///
+/// The sleep is never interrupted, so the remaining time stored in `rmtp`, if any, is always zero.
+/// This function returns `0` on success, and `-1` if `rqtp` is not a valid duration.
+pub fn nanosleep(rqtp: &Timespec, rmtp: Option<&mut Timespec>) -> i32 {
+    let duration = match rqtp.to_duration() {
+        Some(d) => d,
+        None => return -1,
+    };
+
+    syscalls::sleep(duration);
+
+    if let Some(rmtp) = rmtp {
+        *rmtp = Timespec::default();
+    }
+
+    0
+}
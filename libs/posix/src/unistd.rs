@@ -0,0 +1,96 @@
+use {
+    crate::sys::types::Pid,
+    core::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    },
+    x86_64::VirtAddr,
+};
+
+// The current program break, or `0` if it is not queried yet. The kernel tracks the break only in
+// pages, so the exact address is kept here.
+static BREAK: AtomicUsize = AtomicUsize::new(0);
+
+// Returned by `sbrk` on failure.
+const SBRK_FAILED: *mut u8 = usize::MAX as *mut u8;
+
+/// Creates a copy of the calling process. The pages of the two processes are shared until either of
+/// them writes to them.
+///
+/// This function returns the PID of the child process to the parent, and `0` to the child. It
+/// returns `-1` if PM failed to create the child process.
+pub fn fork() -> Pid {
+    match syscalls::fork() {
+        Ok(Some(child)) => child.into(),
+        Ok(None) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Replaces the image of the calling process with the executable file `path` in the initrd. The new
+/// image receives `argv` and `envp`.
+///
+/// This function returns `-1` only if it fails.
+pub fn execve(path: &str, argv: &[&str], envp: &[&str]) -> i32 {
+    let _ = syscalls::exec(path, argv, envp);
+
+    -1
+}
+
+/// Suspends the calling process for `seconds` seconds.
+///
+/// The sleep is never interrupted, so this function always returns `0`, the number of the unslept
+/// seconds.
+pub fn sleep(seconds: u32) -> u32 {
+    syscalls::sleep(Duration::from_secs(seconds.into()));
+
+    0
+}
+
+/// Sets the program break, which is the end of the heap, to `addr`. The memory between the start of
+/// the heap and the break is zeroed when it is accessed for the first time.
+///
+/// This function returns `0` on success, and `-1` on failure.
+pub fn brk(addr: *mut u8) -> i32 {
+    let end = match VirtAddr::try_new(addr as u64) {
+        // `syscalls::brk` does not move the break to the null address, which is never in the heap.
+        Ok(end) if !end.is_null() => end,
+        _ => return -1,
+    };
+
+    if syscalls::brk(Some(end)).is_ok() {
+        BREAK.store(addr as usize, Ordering::Relaxed);
+
+        0
+    } else {
+        -1
+    }
+}
+
+/// Moves the program break by `incr` bytes, and returns the previous break. See [`brk`].
+///
+/// This function returns `(void *)-1` on failure.
+pub fn sbrk(incr: isize) -> *mut u8 {
+    let current = match current_break() {
+        Some(current) => current,
+        None => return SBRK_FAILED,
+    };
+
+    match current.checked_add_signed(incr) {
+        Some(new) if brk(new as *mut u8) == 0 => current as *mut u8,
+        _ => SBRK_FAILED,
+    }
+}
+
+fn current_break() -> Option<usize> {
+    match BREAK.load(Ordering::Relaxed) {
+        0 => {
+            let current = syscalls::brk(None).ok()?.as_u64() as usize;
+
+            BREAK.store(current, Ordering::Relaxed);
+
+            Some(current)
+        }
+        current => Some(current),
+    }
+}
@@ -0,0 +1,6 @@
+#![no_std]
+
+// The types in `sys/types.h`. They are defined in this crate instead of `posix` because `posix`
+// depends on the crates which use them.
+
+pub type Pid = i32;
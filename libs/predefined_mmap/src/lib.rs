@@ -23,6 +23,51 @@ pub fn user() -> PageRange {
     PageRange { start, end }
 }
 
+/// The region where the anonymous memory mappings of user processes are placed. The heap, which
+/// starts from the end of the executable file, grows up to the start of this region.
+#[must_use]
+#[allow(clippy::missing_panics_doc)]
+pub fn user_mmap() -> PageRange {
+    let start = VirtAddr::new(0x6000_0000_0000);
+    let end = VirtAddr::new(0x7000_0000_0000);
+
+    let start = Page::from_start_address(start).unwrap();
+
+    let end = Page::from_start_address(end).unwrap();
+
+    PageRange { start, end }
+}
+
+/// The region where the pages mapped by `vm::map_user` are placed. It is apart from the executable
+/// file and the heap, which start from the bottom of [`user`], and [`user_mmap`].
+#[must_use]
+#[allow(clippy::missing_panics_doc)]
+pub fn user_dma() -> PageRange {
+    let start = VirtAddr::new(0x7000_0000_0000);
+
+    let start = Page::from_start_address(start).unwrap();
+
+    PageRange {
+        start,
+        end: start + 0x10000,
+    }
+}
+
+/// The region where the user stack is placed. The stack starts from the end of this region, and
+/// grows down to its start.
+#[must_use]
+#[allow(clippy::missing_panics_doc)]
+pub fn user_stack() -> PageRange {
+    let end = VirtAddr::new(0x7fff_ffff_f000);
+
+    let end = Page::from_start_address(end).unwrap();
+
+    PageRange {
+        start: end - 2048,
+        end,
+    }
+}
+
 #[must_use]
 #[allow(clippy::missing_panics_doc)]
 pub fn kernel() -> PageRange {
@@ -49,9 +94,13 @@ pub fn kernel_dma() -> PageRange {
     next_to(stack(), NumOfPages::new(64))
 }
 
+/// The number of the pages of the kernel heap. The heap must hold the kernel stacks and the process
+/// table entries of `config::MAX_PID` processes.
+pub const NUM_OF_HEAP_PAGES: usize = 0x5000;
+
 #[must_use]
 pub fn heap() -> PageRange {
-    next_to(kernel_dma(), NumOfPages::new(64))
+    next_to(kernel_dma(), NumOfPages::new(NUM_OF_HEAP_PAGES))
 }
 
 #[must_use]
@@ -0,0 +1,50 @@
+#![no_std]
+
+//! The scheduling priorities of the processes. This crate is apart from `scheduler` so that the
+//! user processes can use the priorities without the heap that the scheduler uses.
+
+use config::NUM_OF_PRIORITY_LEVELS;
+
+const _: () = assert!(NUM_OF_PRIORITY_LEVELS >= 3, "Too few priority levels.");
+
+/// The priority of a process. The smaller value means the higher priority.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Priority(usize);
+impl Priority {
+    pub const SERVER: Self = Self(0);
+    pub const DRIVER: Self = Self(1);
+    pub const APP: Self = Self(2);
+    pub const LOWEST: Self = Self(NUM_OF_PRIORITY_LEVELS - 1);
+
+    /// Returns `None` if `level` is not less than [`NUM_OF_PRIORITY_LEVELS`].
+    #[must_use]
+    pub fn new(level: usize) -> Option<Self> {
+        (level < NUM_OF_PRIORITY_LEVELS).then_some(Self(level))
+    }
+
+    #[must_use]
+    pub fn as_usize(self) -> usize {
+        self.0
+    }
+
+    /// Returns the length of the time slice in the timer ticks. The lower priority has the longer
+    /// time slice so that the CPU-bound processes are switched less frequently.
+    #[must_use]
+    pub fn quantum(self) -> u64 {
+        1 << self.0
+    }
+
+    /// Returns the priority one level lower than this one, or [`Priority::LOWEST`] if this is the
+    /// lowest.
+    #[must_use]
+    pub fn lower(self) -> Self {
+        Self((self.0 + 1).min(Self::LOWEST.0))
+    }
+
+    /// Returns the priority one level higher than this one, or [`Priority::SERVER`] if this is the
+    /// highest.
+    #[must_use]
+    pub fn higher(self) -> Self {
+        Self(self.0.saturating_sub(1))
+    }
+}
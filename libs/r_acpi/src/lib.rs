@@ -102,6 +102,63 @@ pub struct Fadt {
 }
 const_assert_eq!(size_of::<Fadt>(), 276);
 
+#[repr(C, packed)]
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Hpet {
+    pub header: DescriptionHeader,
+    pub event_timer_block_id: u32,
+    pub base_address: GenericAddressStructure,
+    pub hpet_number: u8,
+    pub main_counter_minimum_clock_tick: u16,
+    pub page_protection_and_oem_attribute: u8,
+}
+const_assert_eq!(size_of::<Hpet>(), 56);
+
+#[repr(C, packed)]
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Madt {
+    pub header: DescriptionHeader,
+    pub local_interrupt_controller_address: u32,
+    pub flags: u32,
+    pub interrupt_controller_structure: [u8; 0],
+}
+const_assert_eq!(size_of::<Madt>(), 44);
+
+#[repr(C, packed)]
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ProcessorLocalApic {
+    pub ty: u8,
+    pub length: u8,
+    pub acpi_processor_uid: u8,
+    pub apic_id: u8,
+    pub flags: u32,
+}
+const_assert_eq!(size_of::<ProcessorLocalApic>(), 8);
+
+#[repr(C, packed)]
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct IoApic {
+    pub ty: u8,
+    pub length: u8,
+    pub io_apic_id: u8,
+    pub reserved: u8,
+    pub io_apic_address: u32,
+    pub global_system_interrupt_base: u32,
+}
+const_assert_eq!(size_of::<IoApic>(), 12);
+
+#[repr(C, packed)]
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InterruptSourceOverride {
+    pub ty: u8,
+    pub length: u8,
+    pub bus: u8,
+    pub source: u8,
+    pub global_system_interrupt: u32,
+    pub flags: u16,
+}
+const_assert_eq!(size_of::<InterruptSourceOverride>(), 10);
+
 #[repr(C, packed)]
 #[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct GenericAddressStructure {
@@ -132,4 +189,10 @@ pub const WORD_ACCESS: u8 = 2;
 pub const DWORD_ACCESS: u8 = 3;
 pub const QWORD_ACCESS: u8 = 4;
 
+pub const PROCESSOR_LOCAL_APIC: u8 = 0;
+pub const IO_APIC: u8 = 1;
+pub const INTERRUPT_SOURCE_OVERRIDE: u8 = 2;
+
+pub const LOCAL_APIC_ENABLED: u32 = 1;
+
 pub const PM_TIMER_FREQUENCY_HZ: u32 = 3_579_545;
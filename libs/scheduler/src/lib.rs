@@ -0,0 +1,508 @@
+#![no_std]
+
+//! The scheduling policy of the kernel.
+//!
+//! The runnable processes are queued by their current priorities, and the processes with the same
+//! priority run in round robin. A process which uses up its time slice is demoted, and a process
+//! which wakes up from blocking is promoted up to its base priority.
+
+extern crate alloc;
+
+use {
+    alloc::{collections::VecDeque, vec::Vec},
+    config::NUM_OF_PRIORITY_LEVELS,
+    pid::Pid,
+};
+
+pub use {alloc::collections::TryReserveError, priority::Priority};
+
+/// The scheduler of processes whose PIDs have the indices less than `N`.
+///
+/// The running process is not queued. The processes not added to the scheduler, such as the idle
+/// process, run only if no processes are runnable.
+///
+/// The queues grow with the indices of the added PIDs. [`Scheduler::reserve`] allocates the memory
+/// in advance so that adding a process does not fail.
+pub struct Scheduler<const N: usize> {
+    queues: [VecDeque<Pid>; NUM_OF_PRIORITY_LEVELS],
+    // Indexed by `Pid::index`.
+    entries: Vec<Option<Entry>>,
+}
+impl<const N: usize> Scheduler<N> {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            queues: [const { VecDeque::new() }; NUM_OF_PRIORITY_LEVELS],
+            entries: Vec::new(),
+        }
+    }
+
+    /// Allocates the memory to add `pid` to this scheduler. After this call, [`Scheduler::add`] and
+    /// [`Scheduler::migrate`] do not allocate memory to add `pid`.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the memory is not available.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the index of `pid` is not less than `N`.
+    pub fn reserve(&mut self, pid: Pid) -> Result<(), TryReserveError> {
+        assert!(pid.index() < N, "Too large PID: {}", pid);
+
+        if self.entries.len() <= pid.index() {
+            self.entries
+                .try_reserve(pid.index() + 1 - self.entries.len())?;
+            self.entries.resize(pid.index() + 1, None);
+        }
+
+        // Each queue may have all the added processes.
+        for queue in &mut self.queues {
+            queue.try_reserve(self.entries.len().saturating_sub(queue.len()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds the runnable process `pid` with the base priority `priority`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `pid` is already added or its index is not less than `N`.
+    pub fn add(&mut self, pid: Pid, priority: Priority) {
+        self.grow_for(pid);
+
+        let entry = &mut self.entries[pid.index()];
+
+        assert!(entry.is_none(), "{} is already added.", pid);
+
+        *entry = Some(Entry::new(pid, priority));
+
+        self.push(pid);
+    }
+
+    /// Removes the process `pid`. It does nothing if `pid` is not added.
+    pub fn remove(&mut self, pid: Pid) {
+        if let Some(slot) = self.entries.get_mut(pid.index()) {
+            if let Some(entry) = slot.take_if(|e| e.pid == pid) {
+                remove_from_queue(&mut self.queues[entry.current.as_usize()], pid);
+            }
+        }
+    }
+
+    /// Makes the blocked process `pid` runnable. The process is promoted by one level unless its
+    /// priority is already the base one.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `pid` is not added.
+    pub fn wake(&mut self, pid: Pid) {
+        let entry = self.entry_mut(pid);
+
+        entry.current = entry.current.higher().max(entry.base);
+        entry.remaining = entry.current.quantum();
+
+        self.push(pid);
+    }
+
+    /// Queues the running process `pid` again without changing its priority. It runs after the
+    /// other runnable processes with the same priority.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `pid` is not added.
+    pub fn requeue(&mut self, pid: Pid) {
+        self.push(pid);
+    }
+
+    /// Returns the next process to run, and removes it from the queues. Returns `None` if no
+    /// processes are runnable.
+    pub fn pop(&mut self) -> Option<Pid> {
+        self.queues.iter_mut().find_map(VecDeque::pop_front)
+    }
+
+    /// Returns `true` if no processes are runnable.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.queues.iter().all(VecDeque::is_empty)
+    }
+
+    /// Returns the number of the runnable processes, excluding the running one.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+
+    /// Moves the process `pid` to `other` with its priorities and the rest of its time slice. The
+    /// process is queued in `other` if it is queued in this scheduler.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `pid` is not added to this scheduler or is already added to `other`.
+    pub fn migrate(&mut self, pid: Pid, other: &mut Self) {
+        let entry = *self.entry_mut(pid);
+
+        let queued = remove_from_queue(&mut self.queues[entry.current.as_usize()], pid);
+
+        self.entries[pid.index()] = None;
+
+        other.grow_for(pid);
+
+        let slot = &mut other.entries[pid.index()];
+
+        assert!(slot.is_none(), "{} is already added.", pid);
+
+        *slot = Some(entry);
+
+        if queued {
+            other.push(pid);
+        }
+    }
+
+    /// Consumes a timer tick for the running process `running`, and returns `true` if it must be
+    /// preempted. It must be preempted if it has used up its time slice or a process with a higher
+    /// priority is runnable.
+    ///
+    /// A process which uses up its time slice is demoted by one level.
+    pub fn tick(&mut self, running: Pid) -> bool {
+        let entry = match self.entries.get_mut(running.index()) {
+            Some(Some(entry)) if entry.pid == running => entry,
+            // The idle process, for example.
+            _ => return !self.is_empty(),
+        };
+
+        entry.remaining = entry.remaining.saturating_sub(1);
+
+        if entry.remaining == 0 {
+            entry.current = entry.current.lower();
+            entry.remaining = entry.current.quantum();
+
+            return true;
+        }
+
+        let current = entry.current.as_usize();
+
+        self.queues[..current].iter().any(|q| !q.is_empty())
+    }
+
+    /// Changes the base priority of `pid`. The current priority is also reset to `priority`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `pid` is not added.
+    pub fn set_priority(&mut self, pid: Pid, priority: Priority) {
+        let old = self.entry_mut(pid).current;
+
+        let queued = remove_from_queue(&mut self.queues[old.as_usize()], pid);
+
+        *self.entry_mut(pid) = Entry::new(pid, priority);
+
+        if queued {
+            self.push(pid);
+        }
+    }
+
+    /// Returns the current priority of `pid`, or `None` if `pid` is not added.
+    #[must_use]
+    pub fn current_priority(&self, pid: Pid) -> Option<Priority> {
+        self.entries
+            .get(pid.index())?
+            .as_ref()
+            .filter(|e| e.pid == pid)
+            .map(|e| e.current)
+    }
+
+    fn push(&mut self, pid: Pid) {
+        let priority = self.entry_mut(pid).current;
+
+        self.queues[priority.as_usize()].push_back(pid);
+    }
+
+    // Grows the queues to add `pid` unless `reserve` already did.
+    fn grow_for(&mut self, pid: Pid) {
+        assert!(pid.index() < N, "Too large PID: {}", pid);
+
+        if self.entries.len() <= pid.index() {
+            self.entries.resize(pid.index() + 1, None);
+        }
+
+        for queue in &mut self.queues {
+            queue.reserve(self.entries.len().saturating_sub(queue.len()));
+        }
+    }
+
+    fn entry_mut(&mut self, pid: Pid) -> &mut Entry {
+        let entry = self
+            .entries
+            .get_mut(pid.index())
+            .and_then(Option::as_mut)
+            .filter(|e| e.pid == pid);
+
+        entry.unwrap_or_else(|| panic!("{} is not added.", pid))
+    }
+}
+impl<const N: usize> Default for Scheduler<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Entry {
+    // Distinguishes the processes in the same slot.
+    pid: Pid,
+    base: Priority,
+    current: Priority,
+    // The number of the timer ticks left in the current time slice.
+    remaining: u64,
+}
+impl Entry {
+    fn new(pid: Pid, priority: Priority) -> Self {
+        Self {
+            pid,
+            base: priority,
+            current: priority,
+            remaining: priority.quantum(),
+        }
+    }
+}
+
+// Returns `true` if `pid` was in `queue`.
+fn remove_from_queue(queue: &mut VecDeque<Pid>, pid: Pid) -> bool {
+    let len = queue.len();
+
+    queue.retain(|&p| p != pid);
+
+    queue.len() != len
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{Priority, Scheduler},
+        pid::Pid,
+    };
+
+    const N: usize = 8;
+
+    fn pid(pid: usize) -> Pid {
+        Pid::new(pid)
+    }
+
+    // Consumes the timer ticks of `running` until it is preempted.
+    fn run_until_preempted(s: &mut Scheduler<N>, running: Pid) {
+        while !s.tick(running) {}
+    }
+
+    #[test]
+    fn round_robin_in_same_priority() {
+        let mut s = Scheduler::<N>::new();
+
+        s.add(pid(1), Priority::APP);
+        s.add(pid(2), Priority::APP);
+
+        assert_eq!(s.pop(), Some(pid(1)));
+
+        s.requeue(pid(1));
+
+        assert_eq!(s.pop(), Some(pid(2)));
+        assert_eq!(s.pop(), Some(pid(1)));
+        assert_eq!(s.pop(), None);
+    }
+
+    #[test]
+    fn higher_priority_first() {
+        let mut s = Scheduler::<N>::new();
+
+        s.add(pid(1), Priority::APP);
+        s.add(pid(2), Priority::SERVER);
+        s.add(pid(3), Priority::DRIVER);
+
+        assert_eq!(s.pop(), Some(pid(2)));
+        assert_eq!(s.pop(), Some(pid(3)));
+        assert_eq!(s.pop(), Some(pid(1)));
+    }
+
+    #[test]
+    fn preempt_after_quantum() {
+        let mut s = Scheduler::<N>::new();
+
+        s.add(pid(1), Priority::APP);
+        assert_eq!(s.pop(), Some(pid(1)));
+
+        for _ in 1..Priority::APP.quantum() {
+            assert!(!s.tick(pid(1)));
+        }
+
+        assert!(s.tick(pid(1)));
+    }
+
+    #[test]
+    fn demote_cpu_hog() {
+        let mut s = Scheduler::<N>::new();
+
+        s.add(pid(1), Priority::SERVER);
+        assert_eq!(s.pop(), Some(pid(1)));
+
+        for _ in 0..super::NUM_OF_PRIORITY_LEVELS * 2 {
+            run_until_preempted(&mut s, pid(1));
+        }
+
+        assert_eq!(s.current_priority(pid(1)), Some(Priority::LOWEST));
+    }
+
+    #[test]
+    fn promote_on_wake() {
+        let mut s = Scheduler::<N>::new();
+
+        s.add(pid(1), Priority::DRIVER);
+        assert_eq!(s.pop(), Some(pid(1)));
+
+        run_until_preempted(&mut s, pid(1));
+        run_until_preempted(&mut s, pid(1));
+
+        let demoted = s.current_priority(pid(1)).unwrap();
+        assert!(demoted > Priority::DRIVER);
+
+        // The process blocks on IPC, and wakes up.
+        s.wake(pid(1));
+        assert!(s.current_priority(pid(1)).unwrap() < demoted);
+
+        assert_eq!(s.pop(), Some(pid(1)));
+        s.wake(pid(1));
+        assert_eq!(s.pop(), Some(pid(1)));
+
+        // Never promoted beyond the base priority.
+        s.wake(pid(1));
+        assert_eq!(s.current_priority(pid(1)), Some(Priority::DRIVER));
+    }
+
+    #[test]
+    fn preempt_by_higher_priority() {
+        let mut s = Scheduler::<N>::new();
+
+        s.add(pid(1), Priority::LOWEST);
+        assert_eq!(s.pop(), Some(pid(1)));
+
+        assert!(!s.tick(pid(1)));
+
+        s.add(pid(2), Priority::SERVER);
+
+        assert!(s.tick(pid(1)));
+    }
+
+    #[test]
+    fn idle_is_preempted_only_if_runnable() {
+        let mut s = Scheduler::<N>::new();
+
+        assert!(s.is_empty());
+        assert!(!s.tick(pid(0)));
+
+        s.add(pid(1), Priority::APP);
+
+        assert!(!s.is_empty());
+        assert!(s.tick(pid(0)));
+    }
+
+    #[test]
+    fn set_priority_requeues() {
+        let mut s = Scheduler::<N>::new();
+
+        s.add(pid(1), Priority::APP);
+        s.add(pid(2), Priority::APP);
+
+        s.set_priority(pid(2), Priority::SERVER);
+
+        assert_eq!(s.current_priority(pid(2)), Some(Priority::SERVER));
+        assert_eq!(s.pop(), Some(pid(2)));
+        assert_eq!(s.pop(), Some(pid(1)));
+        assert_eq!(s.pop(), None);
+    }
+
+    #[test]
+    fn remove_from_queue() {
+        let mut s = Scheduler::<N>::new();
+
+        s.add(pid(1), Priority::APP);
+        s.add(pid(2), Priority::APP);
+
+        s.remove(pid(1));
+
+        assert_eq!(s.current_priority(pid(1)), None);
+        assert_eq!(s.pop(), Some(pid(2)));
+        assert_eq!(s.pop(), None);
+    }
+
+    #[test]
+    fn stale_pid_is_ignored() {
+        let mut s = Scheduler::<N>::new();
+
+        let pid = Pid::from_index_and_generation(1, 1);
+
+        s.add(pid, Priority::APP);
+
+        s.remove(Pid::from_index_and_generation(1, 0));
+
+        assert_eq!(s.current_priority(Pid::new(1)), None);
+        assert_eq!(s.current_priority(pid), Some(Priority::APP));
+        assert_eq!(s.pop(), Some(pid));
+    }
+
+    #[test]
+    fn migrate_keeps_priority_and_queue() {
+        let mut from = Scheduler::<N>::new();
+        let mut to = Scheduler::<N>::new();
+
+        from.add(pid(1), Priority::SERVER);
+        from.add(pid(2), Priority::APP);
+
+        assert_eq!(from.pop(), Some(pid(1)));
+        run_until_preempted(&mut from, pid(1));
+        from.requeue(pid(1));
+
+        from.migrate(pid(1), &mut to);
+
+        assert_eq!(from.current_priority(pid(1)), None);
+        assert_eq!(to.current_priority(pid(1)), Some(Priority::DRIVER));
+        assert_eq!(from.len(), 1);
+        assert_eq!(to.len(), 1);
+        assert_eq!(to.pop(), Some(pid(1)));
+
+        // A process which is not queued, such as the running one, stays unqueued.
+        to.migrate(pid(1), &mut from);
+
+        assert_eq!(to.current_priority(pid(1)), None);
+        assert_eq!(from.pop(), Some(pid(2)));
+        assert_eq!(from.pop(), None);
+    }
+
+    #[test]
+    fn reserve_grows_queues() {
+        let mut s = Scheduler::<N>::new();
+
+        assert!(s.reserve(pid(5)).is_ok());
+        assert_eq!(s.entries.len(), 6);
+        assert!(s.queues.iter().all(|q| q.capacity() >= 6));
+
+        // Reserving for a smaller index keeps the entries.
+        assert!(s.reserve(pid(2)).is_ok());
+        assert_eq!(s.entries.len(), 6);
+
+        s.add(pid(5), Priority::APP);
+        assert_eq!(s.pop(), Some(pid(5)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Too large PID")]
+    fn reserve_too_large_pid() {
+        let mut s = Scheduler::<N>::new();
+
+        let _ = s.reserve(pid(N));
+    }
+
+    #[test]
+    fn invalid_priority() {
+        assert_eq!(Priority::new(config::NUM_OF_PRIORITY_LEVELS), None);
+        assert_eq!(Priority::new(0), Some(Priority::SERVER));
+    }
+}
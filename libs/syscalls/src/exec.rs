@@ -0,0 +1,200 @@
+use {
+    crate::Ty,
+    core::{convert::TryInto, str},
+    ipc::message::{Body, Header, Message},
+    x86_64::VirtAddr,
+};
+
+/// The name of an executable file, and the arguments and the environment variables passed to it.
+///
+/// They are packed into a buffer as null-terminated strings in this order to be copied from the
+/// address space of the caller of [`crate::exec`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ExecArgs<'a> {
+    name: &'a str,
+    argv: &'a [u8],
+    argc: usize,
+    envp: &'a [u8],
+    envc: usize,
+}
+impl<'a> ExecArgs<'a> {
+    /// The maximum number of bytes of the packed strings.
+    pub const MAX_LEN: usize = 4096;
+
+    /// Returns the arguments to execute `name` without any arguments and environment variables.
+    #[must_use]
+    pub fn without_arguments(name: &'a str) -> Self {
+        Self {
+            name,
+            argv: &[],
+            argc: 0,
+            envp: &[],
+            envc: 0,
+        }
+    }
+
+    /// Parses the strings packed by [`crate::exec`]. Returns `None` if `bytes` does not contain
+    /// exactly `1 + argc + envc` null-terminated strings or the name is not a valid UTF-8 string.
+    #[must_use]
+    pub fn parse(bytes: &'a [u8], argc: usize, envc: usize) -> Option<Self> {
+        if bytes.last() != Some(&0) || bytes.iter().filter(|b| **b == 0).count() != 1 + argc + envc
+        {
+            return None;
+        }
+
+        let name_len = bytes.iter().position(|b| *b == 0)?;
+        let name = str::from_utf8(&bytes[..name_len]).ok()?;
+
+        let rest = &bytes[name_len + 1..];
+
+        let argv_len = nth_string_end(rest, argc);
+        let (argv, envp) = rest.split_at(argv_len);
+
+        Some(Self {
+            name,
+            argv,
+            argc,
+            envp,
+            envc,
+        })
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    #[must_use]
+    pub fn argc(&self) -> usize {
+        self.argc
+    }
+
+    #[must_use]
+    pub fn envc(&self) -> usize {
+        self.envc
+    }
+
+    /// Returns the arguments. Each of them contains the terminating null character.
+    pub fn argv(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.argv.split_inclusive(|b| *b == 0)
+    }
+
+    /// Returns the environment variables. Each of them contains the terminating null character.
+    pub fn envp(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.envp.split_inclusive(|b| *b == 0)
+    }
+
+    /// Returns the number of bytes of the arguments and the environment variables.
+    #[must_use]
+    pub fn strings_len(&self) -> usize {
+        self.argv.len() + self.envp.len()
+    }
+}
+
+/// The location of the strings packed for [`crate::exec`] in the address space of the caller.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExecRequest {
+    addr: VirtAddr,
+    len: usize,
+    argc: usize,
+    envc: usize,
+}
+impl ExecRequest {
+    /// Returns `None` if `body` does not contain a valid request.
+    #[must_use]
+    pub fn from_body(body: &Body) -> Option<Self> {
+        Some(Self {
+            addr: VirtAddr::try_new(body.1).ok()?,
+            len: body.2.try_into().ok()?,
+            argc: (body.3 >> 32).try_into().ok()?,
+            envc: (body.3 & u64::from(u32::MAX)).try_into().ok()?,
+        })
+    }
+
+    #[must_use]
+    pub fn addr(&self) -> VirtAddr {
+        self.addr
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[must_use]
+    pub fn argc(&self) -> usize {
+        self.argc
+    }
+
+    #[must_use]
+    pub fn envc(&self) -> usize {
+        self.envc
+    }
+
+    /// Returns a message with `ty` in the first field. The last field is `0`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `argc` or `envc` does not fit in 32 bits.
+    #[must_use]
+    pub fn to_message(self, ty: Ty) -> Message {
+        let argc: u32 = self.argc.try_into().unwrap();
+        let envc: u32 = self.envc.try_into().unwrap();
+
+        Message {
+            header: Header::default(),
+            body: Body(
+                ty as _,
+                self.addr.as_u64(),
+                self.len.try_into().unwrap(),
+                u64::from(argc) << 32 | u64::from(envc),
+                0,
+            ),
+        }
+    }
+}
+
+/// Packs `name`, `argv`, and `envp` into `buffer`, and returns the request to pass them. Returns
+/// `None` if they are too long or a string contains a null character.
+pub(crate) fn pack(
+    buffer: &mut [u8; ExecArgs::MAX_LEN],
+    name: &str,
+    argv: &[&str],
+    envp: &[&str],
+) -> Option<ExecRequest> {
+    let mut len = 0;
+
+    for s in [name].iter().chain(argv).chain(envp) {
+        if s.contains('\0') {
+            return None;
+        }
+
+        let end = len + s.len();
+
+        buffer.get_mut(len..end)?.copy_from_slice(s.as_bytes());
+        *buffer.get_mut(end)? = 0;
+
+        len = end + 1;
+    }
+
+    Some(ExecRequest {
+        addr: VirtAddr::from_ptr(buffer.as_ptr()),
+        len,
+        argc: argv.len(),
+        envc: envp.len(),
+    })
+}
+
+// Returns the index just after the `n`-th null character of `bytes`.
+fn nth_string_end(bytes: &[u8], n: usize) -> usize {
+    bytes
+        .split_inclusive(|b| *b == 0)
+        .take(n)
+        .map(<[u8]>::len)
+        .sum()
+}
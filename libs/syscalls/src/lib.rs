@@ -1,15 +1,31 @@
+//! The system calls served by the kernel and the servers.
+//!
+//! A reply holds `0` in its first field on success. Otherwise, the first field holds the
+//! discriminant of the error, so the discriminants of the error enums start from `1`.
+
 #![no_std]
 
+mod exec;
+
+pub use {
+    exec::{ExecArgs, ExecRequest},
+    priority::Priority,
+};
+
 use {
-    core::convert::TryInto,
+    core::{convert::TryInto, ops::BitOr, str, time::Duration},
     ipc::message::{Body, Header, Message},
     num_derive::FromPrimitive,
     num_traits::FromPrimitive,
-    os_units::Bytes,
+    os_units::{Bytes, NumOfPages},
     pid::{predefined, Pid},
-    x86_64::{PhysAddr, VirtAddr},
+    x86_64::{structures::paging::Size4KiB, PhysAddr, VirtAddr},
 };
 
+// The fields of a message are `u64`, and this OS runs only on 64-bit processors, so `usize` and
+// `u64` are converted to each other with `as` without losing any bits.
+const _: () = assert!(usize::BITS == u64::BITS);
+
 /// # Panics
 ///
 /// This function panics if the kernel did not reply an empty message.
@@ -19,42 +35,151 @@ pub fn noop() {
         body: Body(Ty::Noop as _, 0, 0, 0, 0),
     };
 
-    ipc::send(predefined::SYSPROC, message);
-
-    let reply = ipc::receive(predefined::SYSPROC.into());
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
 
     assert_eq!(reply.body, Body::default());
 }
 
+/// Copies `bytes` bytes from `src_addr` of the process `src_pid` to `dst_addr` of the caller's
+/// address space. `src_pid` must grant the caller the read access to the source region by
+/// [`create_grant`], even if both are kernel processes.
+///
 /// # Safety
 ///
-/// The all arguments must be correct.
+/// The destination region must be valid.
 ///
-/// # Panics
+/// # Errors
 ///
-/// This function panics if one of the following conditions is satisfied.
-/// - The kernel did not reply an empty message.
-/// - `bytes < 128`. This is the current implementation limitation.
-pub unsafe fn copy_data_from(src_pid: Pid, src_addr: VirtAddr, dst_addr: VirtAddr, bytes: Bytes) {
-    // TODO: Remove this limitation.
-    assert!(bytes.as_usize() < 128, "`bytes` must be less than 128.");
-
+/// This function returns an error if there is no such grant or the regions are not accessible.
+pub unsafe fn copy_data_from(
+    src_pid: Pid,
+    src_addr: VirtAddr,
+    dst_addr: VirtAddr,
+    bytes: Bytes,
+) -> Result<(), GrantError> {
     let message = Message {
         header: Header::default(),
         body: Body(
             Ty::CopyDataFrom as _,
-            src_pid.as_usize().try_into().unwrap(),
+            src_pid.as_usize() as u64,
             src_addr.as_u64(),
             dst_addr.as_u64(),
-            bytes.as_usize().try_into().unwrap(),
+            bytes.as_usize() as u64,
         ),
     };
 
-    ipc::send(predefined::SYSPROC, message);
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
 
-    let reply = ipc::receive(predefined::SYSPROC.into());
+    result_from_reply(&reply).map(|_| ())
+}
 
-    assert_eq!(reply.body, Body::default());
+/// Allows the process `to` to access `len` bytes from `start` of the caller's address space.
+///
+/// # Safety
+///
+/// The memory region must be valid until the returned grant is revoked by [`revoke_grant`].
+///
+/// # Errors
+///
+/// This function returns an error if the kernel failed to create a grant.
+pub unsafe fn create_grant(
+    to: Pid,
+    start: VirtAddr,
+    len: Bytes,
+    access: GrantAccess,
+) -> Result<GrantId, GrantError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(
+            Ty::CreateGrant as _,
+            to.as_usize() as u64,
+            start.as_u64(),
+            len.as_usize() as u64,
+            access as _,
+        ),
+    };
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    result_from_reply(&reply).map(GrantId)
+}
+
+/// # Errors
+///
+/// This function returns an error if `id` is not a grant created by the caller.
+pub fn revoke_grant(id: GrantId) -> Result<(), GrantError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(Ty::RevokeGrant as _, id.0, 0, 0, 0),
+    };
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    result_from_reply(&reply).map(|_| ())
+}
+
+/// Copies `len` bytes from `offset` of the region granted by `id` to `dst`.
+///
+/// # Safety
+///
+/// `dst` must be valid for writes of `len` bytes.
+///
+/// # Errors
+///
+/// This function returns an error if the grant does not allow the caller to read the region, or
+/// one of the memory regions is not accessible.
+pub unsafe fn copy_from_grant(
+    id: GrantId,
+    offset: Bytes,
+    dst: VirtAddr,
+    len: Bytes,
+) -> Result<(), GrantError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(
+            Ty::CopyFromGrant as _,
+            id.0,
+            offset.as_usize() as u64,
+            dst.as_u64(),
+            len.as_usize() as u64,
+        ),
+    };
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    result_from_reply(&reply).map(|_| ())
+}
+
+/// Copies `len` bytes from `src` to `offset` of the region granted by `id`.
+///
+/// # Safety
+///
+/// `src` must be valid for reads of `len` bytes.
+///
+/// # Errors
+///
+/// This function returns an error if the grant does not allow the caller to write to the region,
+/// or one of the memory regions is not accessible.
+pub unsafe fn copy_to_grant(
+    id: GrantId,
+    offset: Bytes,
+    src: VirtAddr,
+    len: Bytes,
+) -> Result<(), GrantError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(
+            Ty::CopyToGrant as _,
+            id.0,
+            offset.as_usize() as u64,
+            src.as_u64(),
+            len.as_usize() as u64,
+        ),
+    };
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    result_from_reply(&reply).map(|_| ())
 }
 
 /// # Panics
@@ -67,9 +192,7 @@ pub fn get_screen_info() -> ScreenInfo {
         body: Body(Ty::GetScreenInfo as _, 0, 0, 0, 0),
     };
 
-    ipc::send(predefined::SYSPROC, message);
-
-    let reply = ipc::receive(predefined::SYSPROC.into());
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
 
     ScreenInfo {
         resolution_x: reply.body.0.try_into().unwrap(),
@@ -94,15 +217,13 @@ pub unsafe fn map_memory(start: PhysAddr, len: Bytes) -> VirtAddr {
         body: Body(
             Ty::MapMemory as _,
             start.as_u64(),
-            len.as_usize().try_into().unwrap(),
+            len.as_usize() as u64,
             0,
             0,
         ),
     };
 
-    ipc::send(predefined::SYSPROC, message);
-
-    let reply = ipc::receive(predefined::SYSPROC.into());
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
 
     assert_ne!(reply.body.0, 0, "Failed to map memory.");
 
@@ -111,33 +232,31 @@ pub unsafe fn map_memory(start: PhysAddr, len: Bytes) -> VirtAddr {
 
 /// # Panics
 ///
-/// This function panics if `s.len() >= 128`.
+/// This function panics if one of the following conditions is satisfied.
+/// - The kernel failed to create or revoke a grant of `s` for the tty.
+/// - The tty sent an invalid message.
 pub fn write(s: &str) {
-    assert!(
-        s.len() < 128,
-        "The current limitation is that the length must be less than 128 characters."
-    );
+    let start = VirtAddr::from_ptr(s.as_ptr());
+    let len = Bytes::new(s.len());
+
+    // SAFETY: `s` is alive until the grant is revoked at the end of this function.
+    let grant = unsafe { create_grant(predefined::TTY, start, len, GrantAccess::Read) };
+    let grant = grant.expect("Failed to create a grant for the tty.");
 
     let message = Message {
         header: Header::default(),
-        body: Body(
-            Ty::Write as _,
-            s.as_ptr() as _,
-            s.len().try_into().unwrap(),
-            0,
-            0,
-        ),
+        body: Body(Ty::Write as _, grant.as_u64(), s.len() as u64, 0, 0),
     };
 
-    ipc::send(predefined::TTY, message);
-
-    let reply = ipc::receive(predefined::TTY.into());
+    let reply = ipc::send_receive(predefined::TTY, message);
 
     assert_eq!(
         reply.body,
         Body::default(),
         "The tty sent an invalid message."
     );
+
+    revoke_grant(grant).expect("Failed to revoke the grant for the tty.");
 }
 
 #[must_use]
@@ -149,13 +268,510 @@ pub fn pm_syncs_with_kernel() -> Option<Message> {
         body: Body(Ty::PmSyncsWithKernel as _, 0, 0, 0, 0),
     };
 
-    ipc::send(predefined::SYSPROC, message);
-
-    let reply = ipc::receive(predefined::SYSPROC.into());
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
 
     (reply.body.0 == NOT_END).then(|| reply)
 }
 
+/// Asks PM to create a process from the executable file `name` in the initrd.
+///
+/// # Errors
+///
+/// This function returns an error if `name` is too long or PM failed to create the process.
+pub fn spawn(name: &str) -> Result<Pid, SpawnError> {
+    let name = ProcessName::new(name).ok_or(SpawnError::InvalidName)?;
+
+    let reply = ipc::send_receive(predefined::PM, name.to_message(Ty::Spawn));
+
+    result_from_reply(&reply).map(pid_from_field)
+}
+
+/// Creates a process from the executable file `name` in the initrd. Only PM may call this
+/// function. Other processes must use [`spawn`].
+///
+/// # Errors
+///
+/// This function returns an error if the caller is not PM or the kernel failed to create the
+/// process.
+pub fn create_process(name: ProcessName) -> Result<Pid, SpawnError> {
+    let reply = ipc::send_receive(predefined::SYSPROC, name.to_message(Ty::CreateProcess));
+
+    result_from_reply(&reply).map(pid_from_field)
+}
+
+/// Terminates the calling process with `status`.
+pub fn exit(status: i32) -> ! {
+    let message = Message {
+        header: Header::default(),
+        // The status is sign-extended, and PM truncates it to `i32` again.
+        #[allow(clippy::cast_sign_loss)]
+        body: Body(Ty::Exit as _, i64::from(status) as _, 0, 0, 0),
+    };
+
+    // PM never replies to this message.
+    let _ = ipc::try_send_receive(predefined::PM, message);
+
+    unreachable!("The process should be destroyed.");
+}
+
+/// Destroys the process with PID `pid`. Only PM may call this function. Other processes must use
+/// [`exit`].
+///
+/// # Errors
+///
+/// This function returns an error if the caller is not PM or there is no process with PID `pid`.
+pub fn destroy_process(pid: Pid) -> Result<(), ipc::Error> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(Ty::DestroyProcess as _, pid.as_usize() as u64, 0, 0, 0),
+    };
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    if reply.body.0 == 0 {
+        Ok(())
+    } else {
+        Err(ipc::Error::NoSuchProcess(pid.into()))
+    }
+}
+
+/// Asks PM to replace the image of the calling process with the executable file `name` in the
+/// initrd. The new image receives `argv` and `envp`.
+///
+/// This function returns only if it fails.
+///
+/// # Panics
+///
+/// This function panics if PM replies an invalid error.
+pub fn exec(name: &str, argv: &[&str], envp: &[&str]) -> ExecError {
+    let mut buffer = [0; ExecArgs::MAX_LEN];
+
+    let request = match exec::pack(&mut buffer, name, argv, envp) {
+        Some(request) => request,
+        None => return ExecError::InvalidArguments,
+    };
+
+    let reply = ipc::send_receive(predefined::PM, request.to_message(Ty::Exec));
+
+    FromPrimitive::from_u64(reply.body.0).expect("Invalid exec error.")
+}
+
+/// Replaces the image of the process `pid`, which is waiting for the reply to [`exec`], with the
+/// executable file specified by `request`. Only PM may call this function.
+///
+/// # Errors
+///
+/// This function returns an error if the caller is not PM, the arguments are invalid, there is no
+/// such file, or the kernel failed to load it.
+pub fn exec_process(pid: Pid, request: ExecRequest) -> Result<(), ExecError> {
+    let mut message = request.to_message(Ty::ExecProcess);
+
+    message.body.4 = pid.as_usize() as u64;
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    result_from_reply(&reply).map(|_| ())
+}
+
+/// Asks PM to create a copy of the calling process. The pages of the two processes are shared until
+/// either of them writes to them.
+///
+/// This function returns the PID of the child process to the parent, and `None` to the child.
+///
+/// # Errors
+///
+/// This function returns an error if PM failed to create the child process.
+pub fn fork() -> Result<Option<Pid>, ForkError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(Ty::Fork as _, 0, 0, 0, 0),
+    };
+
+    let reply = ipc::send_receive(predefined::PM, message);
+
+    // The idle process is never a child process, so the child receives `0` as the PID.
+    result_from_reply(&reply)
+        .map(pid_from_field)
+        .map(|pid| (pid != predefined::IDLE).then_some(pid))
+}
+
+/// Asks the VM server to create a copy of the process `parent`, which is waiting for the reply to
+/// [`fork`]. Only PM may call this function.
+///
+/// # Errors
+///
+/// This function returns an error if the caller is not PM or the kernel failed to create the
+/// process.
+pub fn fork_process(parent: Pid) -> Result<Pid, ForkError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(Ty::ForkProcess as _, parent.as_usize() as u64, 0, 0, 0),
+    };
+
+    let reply = ipc::send_receive(predefined::VM_SERVER, message);
+
+    result_from_reply(&reply).map(pid_from_field)
+}
+
+/// Creates a copy of the process `parent`, which is waiting for the reply to [`fork`]. The copy
+/// receives the reply from PM with `0` as the PID. Only the VM server may call this function.
+///
+/// # Errors
+///
+/// This function returns an error if the caller is not the VM server or the kernel failed to
+/// create the process.
+pub fn duplicate_process(parent: Pid) -> Result<Pid, ForkError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(Ty::DuplicateProcess as _, parent.as_usize() as u64, 0, 0, 0),
+    };
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    result_from_reply(&reply).map(pid_from_field)
+}
+
+/// Waits for the child process `pid`, or any child process if `pid` is `None`, to terminate, and
+/// returns its PID and how it terminated. The child process is removed after this function returns
+/// it.
+///
+/// If `no_hang` is `true`, this function returns `Ok(None)` instead of waiting if no such child
+/// process has terminated yet.
+///
+/// # Errors
+///
+/// This function returns an error if the calling process has no such child process.
+///
+/// # Panics
+///
+/// This function panics if PM replies an invalid message.
+pub fn wait(pid: Option<Pid>, no_hang: bool) -> Result<Option<(Pid, Termination)>, WaitError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(
+            Ty::Wait as _,
+            // The idle process is never a child process, so `0` means any child process.
+            pid.map_or(0, |pid| pid.as_usize() as u64),
+            no_hang.into(),
+            0,
+            0,
+        ),
+    };
+
+    let reply = ipc::send_receive(predefined::PM, message);
+
+    // The idle process is never a child process, so `0` means no child process has terminated.
+    let pid = match result_from_reply(&reply)? {
+        0 => return Ok(None),
+        pid => pid_from_field(pid),
+    };
+
+    let termination = Termination::from_fields(reply.body.2, reply.body.3);
+    let termination = termination.expect("Invalid termination.");
+
+    Ok(Some((pid, termination)))
+}
+
+/// Changes the base priority of the process `pid`. Only INIT may call this function.
+///
+/// # Errors
+///
+/// This function returns an error if the calling process is not privileged or there is no process
+/// with PID `pid`.
+pub fn set_priority(pid: Pid, priority: Priority) -> Result<(), SetPriorityError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(
+            Ty::SetPriority as _,
+            pid.as_usize() as u64,
+            priority.as_usize() as u64,
+            0,
+            0,
+        ),
+    };
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    result_from_reply(&reply).map(|_| ())
+}
+
+/// Returns the time elapsed since the boot. The clock never goes backward. Its resolution is of
+/// the TSC if the processor has an invariant one, and one timer tick otherwise.
+#[must_use]
+pub fn monotonic_time() -> Duration {
+    let message = Message {
+        header: Header::default(),
+        body: Body(Ty::GetMonotonicTime as _, 0, 0, 0, 0),
+    };
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    Duration::from_nanos(reply.body.1)
+}
+
+/// Blocks the calling process for at least `duration`. The process does not use the CPU while it
+/// is sleeping.
+///
+/// The process wakes up on a timer tick, so it may sleep longer than `duration` by up to a tick.
+///
+/// # Panics
+///
+/// This function panics if the kernel did not reply an empty message.
+pub fn sleep(duration: Duration) {
+    let message = Message {
+        header: Header::default(),
+        body: Body(
+            Ty::Sleep as _,
+            duration.as_nanos().try_into().unwrap_or(u64::MAX),
+            0,
+            0,
+            0,
+        ),
+    };
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    assert_eq!(reply.body, Body::default());
+}
+
+/// Binds the interrupts with `vector` to the calling process. Each interrupt is delivered as a
+/// notification from [`predefined::HARDWARE`], and [`Message::has_interrupt`] tells its vector.
+///
+/// The interrupts are masked until the process calls [`irq_enable`].
+///
+/// # Errors
+///
+/// This function returns an error if the calling process is not a driver allowed to handle
+/// interrupts, no device interrupts with `vector`, or another process has registered it.
+pub fn register_irq(vector: u8) -> Result<(), IrqError> {
+    irq_request(Ty::RegisterIrq, vector)
+}
+
+/// Unmasks the interrupts with `vector`.
+///
+/// # Errors
+///
+/// This function returns an error if the calling process has not registered `vector`.
+pub fn irq_enable(vector: u8) -> Result<(), IrqError> {
+    irq_request(Ty::IrqEnable, vector)
+}
+
+/// Tells the kernel that the calling process has handled an interrupt with `vector`. A
+/// level-triggered interrupt is masked from its delivery until this call.
+///
+/// # Errors
+///
+/// This function returns an error if the calling process has not registered `vector`.
+pub fn irq_ack(vector: u8) -> Result<(), IrqError> {
+    irq_request(Ty::IrqAck, vector)
+}
+
+fn irq_request(ty: Ty, vector: u8) -> Result<(), IrqError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(ty as _, vector.into(), 0, 0, 0),
+    };
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    result_from_reply(&reply).map(|_| ())
+}
+
+/// Asks the VM server to move the end of the heap of the calling process to `end`, which is rounded
+/// up to a page boundary, and returns the new end. If `end` is `None`, the heap is not changed.
+///
+/// The pages of the heap are mapped to zeroed frames when they are accessed for the first time.
+///
+/// # Errors
+///
+/// This function returns an error if `end` is below the start of the heap or too high.
+pub fn brk(end: Option<VirtAddr>) -> Result<VirtAddr, MemoryError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(Ty::Brk as _, end.map_or(0, VirtAddr::as_u64), 0, 0, 0),
+    };
+
+    let reply = ipc::send_receive(predefined::VM_SERVER, message);
+
+    result_from_reply(&reply).map(VirtAddr::new)
+}
+
+/// Asks the VM server to map `len` bytes of anonymous memory with `protection` to the calling
+/// process, and returns its start address. The memory is zeroed.
+///
+/// # Errors
+///
+/// This function returns an error if `len` is zero, `protection` is invalid, or no region is
+/// available.
+pub fn mmap(len: Bytes, protection: Protection) -> Result<VirtAddr, MemoryError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(
+            Ty::Mmap as _,
+            len.as_usize() as u64,
+            protection.bits(),
+            0,
+            0,
+        ),
+    };
+
+    let reply = ipc::send_receive(predefined::VM_SERVER, message);
+
+    result_from_reply(&reply).map(VirtAddr::new)
+}
+
+/// Asks the VM server to unmap the anonymous memory in `[start, start + len)` of the calling
+/// process.
+///
+/// # Errors
+///
+/// This function returns an error if `start` is not page-aligned, `len` is zero, or the range is
+/// not in the region for the anonymous memory.
+pub fn munmap(start: VirtAddr, len: Bytes) -> Result<(), MemoryError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(Ty::Munmap as _, start.as_u64(), len.as_usize() as u64, 0, 0),
+    };
+
+    let reply = ipc::send_receive(predefined::VM_SERVER, message);
+
+    result_from_reply(&reply).map(|_| ())
+}
+
+/// Asks the VM server to change the protection of the anonymous memory in `[start, start + len)`
+/// of the calling process to `protection`.
+///
+/// # Errors
+///
+/// This function returns an error if `start` is not page-aligned, `len` is zero, `protection` is
+/// invalid, or a part of the range is not mapped by [`mmap`].
+pub fn mprotect(start: VirtAddr, len: Bytes, protection: Protection) -> Result<(), MemoryError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(
+            Ty::Mprotect as _,
+            start.as_u64(),
+            len.as_usize() as u64,
+            protection.bits(),
+            0,
+        ),
+    };
+
+    let reply = ipc::send_receive(predefined::VM_SERVER, message);
+
+    result_from_reply(&reply).map(|_| ())
+}
+
+/// Moves the end of the heap of the process `pid` to `end`, and returns the new end. If `end` is
+/// `None`, the heap is not changed. Only the VM server may call this function. Other processes
+/// must use [`brk`].
+///
+/// # Errors
+///
+/// This function returns an error if the caller is not the VM server, or `end` is not page-aligned,
+/// below the start of the heap, or too high.
+pub fn resize_heap(pid: Pid, end: Option<VirtAddr>) -> Result<VirtAddr, MemoryError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(
+            Ty::ResizeHeap as _,
+            pid.as_usize() as u64,
+            end.map_or(0, VirtAddr::as_u64),
+            0,
+            0,
+        ),
+    };
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    result_from_reply(&reply).map(VirtAddr::new)
+}
+
+/// Maps `num_of_pages` pages of anonymous memory with `protection` to the process `pid`, and
+/// returns the start address. Only the VM server may call this function. Other processes must use
+/// [`mmap`].
+///
+/// # Errors
+///
+/// This function returns an error if the caller is not the VM server or no region is available.
+pub fn map_anonymous(
+    pid: Pid,
+    num_of_pages: NumOfPages<Size4KiB>,
+    protection: Protection,
+) -> Result<VirtAddr, MemoryError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(
+            Ty::MapAnonymous as _,
+            pid.as_usize() as u64,
+            num_of_pages.as_usize() as u64,
+            protection.bits(),
+            0,
+        ),
+    };
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    result_from_reply(&reply).map(VirtAddr::new)
+}
+
+/// Unmaps `num_of_pages` pages of anonymous memory from `start` of the process `pid`. Only the VM
+/// server may call this function. Other processes must use [`munmap`].
+///
+/// # Errors
+///
+/// This function returns an error if the caller is not the VM server or the pages are not in the
+/// region for the anonymous memory.
+pub fn unmap_anonymous(
+    pid: Pid,
+    start: VirtAddr,
+    num_of_pages: NumOfPages<Size4KiB>,
+) -> Result<(), MemoryError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(
+            Ty::UnmapAnonymous as _,
+            pid.as_usize() as u64,
+            start.as_u64(),
+            num_of_pages.as_usize() as u64,
+            0,
+        ),
+    };
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    result_from_reply(&reply).map(|_| ())
+}
+
+/// Changes the protection of `num_of_pages` pages of anonymous memory from `start` of the process
+/// `pid`. Only the VM server may call this function. Other processes must use [`mprotect`].
+///
+/// # Errors
+///
+/// This function returns an error if the caller is not the VM server or a part of the pages is not
+/// anonymous memory.
+pub fn protect_anonymous(
+    pid: Pid,
+    start: VirtAddr,
+    num_of_pages: NumOfPages<Size4KiB>,
+    protection: Protection,
+) -> Result<(), MemoryError> {
+    let message = Message {
+        header: Header::default(),
+        body: Body(
+            Ty::ProtectAnonymous as _,
+            pid.as_usize() as u64,
+            start.as_u64(),
+            num_of_pages.as_usize() as u64,
+            protection.bits(),
+        ),
+    };
+
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
+
+    result_from_reply(&reply).map(|_| ())
+}
+
 /// # Panics
 ///
 /// This function panics if the returned value is out of `u32` range.
@@ -166,9 +782,7 @@ pub fn inl(port: u16) -> u32 {
         body: Body(Ty::Inl as _, port.into(), 0, 0, 0),
     };
 
-    ipc::send(predefined::SYSPROC, message);
-
-    let reply = ipc::receive(predefined::SYSPROC.into());
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
 
     reply.body.0.try_into().unwrap()
 }
@@ -182,9 +796,7 @@ pub fn outl(port: u16, value: u32) {
         body: Body(Ty::Outl as _, port.into(), value.into(), 0, 0),
     };
 
-    ipc::send(predefined::SYSPROC, message);
-
-    let reply = ipc::receive(predefined::SYSPROC.into());
+    let reply = ipc::send_receive(predefined::SYSPROC, message);
 
     assert_eq!(reply, Message::default());
 }
@@ -246,6 +858,358 @@ impl ScreenInfo {
     }
 }
 
+// The upper 32 bits hold the PID of the granter, and the lower 32 bits hold the index of the grant
+// in the granter's grant table.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GrantId(u64);
+impl GrantId {
+    /// # Panics
+    ///
+    /// This method panics if `granter` or `index` does not fit in 32 bits.
+    #[must_use]
+    pub fn new(granter: Pid, index: usize) -> Self {
+        let granter: u32 = granter.as_usize().try_into().unwrap();
+        let index: u32 = index.try_into().unwrap();
+
+        Self(u64::from(granter) << 32 | u64::from(index))
+    }
+
+    #[must_use]
+    pub fn from_u64(id: u64) -> Self {
+        Self(id)
+    }
+
+    #[must_use]
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    #[must_use]
+    pub fn granter(self) -> Pid {
+        pid_from_field(self.0 >> 32)
+    }
+
+    #[must_use]
+    pub fn index(self) -> usize {
+        // The index is 32 bits.
+        #[allow(clippy::cast_possible_truncation)]
+        let index = (self.0 & u64::from(u32::MAX)) as usize;
+
+        index
+    }
+}
+
+#[derive(Copy, Clone, Debug, FromPrimitive, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GrantAccess {
+    Read = 0b01,
+    Write = 0b10,
+    ReadWrite = 0b11,
+}
+impl GrantAccess {
+    #[must_use]
+    pub fn allows(self, required: Self) -> bool {
+        (self as u64) & (required as u64) == required as u64
+    }
+}
+
+#[derive(Copy, Clone, Debug, FromPrimitive, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GrantError {
+    NoSlot = 1,
+    InvalidGrant,
+    InvalidAddress,
+}
+
+/// The name of an executable file in the initrd.
+///
+/// The name is stored in the last four fields of a message body, so it must not be longer than
+/// [`ProcessName::MAX_LEN`] bytes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProcessName([u8; ProcessName::MAX_LEN]);
+impl ProcessName {
+    pub const MAX_LEN: usize = 32;
+
+    /// Returns `None` if `name` is longer than [`ProcessName::MAX_LEN`] bytes or contains a NUL
+    /// character.
+    #[must_use]
+    pub fn new(name: &str) -> Option<Self> {
+        if name.len() > Self::MAX_LEN || name.contains('\0') {
+            return None;
+        }
+
+        let mut bytes = [0; Self::MAX_LEN];
+
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+
+        Some(Self(bytes))
+    }
+
+    #[must_use]
+    pub fn from_body(body: &Body) -> Self {
+        let mut bytes = [0; Self::MAX_LEN];
+
+        for (chunk, field) in bytes
+            .chunks_exact_mut(8)
+            .zip([body.1, body.2, body.3, body.4])
+        {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+
+        Self(bytes)
+    }
+
+    /// Returns `None` if the name is not a valid UTF-8 string.
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        let len = self.0.iter().position(|&b| b == 0).unwrap_or(Self::MAX_LEN);
+
+        str::from_utf8(&self.0[..len]).ok()
+    }
+
+    #[must_use]
+    pub fn to_message(self, ty: Ty) -> Message {
+        let mut fields = [0; 4];
+
+        for (field, chunk) in fields.iter_mut().zip(self.0.chunks_exact(8)) {
+            let mut bytes = [0; 8];
+
+            bytes.copy_from_slice(chunk);
+
+            *field = u64::from_le_bytes(bytes);
+        }
+
+        Message {
+            header: Header::default(),
+            body: Body(ty as _, fields[0], fields[1], fields[2], fields[3]),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, FromPrimitive, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SpawnError {
+    InvalidName = 1,
+    CreationFailed,
+    PermissionDenied,
+}
+
+#[derive(Copy, Clone, Debug, FromPrimitive, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ForkError {
+    CreationFailed = 1,
+    PermissionDenied,
+}
+
+#[derive(Copy, Clone, Debug, FromPrimitive, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ExecError {
+    InvalidArguments = 1,
+    NotFound,
+    LoadFailed,
+    PermissionDenied,
+}
+
+#[derive(Copy, Clone, Debug, FromPrimitive, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WaitError {
+    NoChildren = 1,
+}
+
+#[derive(Copy, Clone, Debug, FromPrimitive, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SetPriorityError {
+    PermissionDenied = 1,
+    NoSuchProcess,
+    InvalidPriority,
+}
+
+/// The report of a fault of a user process, which the kernel sends to PM on behalf of the process.
+/// PM terminates the process with [`Termination::Faulted`] instead of replying.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FaultReport {
+    kind: FaultKind,
+    rip: VirtAddr,
+    error_code: u64,
+    address: VirtAddr,
+}
+impl FaultReport {
+    /// `address` is the accessed address for a page fault, and ignored for other faults.
+    #[must_use]
+    pub fn new(kind: FaultKind, rip: VirtAddr, error_code: u64, address: VirtAddr) -> Self {
+        let address = if kind == FaultKind::PageFault {
+            address
+        } else {
+            VirtAddr::zero()
+        };
+
+        Self {
+            kind,
+            rip,
+            error_code,
+            address,
+        }
+    }
+
+    /// Returns `None` if `body` does not hold a valid report.
+    #[must_use]
+    pub fn from_body(body: &Body) -> Option<Self> {
+        let kind = FromPrimitive::from_u64(body.1)?;
+        let rip = VirtAddr::try_new(body.2).ok()?;
+        let address = VirtAddr::try_new(body.4).ok()?;
+
+        Some(Self::new(kind, rip, body.3, address))
+    }
+
+    #[must_use]
+    pub fn to_message(self) -> Message {
+        Message {
+            header: Header::default(),
+            body: Body(
+                Ty::Fault as _,
+                self.kind as _,
+                self.rip.as_u64(),
+                self.error_code,
+                self.address.as_u64(),
+            ),
+        }
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> FaultKind {
+        self.kind
+    }
+
+    /// Returns the address of the faulting instruction, or the next instruction for the traps.
+    #[must_use]
+    pub fn rip(&self) -> VirtAddr {
+        self.rip
+    }
+
+    /// Returns the error code pushed by the processor, or `0` if the exception does not push one.
+    #[must_use]
+    pub fn error_code(&self) -> u64 {
+        self.error_code
+    }
+
+    /// Returns the accessed address of a page fault.
+    #[must_use]
+    pub fn address(&self) -> VirtAddr {
+        self.address
+    }
+}
+
+/// The kinds of the exceptions. The discriminants are the vectors.
+#[derive(Copy, Clone, Debug, FromPrimitive, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FaultKind {
+    DivideError = 0x00,
+    Debug = 0x01,
+    NonMaskableInterrupt = 0x02,
+    Breakpoint = 0x03,
+    Overflow = 0x04,
+    BoundRangeExceeded = 0x05,
+    InvalidOpcode = 0x06,
+    DeviceNotAvailable = 0x07,
+    DoubleFault = 0x08,
+    CoprocessorSegmentOverrun = 0x09,
+    InvalidTss = 0x0a,
+    SegmentNotPresent = 0x0b,
+    StackSegmentFault = 0x0c,
+    GeneralProtection = 0x0d,
+    PageFault = 0x0e,
+    Reserved = 0x0f,
+    X87FloatingPoint = 0x10,
+    AlignmentCheck = 0x11,
+    MachineCheck = 0x12,
+    SimdFloatingPoint = 0x13,
+    Virtualization = 0x14,
+    ControlProtection = 0x15,
+    HypervisorInjection = 0x1c,
+    VmmCommunication = 0x1d,
+    Security = 0x1e,
+}
+
+/// How a process terminated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Termination {
+    /// The process called [`exit`] with the status.
+    Exited(i32),
+    /// The kernel reported the fault of the process to PM.
+    Faulted(FaultKind),
+}
+impl Termination {
+    const EXITED: u64 = 0;
+    const FAULTED: u64 = 1;
+
+    /// Returns `None` if `value` and `kind` do not hold a valid termination. See
+    /// [`Termination::to_fields`].
+    #[must_use]
+    pub fn from_fields(value: u64, kind: u64) -> Option<Self> {
+        match kind {
+            // The status is sign-extended.
+            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            Self::EXITED => Some(Self::Exited(value as i32)),
+            Self::FAULTED => FromPrimitive::from_u64(value).map(Self::Faulted),
+            _ => None,
+        }
+    }
+
+    /// Returns the exit status or the fault, and the kind of the termination.
+    #[must_use]
+    pub fn to_fields(self) -> (u64, u64) {
+        match self {
+            #[allow(clippy::cast_sign_loss)]
+            Self::Exited(status) => (i64::from(status) as u64, Self::EXITED),
+            Self::Faulted(kind) => (kind as u64, Self::FAULTED),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, FromPrimitive, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IrqError {
+    InvalidVector = 1,
+    AlreadyRegistered,
+    NotOwner,
+    PermissionDenied,
+}
+
+/// The access permissions of memory. The bits are those of `PROT_*` in POSIX.
+///
+/// The memory is always readable, so the protection must contain [`Protection::READ`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Protection(u64);
+impl Protection {
+    pub const READ: Self = Self(0b001);
+    pub const WRITE: Self = Self(0b010);
+    pub const EXECUTE: Self = Self(0b100);
+
+    /// Returns `None` if `bits` contains an unknown bit or does not contain [`Protection::READ`].
+    #[must_use]
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        let all = Self::READ | Self::WRITE | Self::EXECUTE;
+
+        (bits & !all.0 == 0 && bits & Self::READ.0 != 0).then_some(Self(bits))
+    }
+
+    #[must_use]
+    pub fn bits(self) -> u64 {
+        self.0
+    }
+
+    #[must_use]
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+impl BitOr for Protection {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, FromPrimitive, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MemoryError {
+    InvalidArguments = 1,
+    InvalidAddress,
+    NoRegion,
+    PermissionDenied,
+}
+
 #[derive(Copy, Clone, Debug, FromPrimitive, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum BitsOrder {
     RedGreenBlueReserved,
@@ -264,4 +1228,47 @@ pub enum Ty {
     TestUserAppFailed,
     Inl,
     Outl,
+    CreateGrant,
+    RevokeGrant,
+    CopyFromGrant,
+    CopyToGrant,
+    Spawn,
+    CreateProcess,
+    Exit,
+    DestroyProcess,
+    Fork,
+    ForkProcess,
+    DuplicateProcess,
+    Exec,
+    ExecProcess,
+    Wait,
+    SetPriority,
+    GetMonotonicTime,
+    Sleep,
+    RegisterIrq,
+    IrqEnable,
+    IrqAck,
+    Fault,
+    Brk,
+    Mmap,
+    Munmap,
+    Mprotect,
+    ResizeHeap,
+    MapAnonymous,
+    UnmapAnonymous,
+    ProtectAnonymous,
+}
+
+// Returns the second field of `reply` if the first field is `0`, or the error in the first field.
+fn result_from_reply<E: FromPrimitive>(reply: &Message) -> Result<u64, E> {
+    match reply.body.0 {
+        0 => Ok(reply.body.1),
+        e => Err(E::from_u64(e).unwrap_or_else(|| panic!("Invalid error code: {}", e))),
+    }
+}
+
+// See the comment on the assertion of the sizes of `usize` and `u64`.
+#[allow(clippy::cast_possible_truncation)]
+fn pid_from_field(field: u64) -> Pid {
+    Pid::new(field as usize)
 }
@@ -22,21 +22,29 @@ impl<T: ?Sized> Kbox<T> {
     }
 }
 impl<T> Kbox<T> {
+    /// # Panics
+    ///
+    /// This method panics if the heap has no room for `x`.
     pub fn new(x: T) -> Self {
+        Self::try_new(x).expect("Failed to allocate memory.")
+    }
+
+    /// Returns `None` if the heap has no room for `x`.
+    pub fn try_new(x: T) -> Option<Self> {
         let p: *mut T = super::alloc(Layout::new::<T>()).cast();
-        let ptr = NonNull::new(p).expect("Failed to allocate memory.");
+        let ptr = NonNull::new(p)?;
 
         // SAFETY: The pointer points to the allocated memory.
         unsafe {
             ptr.as_ptr().write(x);
         }
 
-        Self {
+        Some(Self {
             ptr,
             bytes: size_of::<T>().into(),
             alignment: align_of::<T>().into(),
             _marker: PhantomData,
-        }
+        })
     }
 }
 impl<T: ?Sized> Clone for Kbox<T>
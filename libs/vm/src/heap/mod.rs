@@ -38,6 +38,28 @@ pub unsafe fn dealloc(ptr: *mut u8, layout: Layout) {
     }
 }
 
+/// Returns the number of bytes not allocated in the heap.
+pub(crate) fn free_bytes() -> usize {
+    HEAP.0.lock().free()
+}
+
+/// The allocator for the `alloc` crate. It allocates memory from the same heap as [`alloc`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Allocator;
+unsafe impl GlobalAlloc for Allocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // SAFETY: The caller must uphold the safety requirements.
+        unsafe { HEAP.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: The caller must uphold the safety requirements.
+        unsafe {
+            HEAP.dealloc(ptr, layout);
+        }
+    }
+}
+
 pub(super) fn init() {
     let flags = PageTableFlags::PRESENT
         | PageTableFlags::WRITABLE
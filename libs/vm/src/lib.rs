@@ -1,14 +1,25 @@
 #![no_std]
 #![deny(unsafe_op_in_unsafe_fn)]
 
+extern crate alloc;
+
 pub mod accessor;
 mod heap;
 mod map;
 mod phys;
 
 pub use {
-    heap::{alloc, boxed::Kbox, dealloc},
-    map::{alloc_pages, current_pml4, elf::map_elf, map, map_user, translate, unmap},
+    heap::{alloc, boxed::Kbox, dealloc, Allocator},
+    map::{
+        cow::{
+            copy_on_write, map_shared_pages, protect_user_pages, share_user_pages, SharedPage,
+            TooManySharedFrames,
+        },
+        current_pml4,
+        elf::{map_elf, MappedElf, ZeroFilledPages},
+        free_user_region, is_user_accessible, map, map_user, map_zeroed_page, translate, unmap,
+        unmap_user_pages,
+    },
     phys::frame_allocator,
 };
 use {uefi::service::boot::MemoryDescriptor, x86_64::structures::paging::Size4KiB};
@@ -0,0 +1,321 @@
+use {
+    super::{
+        map, map_page, mapper, phys, table_addr, unmap, unmap_page, NOT_OWNED, RECURSIVE_INDEX,
+    },
+    crate::heap,
+    alloc::collections::BTreeMap,
+    arrayvec::ArrayVec,
+    core::ptr,
+    os_units::Bytes,
+    spinning_top::{const_spinlock, Spinlock},
+    x86_64::{
+        instructions::tlb,
+        structures::paging::{
+            mapper::{MappedFrame, TranslateResult},
+            page::PageRange,
+            page_table::PageTableEntry,
+            FrameAllocator, Mapper, Page, PageSize, PageTable, PageTableFlags, PageTableIndex,
+            PhysFrame, Size4KiB, Translate,
+        },
+        VirtAddr,
+    },
+};
+
+// Set to the pages which were writable before they were shared. Writing to these pages causes a
+// page fault, and the page fault handler makes them writable again by `copy_on_write`.
+const COPY_ON_WRITE: PageTableFlags = PageTableFlags::BIT_9;
+
+// Sharing a frame fails if the heap has less free memory than this, so that counting the shared
+// frames does not exhaust the kernel heap. Inserting an entry allocates a few nodes of the map at
+// most.
+const MIN_FREE_HEAP_BYTES: usize = 4096;
+
+// The number of address spaces which map each frame. The frames mapped by only one address space
+// are not contained.
+static SHARE_COUNTS: Spinlock<BTreeMap<PhysFrame, usize>> = const_spinlock(BTreeMap::new());
+
+/// The error returned by [`share_user_pages`] if the kernel heap has no room to count more shared
+/// frames.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TooManySharedFrames;
+
+/// A page shared by [`share_user_pages`], which is mapped to another address space by
+/// [`map_shared_pages`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SharedPage {
+    page: Page,
+    frame: PhysFrame,
+    flags: PageTableFlags,
+}
+
+/// Shares the pages from `start` in the user region of the current address space, and pushes them
+/// to `pages` until it becomes full. The writable pages become read-only, and the frames of them
+/// are copied when they are written.
+///
+/// This function returns the page to start the next call from, or `None` if all pages are shared.
+///
+/// # Errors
+///
+/// This function returns an error if the kernel heap has no room to count more shared frames. The
+/// pages pushed to `pages` before the error are shared, and must be mapped to the other address
+/// space so that they are unshared when it is freed.
+pub fn share_user_pages<const N: usize>(
+    start: Page,
+    pages: &mut ArrayVec<SharedPage, N>,
+) -> Result<Option<Page>, TooManySharedFrames> {
+    // Prevent others from modifying the page tables.
+    let _mapper = mapper();
+
+    find_map_user_pages(start, |page, entry| {
+        if pages.is_full() {
+            return Some(Ok(page));
+        }
+
+        match share(page, entry) {
+            Ok(shared) => {
+                pages.push(shared);
+
+                None
+            }
+            Err(e) => Some(Err(e)),
+        }
+    })
+    .transpose()
+}
+
+/// Maps the pages shared by [`share_user_pages`] to the current address space.
+///
+/// # Safety
+///
+/// The pages must be shared by [`share_user_pages`], and must not be mapped yet.
+pub unsafe fn map_shared_pages(pages: &[SharedPage]) {
+    // The page tables must be writable so that the copy-on-write pages become writable later.
+    let table_flags =
+        PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+
+    for p in pages {
+        // SAFETY: The caller ensures that the frame is shared.
+        let f = unsafe {
+            mapper().map_to_with_table_flags(
+                p.page,
+                p.frame,
+                p.flags,
+                table_flags,
+                &mut *phys::frame_allocator(),
+            )
+        };
+        let f = f.expect("Failed to map a page.");
+
+        f.flush();
+    }
+}
+
+/// Makes the copy-on-write page containing `addr` in the current address space writable. The frame
+/// is copied if another address space still shares it.
+///
+/// This function returns `false` if the page is not a copy-on-write page.
+///
+/// # Panics
+///
+/// This function panics if no frames are available.
+#[must_use]
+pub fn copy_on_write(addr: VirtAddr) -> bool {
+    let (frame, flags) = match mapper().translate(addr) {
+        TranslateResult::Mapped {
+            frame: MappedFrame::Size4KiB(frame),
+            flags,
+            ..
+        } if flags.contains(COPY_ON_WRITE) => (frame, flags),
+        _ => return false,
+    };
+
+    let page = Page::containing_address(addr);
+    let flags = (flags - COPY_ON_WRITE) | PageTableFlags::WRITABLE;
+
+    if unshare(frame) {
+        let new_frame = phys::frame_allocator().allocate_frame();
+        let new_frame = new_frame.expect("Frame not available.");
+
+        copy_page_to_frame(page, new_frame);
+
+        unmap_page(page);
+
+        // SAFETY: `new_frame` is allocated for this page.
+        unsafe {
+            map_page(page, new_frame, flags);
+        }
+    } else {
+        // SAFETY: Only this address space maps the frame.
+        unsafe {
+            super::update_flags(page, flags);
+        }
+    }
+
+    true
+}
+
+/// Changes the flags of the mapped pages in `pages` of the user region of the current address
+/// space to `flags`. A page whose frame is shared with another address space becomes a
+/// copy-on-write page instead of a writable one.
+pub fn protect_user_pages(pages: PageRange, flags: PageTableFlags) {
+    for page in pages {
+        let (frame, current) = match mapper().translate(page.start_address()) {
+            TranslateResult::Mapped {
+                frame: MappedFrame::Size4KiB(frame),
+                flags,
+                ..
+            } => (frame, flags),
+            _ => continue,
+        };
+
+        let mut flags = flags | (current & NOT_OWNED);
+
+        if flags.contains(PageTableFlags::WRITABLE) && is_shared(frame) {
+            flags = (flags - PageTableFlags::WRITABLE) | COPY_ON_WRITE;
+        }
+
+        // SAFETY: The page is mapped, and a shared frame does not become writable.
+        unsafe {
+            super::update_flags(page, flags);
+        }
+    }
+}
+
+/// Frees `frame` unless another address space shares it.
+pub(super) fn free_frame(frame: PhysFrame) {
+    if !unshare(frame) {
+        phys::frame_allocator().dealloc_frame(frame);
+    }
+}
+
+fn share(page: Page, entry: &mut PageTableEntry) -> Result<SharedPage, TooManySharedFrames> {
+    let frame = entry
+        .frame()
+        .expect("The page is not mapped to a 4 KiB frame.");
+    let mut flags = entry.flags();
+
+    if !flags.contains(NOT_OWNED) {
+        if heap::free_bytes() < MIN_FREE_HEAP_BYTES {
+            return Err(TooManySharedFrames);
+        }
+
+        if flags.contains(PageTableFlags::WRITABLE) {
+            flags = (flags - PageTableFlags::WRITABLE) | COPY_ON_WRITE;
+
+            entry.set_flags(flags);
+            tlb::flush(page.start_address());
+        }
+
+        *SHARE_COUNTS.lock().entry(frame).or_insert(1) += 1;
+    }
+
+    Ok(SharedPage { page, frame, flags })
+}
+
+fn is_shared(frame: PhysFrame) -> bool {
+    SHARE_COUNTS.lock().contains_key(&frame)
+}
+
+// Decrements the share count of `frame`, and returns `true` if another address space still maps it.
+fn unshare(frame: PhysFrame) -> bool {
+    let mut counts = SHARE_COUNTS.lock();
+
+    match counts.get_mut(&frame) {
+        Some(count) if *count > 2 => {
+            *count -= 1;
+
+            true
+        }
+        Some(_) => {
+            counts.remove(&frame);
+
+            true
+        }
+        None => false,
+    }
+}
+
+fn copy_page_to_frame(page: Page, frame: PhysFrame) {
+    let bytes = Bytes::new(Size4KiB::SIZE.try_into().unwrap());
+
+    // SAFETY: `frame` is not used by anyone.
+    let dst = unsafe {
+        map(
+            frame.start_address(),
+            bytes,
+            PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+        )
+    };
+
+    // SAFETY: Both pages are mapped, and they do not overlap.
+    unsafe {
+        ptr::copy_nonoverlapping(
+            page.start_address().as_ptr::<u8>(),
+            dst.as_mut_ptr::<u8>(),
+            bytes.as_usize(),
+        );
+    }
+
+    unmap(dst, bytes);
+}
+
+// Calls `f` with each mapped page from `start` in the user region of the current address space and
+// its page table entry, and returns the first `Some` returned by `f`.
+fn find_map_user_pages<T>(
+    start: Page,
+    mut f: impl FnMut(Page, &mut PageTableEntry) -> Option<T>,
+) -> Option<T> {
+    let start = [
+        start.p4_index(),
+        start.p3_index(),
+        start.p2_index(),
+        start.p1_index(),
+    ]
+    .map(u16::from);
+
+    find_map_pages_in_table(&[], &start, true, &mut f)
+}
+
+// `indices` is the indices from PML4 to the page table. If `from_start` is `true`, the pages before
+// `start` are skipped.
+fn find_map_pages_in_table<T>(
+    indices: &[u16],
+    start: &[u16; 4],
+    from_start: bool,
+    f: &mut impl FnMut(Page, &mut PageTableEntry) -> Option<T>,
+) -> Option<T> {
+    let level = indices.len();
+
+    let first = if from_start { start[level] } else { 0 };
+    let end = if level == 0 { RECURSIVE_INDEX } else { 512 };
+
+    // SAFETY: The page table exists because the entry of the upper table is used.
+    let table = unsafe { &mut *table_addr(indices).as_mut_ptr::<PageTable>() };
+
+    for i in first..end {
+        let entry = &mut table[usize::from(i)];
+
+        if entry.is_unused() || entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+            continue;
+        }
+
+        let mut next = [0; 4];
+
+        next[..level].copy_from_slice(indices);
+        next[level] = i;
+
+        let r = if level == 3 {
+            let [p4, p3, p2, p1] = next.map(PageTableIndex::new);
+
+            f(Page::from_page_table_indices(p4, p3, p2, p1), entry)
+        } else {
+            find_map_pages_in_table(&next[..=level], start, from_start && i == first, f)
+        };
+
+        if r.is_some() {
+            return r;
+        }
+    }
+
+    None
+}
@@ -1,5 +1,7 @@
 use {
+    super::phys,
     aligned_ptr::ptr,
+    arrayvec::ArrayVec,
     core::convert::TryInto,
     elfloader::{
         ElfBinary, ElfLoader, ElfLoaderErr, Flags, LoadableHeaders, ProgramHeader, RelocationEntry,
@@ -11,65 +13,172 @@ use {
     },
 };
 
+const MAX_ZERO_FILLED_SEGMENTS: usize = 8;
+
+/// The executable file mapped by [`map_elf`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MappedElf {
+    entry: VirtAddr,
+    zero_filled: ArrayVec<ZeroFilledPages, MAX_ZERO_FILLED_SEGMENTS>,
+    end: Page,
+}
+impl MappedElf {
+    #[must_use]
+    pub fn entry(&self) -> VirtAddr {
+        self.entry
+    }
+
+    /// Returns the pages of the loadable segments which are not backed by the file.
+    #[must_use]
+    pub fn zero_filled_pages(&self) -> &[ZeroFilledPages] {
+        &self.zero_filled
+    }
+
+    /// Returns the page next to the last page of the loadable segments.
+    #[must_use]
+    pub fn end(&self) -> Page {
+        self.end
+    }
+}
+
+/// The pages of a loadable segment which are not backed by the file, such as `.bss`. [`map_elf`]
+/// does not map them, so they must be mapped to zeroed frames when they are accessed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ZeroFilledPages {
+    pages: PageRange,
+    flags: PageTableFlags,
+}
+impl ZeroFilledPages {
+    #[must_use]
+    pub fn pages(&self) -> PageRange {
+        self.pages
+    }
+
+    #[must_use]
+    pub fn flags(&self) -> PageTableFlags {
+        self.flags
+    }
+}
+
+/// Maps the pages of the given ELF binary which are backed by the file to the current address
+/// space. The other pages are not mapped, and they are returned in [`MappedElf`].
+///
 /// # Safety
 ///
-/// This function maps the given ELF binary to the current address space.
+/// The user region of the current address space must be unused.
+///
+/// This function returns `None` if `binary` is not a valid ELF file, it has too many segments which
+/// are not backed by the file, or no frames are available. The pages mapped before the failure
+/// remain mapped, so the caller must free the user region.
 #[must_use]
 #[allow(clippy::module_name_repetitions)]
-pub unsafe fn map_elf(binary: &[u8]) -> VirtAddr {
-    let elf = ElfBinary::new(binary);
-    let elf = elf.expect("Not an ELF file.");
+pub unsafe fn map_elf(binary: &[u8]) -> Option<MappedElf> {
+    let elf = ElfBinary::new(binary).ok()?;
 
-    let r = elf.load(&mut Loader);
-    r.expect("Failed to map a ELF file.");
+    let mut loader = Loader::default();
 
-    VirtAddr::new(elf.entry_point())
+    elf.load(&mut loader).ok()?;
+
+    Some(MappedElf {
+        entry: VirtAddr::try_new(elf.entry_point()).ok()?,
+        zero_filled: loader.zero_filled,
+        end: loader.end,
+    })
 }
 
-struct Loader;
+struct Loader {
+    zero_filled: ArrayVec<ZeroFilledPages, MAX_ZERO_FILLED_SEGMENTS>,
+    end: Page,
+}
 impl Loader {
-    fn allocate_for_header(header: ProgramHeader<'_>) {
-        if header.virtual_addr() != 0 {
+    fn allocate_for_header(&mut self, header: ProgramHeader<'_>) -> Result<(), ElfLoaderErr> {
+        if header.virtual_addr() == 0 {
+            Ok(())
+        } else {
             // SAFETY: Checked.
-            unsafe {
-                Self::allocate_for_header_unchecked(header);
-            }
+            unsafe { self.allocate_for_header_unchecked(header) }
         }
     }
 
     /// # Safety
     ///
     /// `header.virtual_addr()` must not be 0.
-    unsafe fn allocate_for_header_unchecked(header: ProgramHeader<'_>) {
-        let page_range = Self::page_range_from_header(header);
+    unsafe fn allocate_for_header_unchecked(
+        &mut self,
+        header: ProgramHeader<'_>,
+    ) -> Result<(), ElfLoaderErr> {
+        let file_pages = Self::page_range_from_vaddr_and_len(
+            header.virtual_addr(),
+            to_usize(header.file_size())?,
+        )?;
+        let all_pages = Self::page_range_from_header(header)?;
 
         let flags =
             PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
 
+        let frames = phys::frame_allocator().alloc(super::num_of_page_in_range(file_pages));
+        let frames = frames.ok_or(ElfLoaderErr::OutOfMemory)?;
+
+        // SAFETY: The frames are allocated for the pages.
+        unsafe {
+            super::map_range(file_pages, frames, flags);
+        }
+
+        // The bytes after the file data in the last page belong to `.bss`, so they must be zero.
+        // SAFETY: The pages are just mapped as writable.
         unsafe {
-            super::map_page_range_to_unused_frame_range(page_range, flags);
+            core::ptr::write_bytes(
+                file_pages.start.start_address().as_mut_ptr::<u8>(),
+                0,
+                super::num_of_page_in_range(file_pages)
+                    .as_bytes()
+                    .as_usize(),
+            );
         }
+
+        if file_pages.end < all_pages.end {
+            let r = self.zero_filled.try_push(ZeroFilledPages {
+                pages: PageRange {
+                    start: file_pages.end,
+                    end: all_pages.end,
+                },
+                flags: Self::elf_flags_to_page_table_flags(header.flags()),
+            });
+            r.map_err(|_| ElfLoaderErr::from("Too many segments not backed by the file."))?;
+        }
+
+        self.end = self.end.max(all_pages.end);
+
+        Ok(())
     }
 
-    fn page_range_from_header<S: PageSize>(header: ProgramHeader<'_>) -> PageRange<S> {
-        Self::page_range_from_vaddr_and_len(
-            header.virtual_addr(),
-            header.mem_size().try_into().unwrap(),
-        )
+    fn page_range_from_header<S: PageSize>(
+        header: ProgramHeader<'_>,
+    ) -> Result<PageRange<S>, ElfLoaderErr> {
+        Self::page_range_from_vaddr_and_len(header.virtual_addr(), to_usize(header.mem_size())?)
     }
 
-    fn page_range_from_vaddr_and_len<S: PageSize>(base: VAddr, len: usize) -> PageRange<S> {
-        let start = VirtAddr::new(base);
+    fn page_range_from_vaddr_and_len<S: PageSize>(
+        base: VAddr,
+        len: usize,
+    ) -> Result<PageRange<S>, ElfLoaderErr> {
+        let invalid = |_| ElfLoaderErr::from("Invalid segment address.");
+
+        let start = VirtAddr::try_new(base).map_err(invalid)?;
 
-        let end = start + len;
+        let end = start
+            .as_u64()
+            .checked_add(len.try_into().unwrap())
+            .and_then(|end| VirtAddr::try_new(end).ok())
+            .ok_or(ElfLoaderErr::from("Invalid segment size."))?;
         let end = end.align_up(S::SIZE);
 
         let start = Page::from_start_address(start);
-        let start = start.expect("The address is not page-aligned.");
+        let start = start.map_err(|_| ElfLoaderErr::from("The address is not page-aligned."))?;
 
         let end = Page::containing_address(end);
 
-        PageRange { start, end }
+        Ok(PageRange { start, end })
     }
 
     unsafe fn update_flags(page_range: PageRange, flags: Flags) {
@@ -92,26 +201,38 @@ impl Loader {
         page_table_flags
     }
 }
+impl Default for Loader {
+    fn default() -> Self {
+        Self {
+            zero_filled: ArrayVec::new(),
+            end: Page::containing_address(VirtAddr::zero()),
+        }
+    }
+}
 impl ElfLoader for Loader {
     fn allocate(&mut self, load_headers: LoadableHeaders<'_, '_>) -> Result<(), ElfLoaderErr> {
         for header in load_headers {
-            Self::allocate_for_header(header);
+            self.allocate_for_header(header)?;
         }
 
         Ok(())
     }
 
     fn load(&mut self, flags: Flags, base: VAddr, region: &[u8]) -> Result<(), ElfLoaderErr> {
-        let base = VirtAddr::new(base);
+        let page_range = Self::page_range_from_vaddr_and_len(base, region.len())?;
 
-        assert!(!base.is_null());
+        if base == 0 {
+            return Err("The segment is at the null address.".into());
+        }
 
         unsafe {
-            ptr::copy_nonoverlapping(region.as_ptr(), base.as_mut_ptr(), region.len());
+            ptr::copy_nonoverlapping(
+                region.as_ptr(),
+                page_range.start.start_address().as_mut_ptr(),
+                region.len(),
+            );
         }
 
-        let page_range = Self::page_range_from_vaddr_and_len(base.as_u64(), region.len());
-
         unsafe {
             Self::update_flags(page_range, flags);
         }
@@ -120,11 +241,11 @@ impl ElfLoader for Loader {
     }
 
     fn relocate(&mut self, _entry: RelocationEntry) -> Result<(), ElfLoaderErr> {
-        todo!()
+        Err(ElfLoaderErr::UnsupportedRelocationEntry)
     }
 
     fn make_readonly(&mut self, base: VAddr, size: usize) -> Result<(), ElfLoaderErr> {
-        let page_range = Self::page_range_from_vaddr_and_len(base, size);
+        let page_range = Self::page_range_from_vaddr_and_len(base, size)?;
 
         let flags = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
 
@@ -135,3 +256,8 @@ impl ElfLoader for Loader {
         Ok(())
     }
 }
+
+fn to_usize(n: u64) -> Result<usize, ElfLoaderErr> {
+    n.try_into()
+        .map_err(|_| ElfLoaderErr::from("Invalid segment size."))
+}
@@ -8,17 +8,27 @@ use {
     spinning_top::{MappedSpinlockGuard, Spinlock, SpinlockGuard},
     x86_64::{
         structures::paging::{
-            frame::PhysFrameRange, page::PageRange, Mapper, Page, PageSize, PageTable,
-            PageTableFlags, PhysFrame, RecursivePageTable, Size4KiB, Translate,
+            frame::PhysFrameRange,
+            mapper::{MappedFrame, TranslateResult},
+            page::PageRange,
+            FrameAllocator, Mapper, Page, PageSize, PageTable, PageTableFlags, PageTableIndex,
+            PhysFrame, RecursivePageTable, Size4KiB, Translate,
         },
         PhysAddr, VirtAddr,
     },
 };
 
+pub(super) mod cow;
 pub(super) mod elf;
 
 static PML4: OnceCell<Spinlock<RecursivePageTable<'_>>> = OnceCell::uninit();
 
+const RECURSIVE_INDEX: u16 = 510;
+
+// Set to the pages mapped by `map_user`. The frames of these pages are not allocated for the
+// address space, so they must not be freed by `free_user_region`.
+const NOT_OWNED: PageTableFlags = PageTableFlags::BIT_10;
+
 /// # Safety
 ///
 /// Refer to [`Mapper::map_to`].
@@ -33,29 +43,68 @@ pub unsafe fn map(p: PhysAddr, b: Bytes, flags: PageTableFlags) -> VirtAddr {
 #[must_use]
 #[allow(clippy::module_name_repetitions)]
 pub unsafe fn map_user(p: PhysAddr, b: Bytes, flags: PageTableFlags) -> VirtAddr {
-    map_in_region(p, b, flags, predefined_mmap::user())
+    map_in_region(p, b, flags | NOT_OWNED, predefined_mmap::user_dma())
 }
 
 pub fn unmap(v: VirtAddr, b: Bytes) {
     unmap_range(to_page_range(v, b.as_num_of_pages()));
 }
 
+/// Maps `page` of the current address space to a zeroed frame with `flags`. Returns `false` if no
+/// frames are available for the page or its page tables.
+///
+/// # Safety
+///
+/// `page` must be in the user region and must not be mapped.
 #[must_use]
-pub fn alloc_pages(n: NumOfPages, flags: PageTableFlags) -> Option<PageRange> {
-    let all_range = PageRange {
-        start: Page::containing_address(VirtAddr::zero()),
-        end: predefined_mmap::kernel().start,
+pub unsafe fn map_zeroed_page(page: Page, flags: PageTableFlags) -> bool {
+    // The page tables must be writable so that the read-only pages in the same tables can become
+    // writable later.
+    let table_flags =
+        PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+
+    let frame = match phys::frame_allocator().allocate_frame() {
+        Some(frame) => frame,
+        None => return false,
     };
 
-    let page_range = find_unused_page_range_from_range(n, all_range)?;
+    // SAFETY: The caller ensures that the page is unused, and `frame` is allocated for it.
+    let f = unsafe {
+        mapper().map_to_with_table_flags(
+            page,
+            frame,
+            flags | PageTableFlags::WRITABLE,
+            table_flags,
+            &mut *phys::frame_allocator(),
+        )
+    };
+    let f = match f {
+        Ok(f) => f,
+        Err(_) => {
+            // Allocating an intermediate page table failed. `frame` is not mapped anywhere.
+            phys::frame_allocator().dealloc(frame);
 
-    let frame_range = phys::frame_allocator().alloc(n)?;
+            return false;
+        }
+    };
+
+    f.flush();
 
+    // SAFETY: The page is mapped and writable.
     unsafe {
-        map_range(page_range, frame_range, flags);
+        core::ptr::write_bytes(
+            page.start_address().as_mut_ptr::<u8>(),
+            0,
+            Size4KiB::SIZE.try_into().unwrap(),
+        );
+    }
+
+    // SAFETY: The page is mapped.
+    unsafe {
+        update_flags(page, flags);
     }
 
-    Some(page_range)
+    true
 }
 
 #[must_use]
@@ -68,6 +117,105 @@ pub fn translate(addr: VirtAddr) -> Option<PhysAddr> {
     mapper().translate_addr(addr)
 }
 
+/// Returns `true` if `addr` is mapped to a page that is accessible from the user mode in the current
+/// address space. If `writable` is `true`, the page must also be writable.
+///
+/// The processor checks the flags of the entries of all levels, so this function checks them too.
+#[must_use]
+pub fn is_user_accessible(addr: VirtAddr, writable: bool) -> bool {
+    let mut required = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+
+    if writable {
+        required |= PageTableFlags::WRITABLE;
+    }
+
+    // Prevent others from modifying the page tables.
+    let _mapper = mapper();
+
+    let indices = [
+        addr.p4_index(),
+        addr.p3_index(),
+        addr.p2_index(),
+        addr.p1_index(),
+    ]
+    .map(u16::from);
+
+    // The recursive entry is not a user page.
+    if indices[0] == RECURSIVE_INDEX {
+        return false;
+    }
+
+    for level in 0..indices.len() {
+        // SAFETY: The entries of the upper tables are present, so the table exists.
+        let table = unsafe { &*table_addr(&indices[..level]).as_ptr::<PageTable>() };
+
+        let flags = table[usize::from(indices[level])].flags();
+
+        if !flags.contains(required) {
+            return false;
+        }
+
+        if flags.contains(PageTableFlags::HUGE_PAGE) {
+            // The PML4 entries do not map huge pages.
+            return level > 0;
+        }
+    }
+
+    true
+}
+
+/// Unmaps all pages in the user region of the current address space, and frees the frames of these
+/// pages and the page tables. The frames of the pages mapped by [`map_user`] and the frames shared
+/// with other address spaces are not freed.
+///
+/// # Safety
+///
+/// The user region of the current address space must not be used hereafter.
+pub unsafe fn free_user_region() {
+    let mut pml4 = pml4();
+
+    for i in 0..RECURSIVE_INDEX {
+        let entry = &mut pml4[usize::from(i)];
+
+        if !entry.is_unused() {
+            // SAFETY: The entry is used, and the caller ensures that the region is not used.
+            unsafe {
+                free_table(&[i], 3);
+            }
+
+            phys::frame_allocator().dealloc(entry.frame().unwrap());
+
+            entry.set_unused();
+        }
+    }
+}
+
+/// Unmaps the mapped pages in `pages` of the user region of the current address space, and frees
+/// their frames. The frames of the pages mapped by [`map_user`] and the frames shared with other
+/// address spaces are not freed.
+///
+/// # Safety
+///
+/// The pages must not be used hereafter.
+pub unsafe fn unmap_user_pages(pages: PageRange) {
+    for page in pages {
+        let (frame, flags) = match mapper().translate(page.start_address()) {
+            TranslateResult::Mapped {
+                frame: MappedFrame::Size4KiB(frame),
+                flags,
+                ..
+            } => (frame, flags),
+            _ => continue,
+        };
+
+        unmap_page(page);
+
+        if !flags.contains(NOT_OWNED) {
+            cow::free_frame(frame);
+        }
+    }
+}
+
 /// # Safety
 ///
 /// Hereafter,
@@ -161,6 +309,61 @@ fn unmap_all_user_regions() {
     }
 }
 
+/// Frees the frames of the pages and page tables mapped by the page table specified by `indices`,
+/// which is the indices from PML4 to the table. `level` is the level of the table.
+///
+/// # Safety
+///
+/// The page table must exist, and the region mapped by it must not be used hereafter.
+unsafe fn free_table(indices: &[u16], level: u8) {
+    // SAFETY: The caller ensures that the page table exists.
+    let table = unsafe { &mut *table_addr(indices).as_mut_ptr::<PageTable>() };
+
+    for (i, entry) in table.iter_mut().enumerate() {
+        if entry.is_unused() {
+            continue;
+        }
+
+        let flags = entry.flags();
+
+        if level > 1 && !flags.contains(PageTableFlags::HUGE_PAGE) {
+            let mut next = [0; 4];
+
+            next[..indices.len()].copy_from_slice(indices);
+            next[indices.len()] = i.try_into().unwrap();
+
+            // SAFETY: The entry is used, and the caller ensures that the region is not used.
+            unsafe {
+                free_table(&next[..=indices.len()], level - 1);
+            }
+
+            phys::frame_allocator().dealloc(entry.frame().unwrap());
+        } else if !flags.contains(NOT_OWNED) {
+            // A huge page in a level 2 or 3 table covers 512 or 512 * 512 frames.
+            let start = PhysFrame::containing_address(entry.addr());
+            let n = 512_u64.pow((level - 1).into());
+
+            for frame in PhysFrame::range(start, start + n) {
+                cow::free_frame(frame);
+            }
+        }
+
+        entry.set_unused();
+    }
+}
+
+// Returns the address to access the page table specified by `indices` through the recursive
+// mapping.
+fn table_addr(indices: &[u16]) -> VirtAddr {
+    let mut table_indices = [RECURSIVE_INDEX; 4];
+
+    table_indices[4 - indices.len()..].copy_from_slice(indices);
+
+    let [p4, p3, p2, p1] = table_indices.map(PageTableIndex::new);
+
+    Page::<Size4KiB>::from_page_table_indices(p4, p3, p2, p1).start_address()
+}
+
 fn pml4<'a>() -> MappedSpinlockGuard<'a, PageTable> {
     SpinlockGuard::map(mapper(), RecursivePageTable::level_4_table)
 }
@@ -168,9 +371,10 @@ fn pml4<'a>() -> MappedSpinlockGuard<'a, PageTable> {
 fn mapper<'a>() -> SpinlockGuard<'a, RecursivePageTable<'static>> {
     let pml4 = PML4.try_get();
     let pml4 = pml4.expect("`pml4::init` is not called.");
-    let pml4 = pml4.try_lock();
 
-    pml4.expect("Failed to acquire the lock of kernel's PML4.")
+    // The other processors may hold the lock while they edit the kernel's page tables, which are
+    // shared by all processors.
+    pml4.lock()
 }
 
 unsafe fn try_map_frame_range_from_page_range(
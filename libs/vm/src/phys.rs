@@ -13,9 +13,8 @@ static FRAME_ALLOCATOR: Lazy<Spinlock<FrameAllocator<Size4KiB, REASONABLE_NUM_DE
 
 pub fn frame_allocator<'a>(
 ) -> SpinlockGuard<'a, FrameAllocator<Size4KiB, REASONABLE_NUM_DESCRIPTORS>> {
-    let f = FRAME_ALLOCATOR.try_lock();
-
-    f.expect("Failed to acquire the lock of the frame allocator.")
+    // The other processors may hold the lock.
+    FRAME_ALLOCATOR.lock()
 }
 
 pub(super) fn init(mmap: &[MemoryDescriptor]) {
@@ -3,10 +3,39 @@
 
 extern crate init as _;
 
+use {
+    pid::{predefined, Pid},
+    syscalls::Priority,
+};
+
+// The PIDs of these processes are predefined, so they must be spawned in this order.
+const SERVERS: [(&str, Pid, Priority); 3] = [
+    ("tty", predefined::TTY, Priority::DRIVER),
+    ("vfs", predefined::VFS, Priority::SERVER),
+    ("xhci", predefined::XHCI, Priority::DRIVER),
+];
+
 #[no_mangle]
 fn main() -> ! {
+    for (name, pid, priority) in SERVERS {
+        spawn(name, pid);
+
+        // The spawned processes have the priority of applications.
+        let r = syscalls::set_priority(pid, priority);
+        r.unwrap_or_else(|e| panic!("Failed to set the priority of {}: {:?}", name, e));
+    }
+
+    #[cfg(feature = "test_on_qemu")]
+    spawn("test_user_app", predefined::TEST_USER_APP);
+
     loop {
         syscalls::noop();
         syscalls::write("INIT SERVER.\n");
     }
 }
+
+fn spawn(name: &str, expected_pid: Pid) {
+    let pid = syscalls::spawn(name);
+
+    assert_eq!(pid, Ok(expected_pid), "Failed to spawn {}.", name);
+}
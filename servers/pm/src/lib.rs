@@ -2,12 +2,155 @@
 
 extern crate rlibc as _;
 
+// `process::manager` keeps the processes and the children of each of them in the heap.
+#[global_allocator]
+static ALLOCATOR: allocator::Allocator = allocator::Allocator;
+
 mod process;
 
+use {
+    core::convert::TryInto,
+    ipc::{
+        message::{Body, Header},
+        Message, ReceiveFrom,
+    },
+    num_traits::FromPrimitive,
+    pid::Pid,
+    process::manager::WaitResult,
+    syscalls::{ExecError, ExecRequest, FaultReport, ProcessName, Termination},
+};
+
 pub fn init() {
     process::manager::init();
 }
 
+pub fn main_loop() -> ! {
+    loop {
+        loop_iteration();
+    }
+}
+
+fn loop_iteration() {
+    let message = ipc::receive(ReceiveFrom::Any);
+
+    match FromPrimitive::from_u64(message.body.0) {
+        Some(syscalls::Ty::Spawn) => handle_spawn(&message),
+        Some(syscalls::Ty::Exit) => handle_exit(&message),
+        Some(syscalls::Ty::Fork) => handle_fork(&message),
+        Some(syscalls::Ty::Exec) => handle_exec(&message),
+        Some(syscalls::Ty::Wait) => handle_wait(&message),
+        Some(syscalls::Ty::Fault) => handle_fault(&message),
+        _ => {}
+    }
+}
+
+fn handle_spawn(message: &Message) {
+    let name = ProcessName::from_body(&message.body);
+
+    let body = match process::manager::spawn(name, message.header.sender_pid) {
+        Ok(pid) => Body(0, pid.as_usize().try_into().unwrap(), 0, 0, 0),
+        Err(e) => Body(e as _, 0, 0, 0, 0),
+    };
+
+    ipc::send(
+        message.header.sender_pid,
+        Message {
+            header: Header::default(),
+            body,
+        },
+    );
+}
+
+fn handle_fork(message: &Message) {
+    let body = match process::manager::fork(message.header.sender_pid) {
+        Ok(pid) => Body(0, pid.as_usize().try_into().unwrap(), 0, 0, 0),
+        Err(e) => Body(e as _, 0, 0, 0, 0),
+    };
+
+    // The child process has already received the reply from the kernel.
+    ipc::send(
+        message.header.sender_pid,
+        Message {
+            header: Header::default(),
+            body,
+        },
+    );
+}
+
+// On success, the process starts the new image, so PM does not reply to it.
+fn handle_exec(message: &Message) {
+    let r = ExecRequest::from_body(&message.body)
+        .ok_or(ExecError::InvalidArguments)
+        .and_then(|request| syscalls::exec_process(message.header.sender_pid, request));
+
+    if let Err(e) = r {
+        ipc::send(
+            message.header.sender_pid,
+            Message {
+                header: Header::default(),
+                body: Body(e as _, 0, 0, 0, 0),
+            },
+        );
+    }
+}
+
+// The process is destroyed or becomes a zombie, so PM does not reply to it.
+fn handle_exit(message: &Message) {
+    // `syscalls::exit` sign-extends the status.
+    #[allow(clippy::cast_possible_truncation)]
+    let status = message.body.1 as i32;
+
+    exit(message.header.sender_pid, Termination::Exited(status));
+}
+
+// The kernel sends the report on behalf of the faulting process. The process is terminated without
+// calling `exit`.
+fn handle_fault(message: &Message) {
+    if let Some(report) = FaultReport::from_body(&message.body) {
+        exit(
+            message.header.sender_pid,
+            Termination::Faulted(report.kind()),
+        );
+    }
+}
+
+fn exit(pid: Pid, termination: Termination) {
+    if let Some((parent, r)) = process::manager::exit(pid, termination) {
+        reply_to_wait(parent, r);
+    }
+}
+
+// If no child process has terminated yet, PM replies when one of them terminates.
+fn handle_wait(message: &Message) {
+    // `0` means any child process.
+    let pid = (message.body.1 != 0).then(|| Pid::new(message.body.1.try_into().unwrap()));
+    let no_hang = message.body.2 != 0;
+
+    if let Some(r) = process::manager::wait(message.header.sender_pid, pid, no_hang) {
+        reply_to_wait(message.header.sender_pid, r);
+    }
+}
+
+fn reply_to_wait(to: Pid, r: WaitResult) {
+    let body = match r {
+        Ok(Some((pid, termination))) => {
+            let (value, kind) = termination.to_fields();
+
+            Body(0, pid.as_usize().try_into().unwrap(), value, kind, 0)
+        }
+        Ok(None) => Body(0, 0, 0, 0, 0),
+        Err(e) => Body(e as _, 0, 0, 0, 0),
+    };
+
+    ipc::send(
+        to,
+        Message {
+            header: Header::default(),
+            body,
+        },
+    );
+}
+
 #[panic_handler]
 fn panic(_: &core::panic::PanicInfo<'_>) -> ! {
     loop {
@@ -7,7 +7,5 @@ extern crate pm as _;
 fn main() -> ! {
     pm::init();
 
-    loop {
-        syscalls::write("PM server.\n");
-    }
+    pm::main_loop();
 }
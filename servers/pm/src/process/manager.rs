@@ -1,25 +1,85 @@
 use {
-    super::Process,
-    config::MAX_PID,
-    core::convert::TryInto,
+    super::{Process, State},
+    allocator::{BTreeMap, Vec},
+    core::{convert::TryInto, mem},
     ipc::message::{Body, Header, Message},
     pid::{predefined, Pid},
     spinning_top::{const_spinlock, Spinlock, SpinlockGuard},
+    syscalls::{ForkError, ProcessName, SpawnError, Termination, WaitError},
 };
 
-static MANAGER: Spinlock<Manager<MAX_PID>> = const_spinlock(Manager::new());
+const PROC_INFO: u64 = 1;
+const END_MSG: u64 = 2;
+
+static MANAGER: Spinlock<Manager> = const_spinlock(Manager::new());
+
+/// The reply to `wait`. `Ok(None)` means no child process has terminated yet.
+pub(crate) type WaitResult = Result<Option<(Pid, Termination)>, WaitError>;
 
 pub(crate) fn init() {
-    const PROC_INFO: u64 = 1;
-    const END_MSG: u64 = 2;
     while let Some(message) = syscalls::pm_syncs_with_kernel() {
         let pid = Pid::new(message.body.1.try_into().unwrap());
 
-        let process = Process::new(pid);
+        let process = Process::new(pid, None);
 
         lock().add(process);
+    }
+}
 
-        syscalls::write("Hey\n");
+pub(crate) fn spawn(name: ProcessName, parent: Pid) -> Result<Pid, SpawnError> {
+    let pid = syscalls::create_process(name)?;
+
+    lock().add(Process::new(pid, Some(parent)));
+
+    // VFS receives the list of processes on its initialization. It does not receive any messages
+    // from PM after that, so processes spawned later are not sent.
+    if pid == predefined::VFS {
+        send_processes_to_vfs();
+    }
+
+    Ok(pid)
+}
+
+pub(crate) fn fork(parent: Pid) -> Result<Pid, ForkError> {
+    let pid = syscalls::fork_process(parent)?;
+
+    lock().add(Process::new(pid, Some(parent)));
+
+    Ok(pid)
+}
+
+/// Terminates `pid` with `termination`. The kernel process is destroyed immediately, and PM keeps
+/// only the termination as a zombie until the parent reaps it. The kernel does not reuse the PID because
+/// it changes the generation of the slot.
+///
+/// This function returns the parent and the reply to it if the parent is waiting for `pid`. It does
+/// nothing if PM does not know `pid`.
+pub(crate) fn exit(pid: Pid, termination: Termination) -> Option<(Pid, WaitResult)> {
+    let waiting_parent = lock().exit(pid, termination)?;
+
+    destroy(pid);
+
+    waiting_parent.map(|parent| (parent, Ok(Some((pid, termination)))))
+}
+
+/// Reaps the child process `pid`, or any child process if `pid` is `None`, of `parent`.
+///
+/// This function returns `None` if `parent` must wait for a child process to terminate. If
+/// `no_hang` is `true`, it returns `Some(Ok(None))` instead.
+pub(crate) fn wait(parent: Pid, pid: Option<Pid>, no_hang: bool) -> Option<WaitResult> {
+    lock().wait(parent, pid, no_hang)
+}
+
+fn destroy(pid: Pid) {
+    let r = syscalls::destroy_process(pid);
+    r.expect("Failed to destroy a process.");
+}
+
+fn send_processes_to_vfs() {
+    // Do not hold the lock while sending messages.
+    let pids = lock().pids();
+
+    for pid in pids {
         ipc::send(
             predefined::VFS,
             Message {
@@ -38,32 +98,112 @@ pub(crate) fn init() {
     );
 }
 
-fn lock<'a>() -> SpinlockGuard<'a, Manager<MAX_PID>> {
+fn lock<'a>() -> SpinlockGuard<'a, Manager> {
     MANAGER.try_lock().expect("Failed to lock `MANAGER`")
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-struct Manager<const N: usize> {
-    processes: [Option<Process>; N],
+struct Manager {
+    processes: BTreeMap<Pid, Process>,
 }
-impl<const N: usize> Manager<N> {
+impl Manager {
     const fn new() -> Self {
-        const NULL_PROC: Option<Process> = None;
-
         Self {
-            processes: [NULL_PROC; N],
+            processes: BTreeMap::new(),
         }
     }
 
     fn add(&mut self, process: Process) {
         let pid = process.pid;
 
-        let slot = &mut self.processes[pid.as_usize()];
+        if let Some(parent) = process.parent.and_then(|p| self.processes.get_mut(&p)) {
+            parent.children.push(pid);
+        }
 
-        if slot.is_some() {
+        if self.processes.insert(pid, process).is_some() {
             panic!("Duplicated proces with {}", pid);
+        }
+    }
+
+    fn remove(&mut self, pid: Pid) -> Option<Process> {
+        self.processes.remove(&pid)
+    }
+
+    // Returns `None` if there is no such process. Otherwise, returns the parent of `pid` if it is
+    // waiting for `pid`.
+    fn exit(&mut self, pid: Pid, termination: Termination) -> Option<Option<Pid>> {
+        let process = self.processes.get_mut(&pid)?;
+
+        let children = mem::take(&mut process.children);
+        let parent = process.parent;
+
+        // Nobody reaps the orphans. Their kernel processes are already destroyed.
+        for child in children {
+            if let Some(child_process) = self.processes.get_mut(&child) {
+                child_process.parent = None;
+
+                if let State::Zombie(_) = child_process.state {
+                    self.remove(child);
+                }
+            }
+        }
+
+        match parent.and_then(|p| self.processes.get_mut(&p)) {
+            Some(parent_process) if parent_process.is_waiting_for(pid) => {
+                parent_process.state = State::Alive;
+                parent_process.remove_child(pid);
+
+                self.remove(pid);
+
+                Some(parent)
+            }
+            Some(_) => {
+                if let Some(process) = self.processes.get_mut(&pid) {
+                    process.state = State::Zombie(termination);
+                }
+
+                Some(None)
+            }
+            None => {
+                self.remove(pid);
+
+                Some(None)
+            }
+        }
+    }
+
+    fn wait(&mut self, parent: Pid, pid: Option<Pid>, no_hang: bool) -> Option<WaitResult> {
+        let parent_process = match self.processes.get(&parent) {
+            Some(p) if p.has_child(pid) => p,
+            _ => return Some(Err(WaitError::NoChildren)),
+        };
+
+        let zombie = parent_process
+            .children
+            .iter()
+            .filter(|c| pid.is_none_or(|pid| pid == **c))
+            .find_map(|c| match self.processes.get(c).map(|p| p.state) {
+                Some(State::Zombie(termination)) => Some((*c, termination)),
+                _ => None,
+            });
+
+        let parent_process = self.processes.get_mut(&parent)?;
+
+        if let Some((child, _)) = zombie {
+            parent_process.remove_child(child);
+            self.remove(child);
+
+            Some(Ok(zombie))
+        } else if no_hang {
+            Some(Ok(None))
         } else {
-            *slot = Some(process);
+            parent_process.state = State::Waiting(pid);
+
+            None
         }
     }
+
+    fn pids(&self) -> Vec<Pid> {
+        self.processes.keys().copied().collect()
+    }
 }
@@ -1,13 +1,49 @@
 pub(crate) mod manager;
 
-use pid::Pid;
+use {allocator::Vec, pid::Pid, syscalls::Termination};
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct Process {
     pid: Pid,
+    // `None` if the process is created by the kernel, or the parent has exited.
+    parent: Option<Pid>,
+    children: Vec<Pid>,
+    state: State,
 }
 impl Process {
-    fn new(pid: Pid) -> Self {
-        Self { pid }
+    fn new(pid: Pid, parent: Option<Pid>) -> Self {
+        Self {
+            pid,
+            parent,
+            children: Vec::new(),
+            state: State::Alive,
+        }
     }
+
+    fn has_child(&self, pid: Option<Pid>) -> bool {
+        match pid {
+            Some(pid) => self.children.contains(&pid),
+            None => !self.children.is_empty(),
+        }
+    }
+
+    fn remove_child(&mut self, pid: Pid) {
+        self.children.retain(|c| *c != pid);
+    }
+
+    fn is_waiting_for(&self, child: Pid) -> bool {
+        match self.state {
+            State::Waiting(pid) => pid.is_none_or(|pid| pid == child),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum State {
+    Alive,
+    // Waiting for the child process, or any child process if `None`, to terminate.
+    Waiting(Option<Pid>),
+    // Terminated, but not reaped by the parent yet.
+    Zombie(Termination),
 }
@@ -2,6 +2,10 @@
 
 extern crate rlibc as _;
 
+// `process::manager` keeps the processes which PM sends at the start in the heap.
+#[global_allocator]
+static ALLOCATOR: allocator::Allocator = allocator::Allocator;
+
 mod process;
 
 pub fn init() {
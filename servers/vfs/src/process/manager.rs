@@ -1,12 +1,12 @@
 use {
     super::Process,
-    config::MAX_PID,
+    allocator::BTreeMap,
     core::convert::TryInto,
     pid::{predefined, Pid},
     spinning_top::{const_spinlock, Spinlock, SpinlockGuard},
 };
 
-static MANAGER: Spinlock<Manager<MAX_PID>> = const_spinlock(Manager::new());
+static MANAGER: Spinlock<Manager> = const_spinlock(Manager::new());
 
 pub(crate) fn init() {
     const PROC_INFO: u64 = 1;
@@ -25,30 +25,24 @@ pub(crate) fn init() {
     }
 }
 
-fn lock<'a>() -> SpinlockGuard<'a, Manager<MAX_PID>> {
+fn lock<'a>() -> SpinlockGuard<'a, Manager> {
     MANAGER.try_lock().expect("Failed to lock `MANAGER`.")
 }
 
-struct Manager<const N: usize> {
-    processes: [Option<Process>; N],
+struct Manager {
+    processes: BTreeMap<Pid, Process>,
 }
-impl<const N: usize> Manager<N> {
+impl Manager {
     const fn new() -> Self {
-        const NULL_SLOT: Option<Process> = None;
-
         Self {
-            processes: [NULL_SLOT; N],
+            processes: BTreeMap::new(),
         }
     }
 
     fn add(&mut self, process: Process) {
         let pid = process.pid;
 
-        let slot = &mut self.processes[pid.as_usize()];
-
-        if slot.is_none() {
-            *slot = Some(process);
-        } else {
+        if self.processes.insert(pid, process).is_some() {
             panic!("Duplicated process with {}", pid);
         }
     }
@@ -2,6 +2,152 @@
 
 extern crate rlibc as _;
 
+use {
+    core::convert::TryInto,
+    ipc::{
+        message::{Body, Header},
+        Message, ReceiveFrom,
+    },
+    num_traits::FromPrimitive,
+    os_units::{Bytes, NumOfPages},
+    pid::{predefined, Pid},
+    syscalls::{ForkError, MemoryError, Protection},
+    x86_64::{
+        structures::paging::{PageSize, Size4KiB},
+        VirtAddr,
+    },
+};
+
+pub fn main_loop() -> ! {
+    loop {
+        loop_iteration();
+    }
+}
+
+fn loop_iteration() {
+    let message = ipc::receive(ReceiveFrom::Any);
+
+    match FromPrimitive::from_u64(message.body.0) {
+        Some(syscalls::Ty::ForkProcess) => handle_fork_process(&message),
+        Some(syscalls::Ty::Brk) => handle_memory_request(&message, brk),
+        Some(syscalls::Ty::Mmap) => handle_memory_request(&message, mmap),
+        Some(syscalls::Ty::Munmap) => handle_memory_request(&message, munmap),
+        Some(syscalls::Ty::Mprotect) => handle_memory_request(&message, mprotect),
+        _ => {}
+    }
+}
+
+// The kernel shares the pages of the parent with the child, and copies them when they are written.
+fn handle_fork_process(message: &Message) {
+    let body = match fork_process(message) {
+        Ok(pid) => Body(0, pid.as_usize().try_into().unwrap(), 0, 0, 0),
+        Err(e) => Body(e as _, 0, 0, 0, 0),
+    };
+
+    ipc::send(
+        message.header.sender_pid,
+        Message {
+            header: Header::default(),
+            body,
+        },
+    );
+}
+
+fn fork_process(message: &Message) -> Result<Pid, ForkError> {
+    if message.header.sender_pid != predefined::PM {
+        return Err(ForkError::PermissionDenied);
+    }
+
+    let parent = message.body.1.try_into().ok().map(Pid::new);
+    let parent = parent.ok_or(ForkError::CreationFailed)?;
+
+    syscalls::duplicate_process(parent)
+}
+
+// `f` receives the PID of the sender and the message body, and returns the value to reply in the
+// second field.
+fn handle_memory_request(
+    message: &Message,
+    f: impl FnOnce(Pid, &Body) -> Result<u64, MemoryError>,
+) {
+    let body = match f(message.header.sender_pid, &message.body) {
+        Ok(value) => Body(0, value, 0, 0, 0),
+        Err(e) => Body(e as _, 0, 0, 0, 0),
+    };
+
+    ipc::send(
+        message.header.sender_pid,
+        Message {
+            header: Header::default(),
+            body,
+        },
+    );
+}
+
+// The kernel maps the pages of the heap when they are accessed, so this function only moves the
+// end of the heap.
+fn brk(pid: Pid, body: &Body) -> Result<u64, MemoryError> {
+    // `0` means that the heap is not changed.
+    let end = match body.1 {
+        0 => None,
+        end => Some(page_align_up(end)?),
+    };
+
+    syscalls::resize_heap(pid, end).map(VirtAddr::as_u64)
+}
+
+fn mmap(pid: Pid, body: &Body) -> Result<u64, MemoryError> {
+    let len = Bytes::new(
+        body.1
+            .try_into()
+            .map_err(|_| MemoryError::InvalidArguments)?,
+    );
+    let protection = Protection::from_bits(body.2).ok_or(MemoryError::InvalidArguments)?;
+
+    if len.as_usize() == 0 {
+        return Err(MemoryError::InvalidArguments);
+    }
+
+    syscalls::map_anonymous(pid, len.as_num_of_pages(), protection).map(VirtAddr::as_u64)
+}
+
+fn munmap(pid: Pid, body: &Body) -> Result<u64, MemoryError> {
+    let (start, num_of_pages) = page_range(body.1, body.2)?;
+
+    syscalls::unmap_anonymous(pid, start, num_of_pages).map(|()| 0)
+}
+
+fn mprotect(pid: Pid, body: &Body) -> Result<u64, MemoryError> {
+    let (start, num_of_pages) = page_range(body.1, body.2)?;
+    let protection = Protection::from_bits(body.3).ok_or(MemoryError::InvalidArguments)?;
+
+    syscalls::protect_anonymous(pid, start, num_of_pages, protection).map(|()| 0)
+}
+
+// Returns the start address and the number of pages of `len` bytes from `start`. `start` must be
+// page-aligned, and `len` must not be zero.
+fn page_range(start: u64, len: u64) -> Result<(VirtAddr, NumOfPages<Size4KiB>), MemoryError> {
+    let start = VirtAddr::try_new(start).map_err(|_| MemoryError::InvalidAddress)?;
+    let len = Bytes::new(len.try_into().map_err(|_| MemoryError::InvalidArguments)?);
+
+    if !start.is_aligned(Size4KiB::SIZE) {
+        return Err(MemoryError::InvalidAddress);
+    }
+
+    if len.as_usize() == 0 {
+        return Err(MemoryError::InvalidArguments);
+    }
+
+    Ok((start, len.as_num_of_pages()))
+}
+
+fn page_align_up(addr: u64) -> Result<VirtAddr, MemoryError> {
+    let addr = addr.checked_add(Size4KiB::SIZE - 1);
+    let addr = addr.ok_or(MemoryError::InvalidAddress)?;
+
+    VirtAddr::try_new(addr & !(Size4KiB::SIZE - 1)).map_err(|_| MemoryError::InvalidAddress)
+}
+
 #[panic_handler]
 fn panic(_: &core::panic::PanicInfo<'_>) -> ! {
     loop {
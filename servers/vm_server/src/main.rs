@@ -3,11 +3,7 @@
 
 extern crate vm_server as _;
 
-use ipc::ReceiveFrom;
-
 #[no_mangle]
 fn main() -> ! {
-    loop {
-        let _ = ipc::receive(ReceiveFrom::Any);
-    }
+    vm_server::main_loop();
 }